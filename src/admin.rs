@@ -0,0 +1,46 @@
+use crate::state::AppState;
+use crate::util::parse_query;
+use axum::{
+    body::{Full, boxed},
+    http::{Method, StatusCode, Uri},
+    response::Response,
+};
+use serde_json::{Value, json};
+
+/// Paths under this prefix control the server instead of being served
+pub const ADMIN_PREFIX: &str = "/__rustrate/";
+
+fn json_response(status: StatusCode, value: Value) -> Response {
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(boxed(Full::from(value.to_string())))
+        .unwrap()
+}
+
+/// Handle an admin request. These are not counted, logged, delayed or limited.
+///
+/// - `GET /__rustrate/sequences`: the request counters of the sequences
+/// - `POST /__rustrate/sequences/reset[?name=<sequence>]`: start sequences over
+pub fn admin_handler(state: &AppState, method: &Method, uri: &Uri) -> Response {
+    let path = &uri.path()[ADMIN_PREFIX.len()..];
+    match (method, path) {
+        (&Method::GET, "sequences") => json_response(
+            StatusCode::OK,
+            json!({ "sequences": state.sequences.counters() }),
+        ),
+        (&Method::POST, "sequences/reset") => {
+            let query = parse_query(uri.query().unwrap_or(""));
+            match state.sequences.reset(query.get("name").map(String::as_str)) {
+                Ok(reset) => json_response(StatusCode::OK, json!({ "reset": reset })),
+                Err(err) => {
+                    json_response(StatusCode::NOT_FOUND, json!({ "error": err.to_string() }))
+                }
+            }
+        }
+        _ => json_response(
+            StatusCode::NOT_FOUND,
+            json!({ "error": format!("Unknown admin endpoint {} {}", method, uri.path()) }),
+        ),
+    }
+}
@@ -0,0 +1,112 @@
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+#[cfg(test)]
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A source of time for the handler, statistics, scenarios, throttling and listeners, so
+/// timing logic can be tested without waiting
+pub trait Clock: Send + Sync + std::fmt::Debug {
+    /// Monotonic time, for measuring durations
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time, for timestamps
+    fn wall(&self) -> DateTime<Utc>;
+
+    /// Wait for `duration`
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
+
+/// The clock of a running server.
+///
+/// Monotonic time and sleeps go through tokio, so tests with paused time
+/// (`#[tokio::test(start_paused = true)]`) control them too.
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn wall(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// A clock that only moves when told to. Sleeping advances it instead of waiting.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Instant,
+    wall_start: DateTime<Utc>,
+    elapsed: Mutex<Duration>,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    /// A clock stopped at the given wall-clock time
+    pub fn new(wall_start: DateTime<Utc>) -> Self {
+        Self {
+            start: Instant::now(),
+            wall_start,
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+
+    fn wall(&self) -> DateTime<Utc> {
+        self.wall_start + *self.elapsed.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        self.advance(duration);
+        Box::pin(std::future::ready(()))
+    }
+}
+
+/// The clock used unless one is injected
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_manual_clock_moves_when_told() {
+        let clock = ManualClock::new(DateTime::from_timestamp(1_000, 0).unwrap());
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+        clock.advance(Duration::from_secs(2));
+        clock.sleep(Duration::from_millis(500)).await;
+        assert_eq!(clock.now() - start, Duration::from_millis(2500));
+        assert_eq!(clock.wall().timestamp_millis(), 1_002_500);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_system_clock_follows_paused_time() {
+        let clock = SystemClock;
+        let start = clock.now();
+        clock.sleep(Duration::from_secs(30)).await;
+        assert_eq!(clock.now() - start, Duration::from_secs(30));
+    }
+}
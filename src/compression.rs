@@ -0,0 +1,432 @@
+use crate::formats::parse_quality_list;
+use anyhow::anyhow;
+use axum::{
+    body::{Bytes, HttpBody, boxed},
+    http::{HeaderMap, HeaderValue, Method, header},
+    response::Response,
+};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll, ready};
+
+/// A content coding of response bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Identity,
+    Brotli,
+    Zstd,
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    /// All encodings, compressed ones in the order the server prefers them
+    pub const ALL: [Encoding; 5] = [
+        Encoding::Identity,
+        Encoding::Brotli,
+        Encoding::Zstd,
+        Encoding::Gzip,
+        Encoding::Deflate,
+    ];
+
+    /// The `Content-Encoding` token
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Identity => "identity",
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl std::str::FromStr for Encoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Encoding::ALL
+            .into_iter()
+            .find(|encoding| encoding.name() == s.to_lowercase())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Invalid encoding '{}'. Valid encodings: identity, br, zstd, gzip, deflate",
+                    s
+                )
+            })
+    }
+}
+
+/// Whether and how responses are compressed
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CompressionMode {
+    /// Compress with the best encoding the client accepts
+    #[default]
+    Auto,
+    /// Never compress
+    Off,
+    /// Always use this encoding, whatever the client accepts
+    Force(Encoding),
+    /// Claim this encoding in `Content-Encoding` but send the body uncompressed
+    Mislabel(Encoding),
+}
+
+impl std::str::FromStr for CompressionMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(CompressionMode::Auto),
+            "off" => Ok(CompressionMode::Off),
+            other => match other.strip_prefix("mislabel:") {
+                Some(encoding) => Ok(CompressionMode::Mislabel(encoding.parse()?)),
+                None => other.parse().map(CompressionMode::Force).map_err(|_| {
+                    anyhow!(
+                        "Invalid compression. Valid values: auto, off, br, zstd, gzip, deflate, mislabel:<encoding>"
+                    )
+                }),
+            },
+        }
+    }
+}
+
+/// Pick the best encoding the client accepts; ties go to the server's preference
+pub fn negotiate(accept_encoding: Option<&str>) -> Encoding {
+    let accepted = parse_quality_list(accept_encoding.unwrap_or(""));
+    let quality = |encoding: Encoding| {
+        accepted
+            .iter()
+            .find(|(name, _)| name == encoding.name())
+            .or_else(|| accepted.iter().find(|(name, _)| name == "*"))
+            .map_or(0.0, |(_, quality)| *quality)
+    };
+    let mut best = (Encoding::Identity, 0.0);
+    for encoding in &Encoding::ALL[1..] {
+        let q = quality(*encoding);
+        if q > best.1 {
+            best = (*encoding, q);
+        }
+    }
+    best.0
+}
+
+/// Responses and bytes per encoding
+#[derive(Debug, Default)]
+pub struct EncodingStats {
+    pub responses: AtomicU64,
+    /// Body bytes before compression
+    pub raw_bytes: AtomicU64,
+    /// Body bytes after compression
+    pub encoded_bytes: AtomicU64,
+}
+
+/// Compression statistics of one listener, updated as bodies are streamed
+#[derive(Debug, Default)]
+pub struct CompressionStats {
+    by_encoding: [EncodingStats; 5],
+}
+
+impl CompressionStats {
+    pub fn get(&self, encoding: Encoding) -> &EncodingStats {
+        &self.by_encoding[encoding.index()]
+    }
+}
+
+/// A streaming compressor writing into memory
+enum Compressor {
+    Brotli(Box<brotli::CompressorWriter<Vec<u8>>>),
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+impl Compressor {
+    fn new(encoding: Encoding) -> Option<Self> {
+        let level = flate2::Compression::default();
+        Some(match encoding {
+            Encoding::Identity => return None,
+            Encoding::Brotli => Compressor::Brotli(Box::new(brotli::CompressorWriter::new(
+                Vec::new(),
+                4096,
+                5,
+                22,
+            ))),
+            Encoding::Zstd => {
+                Compressor::Zstd(zstd::stream::write::Encoder::new(Vec::new(), 3).ok()?)
+            }
+            Encoding::Gzip => Compressor::Gzip(GzEncoder::new(Vec::new(), level)),
+            Encoding::Deflate => Compressor::Deflate(ZlibEncoder::new(Vec::new(), level)),
+        })
+    }
+
+    /// Compress a chunk, returning the output produced so far
+    fn write(&mut self, data: &[u8]) -> Bytes {
+        // Writing into a Vec can't fail
+        let output = match self {
+            Compressor::Brotli(writer) => {
+                let _ = writer.write_all(data);
+                writer.get_mut()
+            }
+            Compressor::Zstd(writer) => {
+                let _ = writer.write_all(data);
+                writer.get_mut()
+            }
+            Compressor::Gzip(writer) => {
+                let _ = writer.write_all(data);
+                writer.get_mut()
+            }
+            Compressor::Deflate(writer) => {
+                let _ = writer.write_all(data);
+                writer.get_mut()
+            }
+        };
+        Bytes::from(std::mem::take(output))
+    }
+
+    /// End the stream, returning the remaining output
+    fn finish(self) -> Bytes {
+        Bytes::from(match self {
+            Compressor::Brotli(writer) => writer.into_inner(),
+            Compressor::Zstd(writer) => writer.finish().unwrap_or_default(),
+            Compressor::Gzip(writer) => writer.finish().unwrap_or_default(),
+            Compressor::Deflate(writer) => writer.finish().unwrap_or_default(),
+        })
+    }
+}
+
+/// Wraps a body and compresses it on the fly
+pub struct CompressedBody<B> {
+    inner: B,
+    compressor: Option<Compressor>,
+    stats: Arc<CompressionStats>,
+    encoding: Encoding,
+}
+
+impl<B> HttpBody for CompressedBody<B>
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        let stats = this.stats.get(this.encoding);
+        loop {
+            let Some(compressor) = &mut this.compressor else {
+                return Poll::Ready(None);
+            };
+            let output = match ready!(Pin::new(&mut this.inner).poll_data(cx)) {
+                Some(Ok(chunk)) => {
+                    stats
+                        .raw_bytes
+                        .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                    compressor.write(&chunk)
+                }
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => this
+                    .compressor
+                    .take()
+                    .map(Compressor::finish)
+                    .unwrap_or_default(),
+            };
+            if !output.is_empty() {
+                stats
+                    .encoded_bytes
+                    .fetch_add(output.len() as u64, Ordering::Relaxed);
+                return Poll::Ready(Some(Ok(output)));
+            }
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.compressor.is_none()
+    }
+}
+
+/// Whether a response can be compressed
+fn is_compressible(method: &Method, response: &Response) -> bool {
+    let status = response.status();
+    let grpc = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/grpc"));
+    method != Method::HEAD
+        && !status.is_informational()
+        && status.as_u16() != 204
+        && status.as_u16() != 304
+        && !response.headers().contains_key(header::CONTENT_ENCODING)
+        && !grpc
+}
+
+/// Compress a response according to the mode and the client's `Accept-Encoding`
+pub fn compress(
+    response: Response,
+    mode: CompressionMode,
+    method: &Method,
+    accept_encoding: Option<&str>,
+    stats: &Arc<CompressionStats>,
+) -> Response {
+    if mode == CompressionMode::Off || !is_compressible(method, &response) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let (encoding, label) = match mode {
+        CompressionMode::Auto => {
+            parts
+                .headers
+                .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+            let encoding = negotiate(accept_encoding);
+            (encoding, encoding)
+        }
+        CompressionMode::Force(encoding) => (encoding, encoding),
+        CompressionMode::Mislabel(label) => (Encoding::Identity, label),
+        CompressionMode::Off => unreachable!(),
+    };
+    stats
+        .get(encoding)
+        .responses
+        .fetch_add(1, Ordering::Relaxed);
+    if label != Encoding::Identity {
+        parts.headers.insert(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static(label.name()),
+        );
+    }
+    let Some(compressor) = Compressor::new(encoding) else {
+        return Response::from_parts(parts, body);
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let body = CompressedBody {
+        inner: body,
+        compressor: Some(compressor),
+        stats: stats.clone(),
+        encoding,
+    };
+    Response::from_parts(parts, boxed(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use std::io::Read;
+
+    #[test]
+    fn test_negotiate_encoding() {
+        assert_eq!(negotiate(None), Encoding::Identity);
+        assert_eq!(negotiate(Some("gzip, deflate")), Encoding::Gzip);
+        assert_eq!(negotiate(Some("gzip, deflate, br, zstd")), Encoding::Brotli);
+        assert_eq!(negotiate(Some("br;q=0.5, deflate")), Encoding::Deflate);
+        assert_eq!(negotiate(Some("*;q=0.1, zstd;q=0")), Encoding::Brotli);
+        assert_eq!(negotiate(Some("compress")), Encoding::Identity);
+    }
+
+    #[test]
+    fn test_parse_compression_mode() {
+        assert_eq!(
+            "auto".parse::<CompressionMode>().unwrap(),
+            CompressionMode::Auto
+        );
+        assert_eq!(
+            "GZIP".parse::<CompressionMode>().unwrap(),
+            CompressionMode::Force(Encoding::Gzip)
+        );
+        assert_eq!(
+            "mislabel:br".parse::<CompressionMode>().unwrap(),
+            CompressionMode::Mislabel(Encoding::Brotli)
+        );
+        assert!("lzma".parse::<CompressionMode>().is_err());
+        assert!("mislabel:lzma".parse::<CompressionMode>().is_err());
+    }
+
+    async fn compressed(encoding: &str, body: &'static str) -> (Response, Vec<u8>) {
+        let response = Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_LENGTH, body.len())
+            .body(boxed(body.to_string()))
+            .unwrap();
+        let stats = Arc::new(CompressionStats::default());
+        let response = compress(
+            response,
+            CompressionMode::Auto,
+            &Method::GET,
+            Some(encoding),
+            &stats,
+        );
+        let (parts, body) = response.into_parts();
+        let bytes = hyper::body::to_bytes(body).await.unwrap().to_vec();
+        (Response::from_parts(parts, boxed(String::new())), bytes)
+    }
+
+    #[tokio::test]
+    async fn test_compressed_bodies_decode() {
+        let text = "hello hello hello hello hello hello hello hello";
+        let mut decoded = String::new();
+
+        let (response, bytes) = compressed("gzip", text).await;
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert!(!response.headers().contains_key(header::CONTENT_LENGTH));
+        flate2::read::GzDecoder::new(&bytes[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, text);
+
+        let (_, bytes) = compressed("deflate", text).await;
+        decoded.clear();
+        flate2::read::ZlibDecoder::new(&bytes[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, text);
+
+        let (_, bytes) = compressed("br", text).await;
+        decoded.clear();
+        brotli::Decompressor::new(&bytes[..], 4096)
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, text);
+
+        let (_, bytes) = compressed("zstd", text).await;
+        assert_eq!(zstd::decode_all(&bytes[..]).unwrap(), text.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_mislabeled_body_is_not_compressed() {
+        let stats = Arc::new(CompressionStats::default());
+        let response = compress(
+            Response::new(boxed("plain".to_string())),
+            CompressionMode::Mislabel(Encoding::Gzip),
+            &Method::GET,
+            None,
+            &stats,
+        );
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(bytes, "plain");
+        assert_eq!(
+            stats
+                .get(Encoding::Identity)
+                .responses
+                .load(Ordering::Relaxed),
+            1
+        );
+    }
+}
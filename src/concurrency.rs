@@ -0,0 +1,241 @@
+use anyhow::anyhow;
+use axum::{
+    body::{Bytes, HttpBody},
+    http::HeaderMap,
+};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// What happens to a request that arrives when every worker is busy and the queue is full
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum QueueOverflow {
+    /// Reply `503 Service Unavailable`
+    #[default]
+    Reject,
+    /// Close the connection without a response
+    Refuse,
+    /// Wait for a worker anyway; the queue has no bound
+    Wait,
+}
+
+impl std::str::FromStr for QueueOverflow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "503" | "reject" => Ok(Self::Reject),
+            "refuse" => Ok(Self::Refuse),
+            "wait" => Ok(Self::Wait),
+            _ => Err(anyhow!(
+                "Invalid queue overflow '{}'. Valid values: 503, refuse, wait",
+                s
+            )),
+        }
+    }
+}
+
+/// A bounded pool of workers with a queue in front of it
+#[derive(Debug)]
+pub struct ConcurrencyLimit {
+    pub max: usize,
+    pub queue_size: usize,
+    pub overflow: QueueOverflow,
+    workers: Arc<Semaphore>,
+    /// Requests being served
+    pub in_flight: AtomicU64,
+    /// Requests waiting for a worker
+    pub queued: AtomicU64,
+    /// Requests turned away because the queue was full
+    pub rejected: AtomicU64,
+}
+
+/// A busy worker, released when dropped
+#[derive(Debug)]
+pub struct Worker {
+    limit: Arc<ConcurrencyLimit>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.limit.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Counts a request as queued until dropped, including when the client gives up waiting
+struct QueuedGuard<'a>(&'a AtomicU64);
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl ConcurrencyLimit {
+    pub fn new(max: usize, queue_size: usize, overflow: QueueOverflow) -> anyhow::Result<Self> {
+        if max == 0 {
+            return Err(anyhow!("Max concurrency must be at least 1"));
+        }
+        Ok(Self {
+            max,
+            queue_size,
+            overflow,
+            workers: Arc::new(Semaphore::new(max)),
+            in_flight: AtomicU64::new(0),
+            queued: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        })
+    }
+
+    /// Wait for a free worker in arrival order. `None` if the queue is full.
+    pub async fn acquire(self: &Arc<Self>) -> Option<Worker> {
+        let permit = match self.workers.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                let ahead = self.queued.fetch_add(1, Ordering::Relaxed);
+                let _queued = QueuedGuard(&self.queued);
+                if self.overflow != QueueOverflow::Wait && ahead >= self.queue_size as u64 {
+                    self.rejected.fetch_add(1, Ordering::Relaxed);
+                    return None;
+                }
+                // The semaphore is never closed
+                self.workers.clone().acquire_owned().await.ok()?
+            }
+        };
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        Some(Worker {
+            limit: self.clone(),
+            _permit: permit,
+        })
+    }
+}
+
+/// Keeps a worker busy until the response body has been sent
+pub struct WorkerBody<B> {
+    inner: B,
+    _worker: Worker,
+}
+
+impl<B> WorkerBody<B> {
+    pub fn new(inner: B, worker: Worker) -> Self {
+        Self {
+            inner,
+            _worker: worker,
+        }
+    }
+}
+
+impl<B> HttpBody for WorkerBody<B>
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Pin::new(&mut self.get_mut().inner).poll_data(cx)
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// A body that fails before sending anything, so the server closes the
+/// connection without writing a response
+pub struct RefusedBody;
+
+impl HttpBody for RefusedBody {
+    type Data = Bytes;
+    type Error = std::io::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Poll::Ready(Some(Err(std::io::Error::new(
+            std::io::ErrorKind::ConnectionAborted,
+            "request queue full",
+        ))))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_overflow() {
+        assert_eq!(
+            "503".parse::<QueueOverflow>().unwrap(),
+            QueueOverflow::Reject
+        );
+        assert_eq!(
+            "refuse".parse::<QueueOverflow>().unwrap(),
+            QueueOverflow::Refuse
+        );
+        assert!("drop".parse::<QueueOverflow>().is_err());
+        assert!(ConcurrencyLimit::new(0, 10, QueueOverflow::Wait).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_queue_bounds_and_gauges() {
+        let limit = Arc::new(ConcurrencyLimit::new(1, 1, QueueOverflow::Reject).unwrap());
+        let worker = limit.acquire().await.unwrap();
+        assert_eq!(limit.in_flight.load(Ordering::Relaxed), 1);
+
+        // The second request queues behind the first, the third finds the queue full
+        let queued = tokio::spawn({
+            let limit = limit.clone();
+            async move { limit.acquire().await.is_some() }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(limit.queued.load(Ordering::Relaxed), 1);
+        assert!(limit.acquire().await.is_none());
+        assert_eq!(limit.rejected.load(Ordering::Relaxed), 1);
+
+        drop(worker);
+        assert!(queued.await.unwrap());
+        assert_eq!(limit.queued.load(Ordering::Relaxed), 0);
+        assert_eq!(limit.in_flight.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_wait_has_no_queue_bound() {
+        let limit = Arc::new(ConcurrencyLimit::new(1, 0, QueueOverflow::Wait).unwrap());
+        let worker = limit.acquire().await.unwrap();
+        let waiting = tokio::spawn({
+            let limit = limit.clone();
+            async move { limit.acquire().await.is_some() }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(limit.queued.load(Ordering::Relaxed), 1);
+        drop(worker);
+        assert!(waiting.await.unwrap());
+        assert_eq!(limit.rejected.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,291 @@
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Optional configuration file loaded with `--config`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// gRPC methods served over h2
+    pub grpc: Vec<GrpcMethodConfig>,
+    /// Panels of the TUI dashboard and their sizes, offered as the `custom` layout
+    pub layout: Option<LayoutConfig>,
+    /// Listeners to bind instead of the single `--port` listener
+    pub listeners: Vec<ListenerConfig>,
+    /// Templated response replacing the built-in JSON/text body
+    pub response: Option<ResponseConfig>,
+    /// Per-path bandwidth limits, matched by longest prefix
+    pub routes: Vec<RouteConfig>,
+    /// Scripted responses by request count, for retry tests
+    pub sequences: Vec<SequenceConfig>,
+}
+
+impl Config {
+    /// Load the configuration from a JSON file
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        Self::parse(&contents)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// Parse the configuration from a JSON string
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(contents)?)
+    }
+}
+
+/// The shape of a gRPC method
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrpcMethodKind {
+    #[default]
+    Unary,
+    ServerStreaming,
+    ClientStreaming,
+    BidiStreaming,
+}
+
+/// A gRPC method declared in the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GrpcMethodConfig {
+    /// Fully qualified service name, e.g. `helloworld.Greeter`
+    pub service: String,
+    /// Method name, e.g. `SayHello`
+    pub method: String,
+    #[serde(default)]
+    pub kind: GrpcMethodKind,
+    /// Raw protobuf-encoded response message as hex (empty message if unset)
+    #[serde(default)]
+    pub response_hex: Option<String>,
+    /// Number of messages sent by a server streaming method
+    #[serde(default = "default_messages")]
+    pub messages: u32,
+}
+
+fn default_messages() -> u32 {
+    1
+}
+
+/// A listener declared in the config file.
+///
+/// Unset profile fields fall back to the command-line options.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerConfig {
+    /// Name shown in the TUI (defaults to the bind address)
+    #[serde(default)]
+    pub name: Option<String>,
+    /// `host:port`, `[ipv6]:port` or `unix:/path/to.sock`
+    pub bind: String,
+    #[serde(default)]
+    pub delay: Option<String>,
+    #[serde(default)]
+    pub error_rate: Option<f64>,
+    #[serde(default)]
+    pub error_status: Option<u16>,
+    #[serde(default)]
+    pub format: Option<String>,
+    /// Upstream URL to proxy to instead of synthesizing responses
+    #[serde(default)]
+    pub upstream: Option<String>,
+    #[serde(default)]
+    pub response: Option<ResponseConfig>,
+    /// Size of generated response bodies, as for `--body-size`
+    #[serde(default)]
+    pub body_size: Option<String>,
+    /// Content of generated response bodies, as for `--payload`
+    #[serde(default)]
+    pub payload: Option<String>,
+    #[serde(default)]
+    pub bandwidth: Option<String>,
+    #[serde(default)]
+    pub trickle: Option<String>,
+    #[serde(default)]
+    pub upload_bandwidth: Option<String>,
+    #[serde(default)]
+    pub compression: Option<String>,
+    /// Rate limit, as for `--rate-limit`; the listener counts its requests separately
+    #[serde(default)]
+    pub rate_limit: Option<String>,
+    #[serde(default)]
+    pub rate_limit_algorithm: Option<String>,
+    #[serde(default)]
+    pub rate_limit_key: Option<String>,
+    /// Worker pool size, as for `--max-concurrency`
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+    #[serde(default)]
+    pub queue_size: Option<usize>,
+    #[serde(default)]
+    pub queue_overflow: Option<String>,
+    /// Load-dependent latency, as for `--load-latency`
+    #[serde(default)]
+    pub load_latency: Option<String>,
+    #[serde(default)]
+    pub load_metric: Option<String>,
+    #[serde(default)]
+    pub overload_threshold: Option<f64>,
+}
+
+/// Bandwidth limits for requests whose path starts with `path`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteConfig {
+    pub path: String,
+    #[serde(default)]
+    pub bandwidth: Option<String>,
+    #[serde(default)]
+    pub trickle: Option<String>,
+    #[serde(default)]
+    pub upload_bandwidth: Option<String>,
+}
+
+/// Responses to successive requests to a path, e.g. two 503s and then a success
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SequenceConfig {
+    /// Name shown in the TUI and used to reset the sequence (defaults to `METHOD path`)
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Method to match (any if unset)
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Exact path to match
+    pub path: String,
+    /// What requests share a counter, as for `--rate-limit-key` (default: global)
+    #[serde(default)]
+    pub key: Option<String>,
+    /// `ok`, an error status, `timeout` or `drop`, one per request
+    pub steps: Vec<String>,
+    /// Start over after the last step instead of serving requests normally
+    #[serde(default)]
+    pub repeat: bool,
+}
+
+/// Rows of TUI panels, top to bottom
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutConfig {
+    pub rows: Vec<LayoutRowConfig>,
+}
+
+/// A row of TUI panels, left to right. Rows without a size share the remaining height.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutRowConfig {
+    /// Height as a percentage of the terminal
+    #[serde(default)]
+    pub height: Option<u16>,
+    /// Height in terminal lines, instead of a percentage
+    #[serde(default)]
+    pub lines: Option<u16>,
+    pub panels: Vec<LayoutPanelConfig>,
+}
+
+/// A TUI panel, e.g. `rps-chart`. Panels without a width share the remaining width.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutPanelConfig {
+    pub panel: String,
+    /// Width as a percentage of the row
+    #[serde(default)]
+    pub width: Option<u16>,
+}
+
+/// A response whose headers and body are MiniJinja templates
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResponseConfig {
+    #[serde(default = "default_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Read the body template from a file instead
+    #[serde(default)]
+    pub body_file: Option<PathBuf>,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_empty_config() {
+        let config = Config::parse("{}").unwrap();
+        assert!(config.grpc.is_empty());
+    }
+
+    #[test]
+    fn test_parse_grpc_methods() {
+        let config = Config::parse(
+            r#"{"grpc": [
+                {"service": "helloworld.Greeter", "method": "SayHello", "response_hex": "0a0568656c6c6f"},
+                {"service": "helloworld.Greeter", "method": "Watch", "kind": "server_streaming", "messages": 5}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.grpc.len(), 2);
+        assert_eq!(config.grpc[0].kind, GrpcMethodKind::Unary);
+        assert_eq!(config.grpc[0].messages, 1);
+        assert_eq!(config.grpc[1].kind, GrpcMethodKind::ServerStreaming);
+        assert_eq!(config.grpc[1].messages, 5);
+    }
+
+    #[test]
+    fn test_parse_listeners() {
+        let config = Config::parse(
+            r#"{"listeners": [
+                {"name": "payments", "bind": "127.0.0.1:9001", "delay": "30-150", "error_rate": 5},
+                {"bind": "unix:/tmp/users.sock", "format": "text"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.listeners.len(), 2);
+        assert_eq!(config.listeners[0].name.as_deref(), Some("payments"));
+        assert_eq!(config.listeners[0].error_rate, Some(5.0));
+        assert_eq!(config.listeners[1].name, None);
+        assert_eq!(config.listeners[1].format.as_deref(), Some("text"));
+    }
+
+    #[test]
+    fn test_parse_response_template() {
+        let config = Config::parse(
+            r#"{"response": {"headers": {"x-id": "{{ uuid() }}"}, "body": "{{ body.id }}"}}"#,
+        )
+        .unwrap();
+        let response = config.response.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.headers["x-id"], "{{ uuid() }}");
+        assert_eq!(response.body.as_deref(), Some("{{ body.id }}"));
+    }
+
+    #[test]
+    fn test_parse_sequences() {
+        let config = Config::parse(
+            r#"{"sequences": [
+                {"method": "POST", "path": "/pay", "key": "header:idempotency-key", "steps": ["503", "503", "ok"]},
+                {"name": "flaky", "path": "/slow", "steps": ["ok", "ok", "timeout"], "repeat": true}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.sequences.len(), 2);
+        assert_eq!(config.sequences[0].name, None);
+        assert_eq!(config.sequences[0].steps, ["503", "503", "ok"]);
+        assert!(!config.sequences[0].repeat);
+        assert!(config.sequences[1].repeat);
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_fields() {
+        assert!(Config::parse(r#"{"grpcs": []}"#).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use crate::listener::PeerAddr;
+use axum::extract::connect_info::Connected;
+use axum::http::{HeaderMap, HeaderName};
+use std::collections::HashMap;
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpStream, UnixStream};
+
+/// Connection and request counters of a listener
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    /// Connections currently open
+    pub open: AtomicU64,
+    /// Connections accepted since the start
+    pub opened: AtomicU64,
+    /// Connections closed since the start
+    pub closed: AtomicU64,
+    /// Requests received over all connections
+    pub requests: AtomicU64,
+    /// Requests currently being handled
+    pub in_flight: AtomicU64,
+    /// Requests currently being handled per client
+    clients: Mutex<HashMap<String, u64>>,
+}
+
+/// Who sent a request: the value of the identity header if configured and present,
+/// otherwise the peer's IP address (`local` over Unix sockets)
+pub fn client_identity(
+    header: Option<&HeaderName>,
+    headers: &HeaderMap,
+    peer: Option<IpAddr>,
+) -> String {
+    header
+        .and_then(|name| headers.get(name))
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+        .or_else(|| peer.map(|ip| ip.to_string()))
+        .unwrap_or_else(|| "local".to_string())
+}
+
+impl ConnectionStats {
+    /// Count `stream` as open until it is dropped
+    pub fn track<S>(self: &Arc<Self>, stream: S) -> TrackedStream<S> {
+        self.open.fetch_add(1, Ordering::Relaxed);
+        self.opened.fetch_add(1, Ordering::Relaxed);
+        TrackedStream {
+            stream,
+            stats: self.clone(),
+        }
+    }
+
+    /// Count a request from `client`, in flight until the returned guard is dropped
+    pub fn request(self: &Arc<Self>, client: &str) -> RequestGuard {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        *self
+            .clients
+            .lock()
+            .unwrap()
+            .entry(client.to_string())
+            .or_default() += 1;
+        RequestGuard {
+            stats: self.clone(),
+            client: client.to_string(),
+        }
+    }
+
+    /// Requests currently being handled per client, for clients with any
+    pub fn clients_in_flight(&self) -> HashMap<String, u64> {
+        self.clients.lock().unwrap().clone()
+    }
+}
+
+/// Counts a request as in flight until dropped
+pub struct RequestGuard {
+    stats: Arc<ConnectionStats>,
+    client: String,
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        self.stats.in_flight.fetch_sub(1, Ordering::Relaxed);
+        let mut clients = self.stats.clients.lock().unwrap();
+        if let Some(in_flight) = clients.get_mut(&self.client) {
+            *in_flight -= 1;
+            if *in_flight == 0 {
+                clients.remove(&self.client);
+            }
+        }
+    }
+}
+
+/// An accepted connection, counted as open until dropped
+pub struct TrackedStream<S> {
+    stream: S,
+    stats: Arc<ConnectionStats>,
+}
+
+impl<S> Drop for TrackedStream<S> {
+    fn drop(&mut self) {
+        self.stats.open.fetch_sub(1, Ordering::Relaxed);
+        self.stats.closed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for TrackedStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for TrackedStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+}
+
+impl Connected<&TrackedStream<TcpStream>> for PeerAddr {
+    fn connect_info(stream: &TrackedStream<TcpStream>) -> Self {
+        PeerAddr::connect_info(&stream.stream)
+    }
+}
+
+impl Connected<&TrackedStream<UnixStream>> for PeerAddr {
+    fn connect_info(stream: &TrackedStream<UnixStream>) -> Self {
+        PeerAddr::connect_info(&stream.stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_tracked_stream_counts_connections() {
+        let stats = Arc::new(ConnectionStats::default());
+        let (client, server) = tokio::io::duplex(64);
+        let mut server = stats.track(server);
+        let mut client = stats.track(client);
+        assert_eq!(stats.open.load(Ordering::Relaxed), 2);
+
+        // Data passes through unchanged
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0; 4];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+
+        drop(client);
+        assert_eq!(stats.open.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.opened.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.closed.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_requests_in_flight() {
+        let stats = Arc::new(ConnectionStats::default());
+        let first = stats.request("10.0.0.1");
+        let second = stats.request("10.0.0.1");
+        let third = stats.request("billing");
+        assert_eq!(stats.in_flight.load(Ordering::Relaxed), 3);
+        assert_eq!(stats.clients_in_flight()["10.0.0.1"], 2);
+        drop(first);
+        drop(third);
+        // Clients without requests in flight are forgotten
+        assert_eq!(
+            stats.clients_in_flight(),
+            HashMap::from([("10.0.0.1".to_string(), 1)])
+        );
+        drop(second);
+        assert_eq!(stats.in_flight.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.requests.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_client_identity() {
+        let header = HeaderName::from_static("x-client-id");
+        let mut headers = HeaderMap::new();
+        let peer = Some(IpAddr::from([10, 0, 0, 7]));
+        assert_eq!(client_identity(Some(&header), &headers, peer), "10.0.0.7");
+        headers.insert("x-client-id", "billing".parse().unwrap());
+        assert_eq!(client_identity(Some(&header), &headers, peer), "billing");
+        assert_eq!(client_identity(None, &headers, peer), "10.0.0.7");
+        assert_eq!(client_identity(None, &headers, None), "local");
+    }
+}
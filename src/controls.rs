@@ -0,0 +1,144 @@
+use crate::OutputFormat;
+use crate::routes::DelayConfig;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// How a paused listener treats requests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Pause {
+    #[default]
+    Off,
+    /// Answer every request with a 503
+    Unavailable,
+    /// Close connections and stop accepting new ones, as in a scenario outage
+    Refused,
+}
+
+impl Pause {
+    const ALL: [Pause; 3] = [Pause::Off, Pause::Unavailable, Pause::Refused];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pause::Off => "off",
+            Pause::Unavailable => "503",
+            Pause::Refused => "refusing connections",
+        }
+    }
+}
+
+/// A change the TUI asks the server to make while it runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Shift the delay by this many milliseconds
+    AdjustDelay(i64),
+    /// Replace the delay with `min`-`max` milliseconds
+    SetDelay(u64, u64),
+    /// Switch injected errors off, or back on
+    ToggleErrors,
+    /// Go from serving to 503s, to refusing connections, and back
+    CyclePause,
+    /// Switch the default response format to the next one
+    CycleFormat,
+}
+
+/// A command for the listener with the given name, or for every listener
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlEvent {
+    pub listener: Option<String>,
+    pub command: Command,
+}
+
+/// Settings of a listener changed from the TUI while the server runs
+#[derive(Debug, Default)]
+pub struct Controls {
+    /// Delay replacing the configured one and the scenario's
+    delay: RwLock<Option<Arc<DelayConfig>>>,
+    /// Injected errors are switched off (scripted sequences still apply)
+    errors_off: AtomicBool,
+    /// Index of the pause mode in `Pause::ALL`
+    pause: AtomicU8,
+    /// Default response format replacing the configured one
+    format: RwLock<Option<OutputFormat>>,
+}
+
+impl Controls {
+    /// The delay set from the TUI, if any
+    pub fn delay(&self) -> Option<Arc<DelayConfig>> {
+        self.delay.read().unwrap().clone()
+    }
+
+    pub fn set_delay(&self, delay: DelayConfig) {
+        *self.delay.write().unwrap() = Some(Arc::new(delay));
+    }
+
+    pub fn errors_enabled(&self) -> bool {
+        !self.errors_off.load(Ordering::Relaxed)
+    }
+
+    pub fn toggle_errors(&self) {
+        self.errors_off.fetch_xor(true, Ordering::Relaxed);
+    }
+
+    pub fn pause(&self) -> Pause {
+        Pause::ALL[self.pause.load(Ordering::Relaxed) as usize]
+    }
+
+    pub fn cycle_pause(&self) {
+        let next = (self.pause.load(Ordering::Relaxed) + 1) % Pause::ALL.len() as u8;
+        self.pause.store(next, Ordering::Relaxed);
+    }
+
+    /// The response format set from the TUI, if any
+    pub fn format(&self) -> Option<OutputFormat> {
+        *self.format.read().unwrap()
+    }
+
+    pub fn set_format(&self, format: OutputFormat) {
+        *self.format.write().unwrap() = Some(format);
+    }
+
+    /// What was changed from the TUI, e.g. `delay 20-50 ms, errors off`; `None` if nothing
+    pub fn summary(&self) -> Option<String> {
+        let mut changes = Vec::new();
+        if let Some(delay) = self.delay() {
+            changes.push(match delay.range() {
+                (min, max) if min == max => format!("delay {} ms", min),
+                (min, max) => format!("delay {}-{} ms", min, max),
+            });
+        }
+        if !self.errors_enabled() {
+            changes.push("errors off".to_string());
+        }
+        if self.pause() != Pause::Off {
+            changes.push(format!("PAUSED ({})", self.pause().name()));
+        }
+        if let Some(format) = self.format() {
+            changes.push(format!("format {}", format.name()));
+        }
+        (!changes.is_empty()).then(|| changes.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_controls() {
+        let controls = Controls::default();
+        assert_eq!(controls.summary(), None);
+        controls.set_delay(DelayConfig::parse("20-50").unwrap());
+        controls.toggle_errors();
+        controls.cycle_pause();
+        controls.set_format(OutputFormat::Yaml);
+        assert_eq!(
+            controls.summary().unwrap(),
+            "delay 20-50 ms, errors off, PAUSED (503), format yaml"
+        );
+        controls.toggle_errors();
+        controls.cycle_pause();
+        assert_eq!(controls.pause(), Pause::Refused);
+        controls.cycle_pause();
+        assert_eq!(controls.summary().unwrap(), "delay 20-50 ms, format yaml");
+    }
+}
@@ -0,0 +1,325 @@
+use crate::OutputFormat;
+use axum::body::Bytes;
+use serde_json::Value;
+
+/// Media types clients may ask for in `Accept`, and the format served for each
+const MEDIA_TYPES: [(&str, OutputFormat); 14] = [
+    ("application/json", OutputFormat::Json),
+    ("text/plain", OutputFormat::Text),
+    ("application/xml", OutputFormat::Xml),
+    ("text/xml", OutputFormat::Xml),
+    ("application/msgpack", OutputFormat::MsgPack),
+    ("application/x-msgpack", OutputFormat::MsgPack),
+    ("application/vnd.msgpack", OutputFormat::MsgPack),
+    ("application/cbor", OutputFormat::Cbor),
+    ("application/x-protobuf", OutputFormat::Protobuf),
+    ("application/protobuf", OutputFormat::Protobuf),
+    ("application/yaml", OutputFormat::Yaml),
+    ("application/x-yaml", OutputFormat::Yaml),
+    ("text/yaml", OutputFormat::Yaml),
+    ("text/html", OutputFormat::Html),
+];
+
+/// The content type of responses in the given format
+pub fn content_type(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Json => "application/json",
+        OutputFormat::Text => "text/plain",
+        OutputFormat::Xml => "application/xml",
+        OutputFormat::MsgPack => "application/msgpack",
+        OutputFormat::Cbor => "application/cbor",
+        OutputFormat::Protobuf => "application/x-protobuf",
+        OutputFormat::Yaml => "application/yaml",
+        OutputFormat::Html => "text/html; charset=utf-8",
+    }
+}
+
+/// Parse a header like `Accept` or `Accept-Encoding` into values and their
+/// quality, highest quality first (ties keep the client's order)
+pub fn parse_quality_list(header: &str) -> Vec<(String, f32)> {
+    let mut values: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|part| {
+            let mut params = part.split(';');
+            let value = params.next()?.trim().to_lowercase();
+            if value.is_empty() {
+                return None;
+            }
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((value, quality))
+        })
+        .collect();
+    values.sort_by(|a, b| b.1.total_cmp(&a.1));
+    values
+}
+
+/// Pick the response format from an `Accept` header, falling back to `default`
+/// when the header is missing or asks for nothing we can produce
+pub fn negotiate(accept: Option<&str>, default: OutputFormat) -> OutputFormat {
+    let Some(accept) = accept else {
+        return default;
+    };
+    for (range, quality) in parse_quality_list(accept) {
+        if quality <= 0.0 {
+            continue;
+        }
+        if range == "*/*" {
+            return default;
+        }
+        if let Some(kind) = range.strip_suffix("/*") {
+            if content_type(default).starts_with(&format!("{}/", kind)) {
+                return default;
+            }
+            if let Some((_, format)) = MEDIA_TYPES
+                .iter()
+                .find(|(media, _)| media.starts_with(&format!("{}/", kind)))
+            {
+                return *format;
+            }
+            continue;
+        }
+        if let Some((_, format)) = MEDIA_TYPES.iter().find(|(media, _)| *media == range) {
+            return *format;
+        }
+    }
+    default
+}
+
+/// Render a response body. `Text` uses the given human-readable line, `Html`
+/// shows it as the heading of a page listing `value`; the other formats
+/// encode `value`.
+pub fn render(format: OutputFormat, value: &Value, text: &str) -> Bytes {
+    match format {
+        OutputFormat::Json => Bytes::from(value.to_string()),
+        OutputFormat::Text => Bytes::from(text.to_string()),
+        OutputFormat::Xml => {
+            let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+            write_xml(&mut xml, "response", value);
+            Bytes::from(xml)
+        }
+        OutputFormat::MsgPack => Bytes::from(rmp_serde::to_vec_named(value).unwrap_or_default()),
+        OutputFormat::Cbor => {
+            let mut cbor = Vec::new();
+            let _ = ciborium::into_writer(value, &mut cbor);
+            Bytes::from(cbor)
+        }
+        OutputFormat::Protobuf => {
+            let mut message = Vec::new();
+            if let Value::Object(fields) = value {
+                write_protobuf_fields(&mut message, fields);
+            }
+            Bytes::from(message)
+        }
+        OutputFormat::Yaml => Bytes::from(serde_yaml::to_string(value).unwrap_or_default()),
+        OutputFormat::Html => {
+            let mut html = format!(
+                "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>rustrate</title></head>\
+                 <body><h1>{}</h1>",
+                escape_xml(text)
+            );
+            write_html(&mut html, value);
+            html.push_str("</body></html>");
+            Bytes::from(html)
+        }
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Objects become child elements and arrays repeated `<item>` elements
+fn write_xml(out: &mut String, name: &str, value: &Value) {
+    out.push_str(&format!("<{}>", name));
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                write_xml(out, key, value);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                write_xml(out, "item", item);
+            }
+        }
+        Value::String(text) => out.push_str(&escape_xml(text)),
+        Value::Null => {}
+        other => out.push_str(&other.to_string()),
+    }
+    out.push_str(&format!("</{}>", name));
+}
+
+/// Objects become definition lists and arrays ordered lists
+fn write_html(out: &mut String, value: &Value) {
+    match value {
+        Value::Object(fields) => {
+            out.push_str("<dl>");
+            for (key, value) in fields {
+                out.push_str(&format!("<dt>{}</dt><dd>", escape_xml(key)));
+                write_html(out, value);
+                out.push_str("</dd>");
+            }
+            out.push_str("</dl>");
+        }
+        Value::Array(items) => {
+            out.push_str("<ol>");
+            for item in items {
+                out.push_str("<li>");
+                write_html(out, item);
+                out.push_str("</li>");
+            }
+            out.push_str("</ol>");
+        }
+        Value::String(text) => out.push_str(&escape_xml(text)),
+        Value::Null => {}
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Encode an object in the protobuf wire format. Fields are numbered from 1
+/// in key order; integers are varints (int64), other numbers doubles, and
+/// objects embedded messages. Arrays become repeated fields.
+fn write_protobuf_fields(out: &mut Vec<u8>, fields: &serde_json::Map<String, Value>) {
+    for (number, value) in (1u64..).zip(fields.values()) {
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    write_protobuf_field(out, number, item);
+                }
+            }
+            value => write_protobuf_field(out, number, value),
+        }
+    }
+}
+
+fn write_protobuf_field(out: &mut Vec<u8>, number: u64, value: &Value) {
+    const VARINT: u64 = 0;
+    const FIXED64: u64 = 1;
+    const LENGTH_DELIMITED: u64 = 2;
+    let length_delimited = |out: &mut Vec<u8>, bytes: &[u8]| {
+        write_varint(out, number << 3 | LENGTH_DELIMITED);
+        write_varint(out, bytes.len() as u64);
+        out.extend_from_slice(bytes);
+    };
+    match value {
+        Value::Null => {}
+        Value::Bool(flag) => {
+            write_varint(out, number << 3 | VARINT);
+            write_varint(out, *flag as u64);
+        }
+        Value::Number(n) => match n.as_i64().map(|i| i as u64).or_else(|| n.as_u64()) {
+            Some(int) => {
+                write_varint(out, number << 3 | VARINT);
+                write_varint(out, int);
+            }
+            None => {
+                write_varint(out, number << 3 | FIXED64);
+                out.extend_from_slice(&n.as_f64().unwrap_or_default().to_le_bytes());
+            }
+        },
+        Value::String(text) => length_delimited(out, text.as_bytes()),
+        Value::Object(fields) => {
+            let mut message = Vec::new();
+            write_protobuf_fields(&mut message, fields);
+            length_delimited(out, &message);
+        }
+        // Nested arrays have no protobuf equivalent; encode them as JSON text
+        Value::Array(_) => length_delimited(out, value.to_string().as_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_negotiate_format() {
+        let default = OutputFormat::Json;
+        assert_eq!(negotiate(None, default), OutputFormat::Json);
+        assert_eq!(
+            negotiate(Some("*/*"), OutputFormat::Text),
+            OutputFormat::Text
+        );
+        assert_eq!(
+            negotiate(Some("application/msgpack"), default),
+            OutputFormat::MsgPack
+        );
+        assert_eq!(
+            negotiate(Some("application/json;q=0.5, text/xml"), default),
+            OutputFormat::Xml
+        );
+        assert_eq!(
+            negotiate(Some("application/cbor;q=0, text/*"), default),
+            OutputFormat::Text
+        );
+        // Browsers prefer HTML
+        assert_eq!(
+            negotiate(Some("text/html,application/xhtml+xml,*/*;q=0.8"), default),
+            OutputFormat::Html
+        );
+        // Nothing we can produce: serve the default rather than a 406
+        assert_eq!(negotiate(Some("image/png"), default), OutputFormat::Json);
+    }
+
+    #[test]
+    fn test_render_xml() {
+        let value = json!({"code": 200, "request": {"path": "/a&b"}, "tags": [1, 2]});
+        assert_eq!(
+            render(OutputFormat::Xml, &value, ""),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><response><code>200</code>\
+             <request><path>/a&amp;b</path></request><tags><item>1</item><item>2</item></tags>\
+             </response>"
+        );
+    }
+
+    #[test]
+    fn test_render_binary_formats_round_trip() {
+        let value = json!({"code": 200, "request": {"method": "GET"}, "ms": 1.5});
+        let msgpack = render(OutputFormat::MsgPack, &value, "");
+        assert_eq!(rmp_serde::from_slice::<Value>(&msgpack).unwrap(), value);
+        let cbor = render(OutputFormat::Cbor, &value, "");
+        assert_eq!(ciborium::from_reader::<Value, _>(&cbor[..]).unwrap(), value);
+    }
+
+    #[test]
+    fn test_render_yaml_and_html() {
+        let value = json!({"code": 200, "request": {"path": "/<a>"}});
+        let yaml = render(OutputFormat::Yaml, &value, "");
+        assert_eq!(serde_yaml::from_slice::<Value>(&yaml).unwrap(), value);
+
+        let html = render(OutputFormat::Html, &value, "Request processed");
+        let html = std::str::from_utf8(&html).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>Request processed</h1>"));
+        assert!(html.contains("<dt>path</dt><dd>/&lt;a&gt;</dd>"));
+    }
+
+    #[test]
+    fn test_render_protobuf() {
+        // Fields are numbered in key order: code, ok, request
+        let value = json!({"code": 200, "request": {"method": "GET"}, "ok": true});
+        assert_eq!(
+            render(OutputFormat::Protobuf, &value, "").as_ref(),
+            [
+                0x08, 0xc8, 0x01, // 1: 200
+                0x10, 0x01, // 2: true
+                0x1a, 0x05, 0x0a, 0x03, b'G', b'E', b'T', // 3: { 1: "GET" }
+            ]
+        );
+    }
+}
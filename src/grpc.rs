@@ -0,0 +1,435 @@
+use crate::clock::Clock;
+use crate::config::{GrpcMethodConfig, GrpcMethodKind};
+use crate::state::{AppState, Outcome, RequestLog};
+use anyhow::anyhow;
+use axum::{
+    body::{Bytes, HttpBody, boxed},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+};
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// gRPC status code names, indexed by code
+const CODE_NAMES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+
+/// gRPC status codes used by the mock server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrpcCode {
+    Ok = 0,
+    Unknown = 2,
+    InvalidArgument = 3,
+    DeadlineExceeded = 4,
+    NotFound = 5,
+    PermissionDenied = 7,
+    ResourceExhausted = 8,
+    Unimplemented = 12,
+    Internal = 13,
+    Unavailable = 14,
+    Unauthenticated = 16,
+}
+
+impl GrpcCode {
+    /// Map an injected HTTP error status to the gRPC code with the same meaning
+    pub fn from_http_status(status: u16) -> Self {
+        match status {
+            400 => GrpcCode::InvalidArgument,
+            401 => GrpcCode::Unauthenticated,
+            403 => GrpcCode::PermissionDenied,
+            404 => GrpcCode::NotFound,
+            429 => GrpcCode::ResourceExhausted,
+            501 => GrpcCode::Unimplemented,
+            502 | 503 => GrpcCode::Unavailable,
+            504 => GrpcCode::DeadlineExceeded,
+            500..=599 => GrpcCode::Internal,
+            _ => GrpcCode::Unknown,
+        }
+    }
+}
+
+/// Return the canonical name of a gRPC status code
+pub fn code_name(code: u8) -> &'static str {
+    CODE_NAMES.get(code as usize).copied().unwrap_or("UNKNOWN")
+}
+
+#[derive(Debug, Clone)]
+struct GrpcMethod {
+    kind: GrpcMethodKind,
+    response: Bytes,
+    messages: u32,
+}
+
+/// The gRPC methods declared in the config, keyed by request path (`/service/Method`)
+#[derive(Debug, Clone, Default)]
+pub struct GrpcRegistry {
+    methods: HashMap<String, GrpcMethod>,
+}
+
+impl GrpcRegistry {
+    pub fn from_config(methods: &[GrpcMethodConfig]) -> anyhow::Result<Self> {
+        let mut registry = Self::default();
+        for config in methods {
+            let response = match &config.response_hex {
+                Some(hex) => decode_hex(hex).map_err(|e| {
+                    anyhow!(
+                        "Invalid response_hex for {}/{}: {}",
+                        config.service,
+                        config.method,
+                        e
+                    )
+                })?,
+                None => Vec::new(),
+            };
+            let path = format!("/{}/{}", config.service, config.method);
+            let method = GrpcMethod {
+                kind: config.kind,
+                response: Bytes::from(response),
+                messages: config.messages,
+            };
+            if registry.methods.insert(path.clone(), method).is_some() {
+                return Err(anyhow!("Duplicate gRPC method {}", path));
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty()
+    }
+}
+
+/// Whether the request should be handled as gRPC
+pub fn is_grpc_request(headers: &HeaderMap) -> bool {
+    headers
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/grpc"))
+}
+
+pub(crate) fn decode_hex(hex: &str) -> anyhow::Result<Vec<u8>> {
+    let digits = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).ok_or_else(|| anyhow!("invalid hex digit")))
+        .collect::<anyhow::Result<Vec<u32>>>()?;
+    if !digits.len().is_multiple_of(2) {
+        return Err(anyhow!("odd number of hex digits"));
+    }
+    Ok(digits
+        .chunks(2)
+        .map(|pair| (pair[0] * 16 + pair[1]) as u8)
+        .collect())
+}
+
+/// Wrap a message in the gRPC length-prefixed framing
+fn encode_frame(message: &[u8]) -> Bytes {
+    let mut frame = Vec::with_capacity(5 + message.len());
+    frame.push(0);
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(message);
+    Bytes::from(frame)
+}
+
+/// Count the length-prefixed messages in a request body
+fn count_frames(mut body: &[u8]) -> Option<usize> {
+    let mut count = 0;
+    while !body.is_empty() {
+        if body.len() < 5 {
+            return None;
+        }
+        let len = u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize;
+        body = body.get(5 + len..)?;
+        count += 1;
+    }
+    Some(count)
+}
+
+/// Parse the `grpc-timeout` header (e.g. `100m`, `5S`). The spec allows at most 8 digits,
+/// so longer values are ignored rather than overflowing.
+fn parse_timeout(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get("grpc-timeout")?.to_str().ok()?;
+    let (amount, unit) = value.split_at(value.len().checked_sub(1)?);
+    if !(1..=8).contains(&amount.len()) || !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount = amount.parse::<u64>().ok()?;
+    Some(match unit {
+        "H" => Duration::from_secs(amount * 3600),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return None,
+    })
+}
+
+/// A gRPC response body: framed messages (each optionally delayed) followed by trailers
+struct GrpcBody {
+    clock: Arc<dyn Clock>,
+    messages: VecDeque<(Duration, Bytes)>,
+    pending: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    trailers: Option<HeaderMap>,
+}
+
+impl GrpcBody {
+    fn new(
+        clock: Arc<dyn Clock>,
+        messages: VecDeque<(Duration, Bytes)>,
+        code: GrpcCode,
+        message: Option<&str>,
+    ) -> Self {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from(code as u16));
+        if let Some(message) = message.and_then(|m| HeaderValue::from_str(m).ok()) {
+            trailers.insert("grpc-message", message);
+        }
+        Self {
+            clock,
+            messages,
+            pending: None,
+            trailers: Some(trailers),
+        }
+    }
+}
+
+impl HttpBody for GrpcBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = &mut *self;
+        loop {
+            if let Some(pending) = this.pending.as_mut() {
+                if pending.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                this.pending = None;
+                let message = this.messages.pop_front().map(|(_, message)| message);
+                return Poll::Ready(message.map(Ok));
+            }
+            match this.messages.front() {
+                None => return Poll::Ready(None),
+                Some((delay, _)) if !delay.is_zero() => {
+                    this.pending = Some(this.clock.sleep(*delay));
+                }
+                Some(_) => {
+                    let message = this.messages.pop_front().map(|(_, message)| message);
+                    return Poll::Ready(message.map(Ok));
+                }
+            }
+        }
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(self.trailers.take()))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.messages.is_empty() && self.trailers.is_none()
+    }
+}
+
+/// Handle a gRPC call.
+///
+/// Applies the configured delay and error models and reports the outcome as a
+/// gRPC status in trailers. For streaming responses, every message after the
+/// first is delayed by a fresh sample of the delay model; the logged duration
+/// covers the time until the first message.
+pub async fn grpc_handler(
+    state: &AppState,
+    path: &str,
+    method: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Response {
+    let start = state.clock.now();
+    let now = state.now_timestamp();
+    state.increment_requests();
+
+    let delay = Duration::from_millis(state.get_delay());
+    let deadline = parse_timeout(headers);
+
+    let mut messages = VecDeque::new();
+    let mut injected_error = false;
+    let (code, message) = if deadline.is_some_and(|deadline| deadline < delay) {
+        state.clock.sleep(deadline.unwrap_or_default()).await;
+        (GrpcCode::DeadlineExceeded, Some("Deadline exceeded"))
+    } else {
+        if !delay.is_zero() {
+            state.clock.sleep(delay).await;
+        }
+        match (state.grpc.methods.get(path), state.get_error()) {
+            (None, _) => (
+                GrpcCode::Unimplemented,
+                Some("Method not declared in config"),
+            ),
+            (Some(_), Some(status)) => {
+                injected_error = true;
+                (GrpcCode::from_http_status(status), Some("Injected error"))
+            }
+            (Some(grpc_method), None) => match count_frames(body) {
+                None => (GrpcCode::Internal, Some("Malformed request framing")),
+                Some(received) => {
+                    let count = match grpc_method.kind {
+                        GrpcMethodKind::Unary | GrpcMethodKind::ClientStreaming => 1,
+                        GrpcMethodKind::ServerStreaming => grpc_method.messages as usize,
+                        GrpcMethodKind::BidiStreaming => received,
+                    };
+                    let frame = encode_frame(&grpc_method.response);
+                    for i in 0..count {
+                        let delay = if i == 0 {
+                            Duration::ZERO
+                        } else {
+                            Duration::from_millis(state.get_delay())
+                        };
+                        messages.push_back((delay, frame.clone()));
+                    }
+                    (GrpcCode::Ok, None)
+                }
+            },
+        }
+    };
+
+    let log = RequestLog {
+        grpc_status: Some(code as u8),
+        outcome: if injected_error {
+            Outcome::InjectedError
+        } else {
+            Outcome::Served
+        },
+        timestamp: now,
+        duration_ms: state.elapsed_ms(start),
+        ..RequestLog::new(state, method, path, 200)
+    };
+    state.record(log).await;
+
+    grpc_response(&state.clock, messages, code, message)
+}
+
+/// A gRPC response streaming `messages`, then trailers with the status
+fn grpc_response(
+    clock: &Arc<dyn Clock>,
+    messages: VecDeque<(Duration, Bytes)>,
+    code: GrpcCode,
+    message: Option<&str>,
+) -> Response {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/grpc")
+        .body(boxed(GrpcBody::new(clock.clone(), messages, code, message)))
+        .unwrap()
+}
+
+/// A gRPC response with no messages and the given error status
+pub fn grpc_error(clock: &Arc<dyn Clock>, code: GrpcCode, message: &str) -> Response {
+    grpc_response(clock, VecDeque::new(), code, Some(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::config::Config;
+
+    #[test]
+    fn test_encode_and_count_frames() {
+        let mut body = encode_frame(b"hello").to_vec();
+        body.extend_from_slice(&encode_frame(b""));
+        assert_eq!(count_frames(&body), Some(2));
+        assert_eq!(count_frames(&[]), Some(0));
+        assert_eq!(count_frames(&body[..7]), None);
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("0a 05").unwrap(), vec![0x0a, 0x05]);
+        assert!(decode_hex("abc").is_err());
+        assert!(decode_hex("zz").is_err());
+        // Neither a sign nor non-ASCII characters are digits
+        assert!(decode_hex("+f").is_err());
+        assert!(decode_hex("é0").is_err());
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        let mut headers = HeaderMap::new();
+        headers.insert("grpc-timeout", HeaderValue::from_static("250m"));
+        assert_eq!(parse_timeout(&headers), Some(Duration::from_millis(250)));
+        headers.insert("grpc-timeout", HeaderValue::from_static("99999999H"));
+        assert_eq!(
+            parse_timeout(&headers),
+            Some(Duration::from_secs(99_999_999 * 3600))
+        );
+        for invalid in ["bogus", "123456789S", "18446744073709551615H", "+5S", "S"] {
+            headers.insert("grpc-timeout", HeaderValue::from_static(invalid));
+            assert_eq!(parse_timeout(&headers), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_code_from_http_status() {
+        assert_eq!(GrpcCode::from_http_status(503), GrpcCode::Unavailable);
+        assert_eq!(GrpcCode::from_http_status(504), GrpcCode::DeadlineExceeded);
+        assert_eq!(GrpcCode::from_http_status(500), GrpcCode::Internal);
+        assert_eq!(code_name(GrpcCode::Unavailable as u8), "UNAVAILABLE");
+    }
+
+    #[test]
+    fn test_registry_rejects_duplicates() {
+        let config = Config::parse(
+            r#"{"grpc": [
+                {"service": "a.B", "method": "C"},
+                {"service": "a.B", "method": "C"}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(GrpcRegistry::from_config(&config.grpc).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_message_delays_use_the_clock() {
+        let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+        let start = clock.now();
+        let messages = VecDeque::from([
+            (Duration::ZERO, Bytes::from_static(b"a")),
+            (Duration::from_secs(5), Bytes::from_static(b"b")),
+        ]);
+        let mut body = GrpcBody::new(clock.clone(), messages, GrpcCode::Ok, None);
+        assert_eq!(body.data().await.unwrap().unwrap(), "a");
+        assert_eq!(body.data().await.unwrap().unwrap(), "b");
+        assert!(body.data().await.is_none());
+        assert_eq!(clock.now() - start, Duration::from_secs(5));
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["grpc-status"], "0");
+    }
+}
@@ -0,0 +1,474 @@
+use crate::util::parse_duration;
+use anyhow::anyhow;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Bucket width of the downsampled history kept for windows longer than a minute
+const COARSE_BUCKET: Duration = Duration::from_secs(10);
+
+/// How far back the history is kept at full resolution
+const FINE_SPAN: Duration = Duration::from_secs(60);
+
+/// Windows the chart can zoom between, besides the configured one
+const ZOOM_LEVELS: [Duration; 7] = [
+    Duration::from_secs(10),
+    Duration::from_secs(30),
+    Duration::from_secs(60),
+    Duration::from_secs(5 * 60),
+    Duration::from_secs(15 * 60),
+    Duration::from_secs(30 * 60),
+    Duration::from_secs(60 * 60),
+];
+
+/// Bucket width and window length of the request history
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryConfig {
+    pub bucket: Duration,
+    pub window: Duration,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            bucket: Duration::from_secs(1),
+            window: Duration::from_secs(60),
+        }
+    }
+}
+
+impl HistoryConfig {
+    /// Parse a bucket width (100ms to 10s, dividing 10s evenly) and a window (1m to 1h)
+    pub fn parse(bucket: &str, window: &str) -> anyhow::Result<Self> {
+        let bucket_width = parse_duration(bucket)?;
+        if bucket_width < Duration::from_millis(100)
+            || bucket_width > COARSE_BUCKET
+            || !COARSE_BUCKET
+                .as_millis()
+                .is_multiple_of(bucket_width.as_millis())
+        {
+            return Err(anyhow!(
+                "Invalid bucket width '{}'. Use 100ms to 10s, dividing 10s evenly (e.g. 100ms, 1s, 10s)",
+                bucket
+            ));
+        }
+        let window_length = parse_duration(window)?;
+        if !(FINE_SPAN..=Duration::from_secs(3600)).contains(&window_length) {
+            return Err(anyhow!(
+                "Invalid window '{}'. Use 1m to 1h (e.g. 1m, 15m, 1h)",
+                window
+            ));
+        }
+        Ok(Self {
+            bucket: bucket_width,
+            window: window_length,
+        })
+    }
+
+    /// The windows the chart can zoom between, shortest first, ending with the configured one
+    pub fn zoom_levels(&self) -> Vec<Duration> {
+        ZOOM_LEVELS
+            .into_iter()
+            .filter(|&level| level >= self.bucket * 10 && level < self.window)
+            .chain([self.window])
+            .collect()
+    }
+}
+
+/// Latency histogram bins by upper bound in milliseconds; slower requests fall in a last,
+/// open-ended bin
+pub const LATENCY_BINS_MS: [f64; 12] = [
+    1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0,
+];
+
+/// Served latencies of the requests in one bucket
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencyBucket {
+    /// Requests per bin of `LATENCY_BINS_MS`, plus the open-ended bin
+    pub counts: [u64; LATENCY_BINS_MS.len() + 1],
+    pub max_ms: f64,
+}
+
+impl LatencyBucket {
+    fn add(&mut self, ms: f64) {
+        let bin = LATENCY_BINS_MS
+            .iter()
+            .position(|&bound| ms <= bound)
+            .unwrap_or(LATENCY_BINS_MS.len());
+        self.counts[bin] += 1;
+        self.max_ms = self.max_ms.max(ms);
+    }
+
+    fn merge(&mut self, other: &LatencyBucket) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+        self.max_ms = self.max_ms.max(other.max_ms);
+    }
+
+    /// The latency `p` (0 to 1) of the requests are within: the upper bound of the bin
+    /// holding it, capped at the slowest request. `None` without requests.
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        let total: u64 = self.counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((total as f64 * p).ceil() as u64).max(1);
+        let mut seen = 0;
+        let bin = self
+            .counts
+            .iter()
+            .position(|&count| {
+                seen += count;
+                seen >= rank
+            })
+            .unwrap_or(LATENCY_BINS_MS.len());
+        let bound = LATENCY_BINS_MS.get(bin).copied().unwrap_or(f64::MAX);
+        Some(bound.min(self.max_ms))
+    }
+}
+
+/// Values in fixed-width buckets, newest first (index 0 = the current bucket)
+#[derive(Debug, Clone)]
+struct Series<T> {
+    width: Duration,
+    buckets: VecDeque<T>,
+    capacity: usize,
+    /// Number of the current bucket, counted from the start
+    current: u64,
+}
+
+impl<T: Default> Series<T> {
+    fn new(width: Duration, span: Duration) -> Self {
+        // One more than the span, as the current bucket isn't complete
+        let capacity = span.as_millis().div_ceil(width.as_millis()) as usize + 1;
+        let mut buckets = VecDeque::with_capacity(capacity);
+        buckets.push_front(T::default());
+        Self {
+            width,
+            buckets,
+            capacity,
+            current: 0,
+        }
+    }
+
+    /// Start new buckets up to the one holding `elapsed`
+    fn advance(&mut self, elapsed: Duration) {
+        let bucket = (elapsed.as_millis() / self.width.as_millis()) as u64;
+        let new = bucket
+            .saturating_sub(self.current)
+            .min(self.capacity as u64);
+        for _ in 0..new {
+            self.buckets.push_front(T::default());
+        }
+        self.buckets.truncate(self.capacity);
+        self.current = self.current.max(bucket);
+    }
+
+    /// The `n` completed buckets, newest first; `None` for buckets from before the start
+    fn completed(&self, n: usize) -> impl Iterator<Item = Option<&T>> {
+        (1..=n).map(|i| self.buckets.get(i))
+    }
+}
+
+/// Values over time: at full resolution for the last minute and downsampled to 10s buckets
+/// for the rest of the window. Request counts by default.
+#[derive(Debug, Clone)]
+pub struct History<T = u64> {
+    fine: Series<T>,
+    coarse: Series<T>,
+}
+
+impl<T: Default> History<T> {
+    pub fn new(config: HistoryConfig) -> Self {
+        Self {
+            fine: Series::new(config.bucket, FINE_SPAN),
+            coarse: Series::new(COARSE_BUCKET, config.window),
+        }
+    }
+
+    /// Move the current bucket to `elapsed` since the start
+    pub fn advance(&mut self, elapsed: Duration) {
+        self.fine.advance(elapsed);
+        self.coarse.advance(elapsed);
+    }
+
+    /// Update the current bucket at both resolutions
+    fn record(&mut self, update: impl Fn(&mut T)) {
+        update(&mut self.fine.buckets[0]);
+        update(&mut self.coarse.buckets[0]);
+    }
+
+    fn series(&self, window: Duration) -> &Series<T> {
+        if window <= FINE_SPAN {
+            &self.fine
+        } else {
+            &self.coarse
+        }
+    }
+
+    /// Width of the buckets shown for `window`
+    pub fn bucket_width(&self, window: Duration) -> Duration {
+        self.series(window).width
+    }
+
+    /// The completed buckets within `window`, newest first
+    fn completed(&self, window: Duration) -> impl Iterator<Item = Option<&T>> {
+        let series = self.series(window);
+        let n = (window.as_millis() / series.width.as_millis()).max(1) as usize;
+        series.completed(n)
+    }
+}
+
+impl History {
+    /// Count a request in the current bucket
+    pub fn add(&mut self) {
+        self.add_n(1);
+    }
+
+    /// Count `n` events in the current bucket
+    pub fn add_n(&mut self, n: u64) {
+        self.record(|count| *count += n);
+    }
+
+    /// Keep the highest sample of a gauge in the current bucket
+    pub fn peak(&mut self, value: u64) {
+        self.record(|peak| *peak = (*peak).max(value));
+    }
+
+    /// The values of the completed buckets within `window`, newest first.
+    /// Buckets from before the start count as zero.
+    pub fn values(&self, window: Duration) -> Vec<f64> {
+        self.completed(window)
+            .map(|value| value.copied().unwrap_or(0) as f64)
+            .collect()
+    }
+
+    /// Counts per second of the completed buckets within `window`, newest first
+    pub fn rates(&self, window: Duration) -> Vec<f64> {
+        let seconds = self.bucket_width(window).as_secs_f64();
+        self.values(window)
+            .into_iter()
+            .map(|count| count / seconds)
+            .collect()
+    }
+
+    /// Requests per second over the last completed second (or bucket, if longer)
+    pub fn last_second(&self) -> f64 {
+        let rates = self.rates(FINE_SPAN);
+        let n = (1000 / self.fine.width.as_millis()).max(1) as usize;
+        rates[..n].iter().sum::<f64>() / n as f64
+    }
+}
+
+impl History<LatencyBucket> {
+    /// Record the served latency of a request in the current bucket
+    pub fn add_latency(&mut self, ms: f64) {
+        self.record(|bucket| bucket.add(ms));
+    }
+
+    /// The latencies of the completed buckets within `window`, newest first. Empty buckets
+    /// are empty histograms.
+    pub fn buckets(&self, window: Duration) -> Vec<LatencyBucket> {
+        self.completed(window)
+            .map(|bucket| bucket.cloned().unwrap_or_default())
+            .collect()
+    }
+}
+
+/// Bins per doubling of latency in a `LatencyHistogram`, about 4.4% wide each
+const BINS_PER_DOUBLING: f64 = 16.0;
+
+/// Upper bound of a `LatencyHistogram`'s first bin, in milliseconds
+const FIRST_BIN_MS: f64 = 0.01;
+
+/// Served latencies in log-scale bins, fine enough for percentiles to within 5%
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencyHistogram {
+    counts: Vec<u64>,
+    max_ms: f64,
+}
+
+impl LatencyHistogram {
+    pub fn add(&mut self, ms: f64) {
+        let bin = if ms <= FIRST_BIN_MS {
+            0
+        } else {
+            ((ms / FIRST_BIN_MS).log2() * BINS_PER_DOUBLING).ceil() as usize
+        };
+        if self.counts.len() <= bin {
+            self.counts.resize(bin + 1, 0);
+        }
+        self.counts[bin] += 1;
+        self.max_ms = self.max_ms.max(ms);
+    }
+
+    /// The latency `p` (0 to 1) of the requests are within: the upper bound of the bin
+    /// holding it, capped at the slowest request. `None` without requests.
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        let total: u64 = self.counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((total as f64 * p).ceil() as u64).max(1);
+        let mut seen = 0;
+        let bin = self
+            .counts
+            .iter()
+            .position(|&count| {
+                seen += count;
+                seen >= rank
+            })
+            .unwrap_or(self.counts.len() - 1);
+        let bound = FIRST_BIN_MS * 2f64.powf(bin as f64 / BINS_PER_DOUBLING);
+        Some(bound.min(self.max_ms))
+    }
+}
+
+/// Merge runs of `per_column` adjacent buckets, e.g. to fit a heatmap to its width
+pub fn merge_columns(buckets: &[LatencyBucket], per_column: usize) -> Vec<LatencyBucket> {
+    buckets
+        .chunks(per_column.max(1))
+        .map(|chunk| {
+            let mut merged = LatencyBucket::default();
+            for bucket in chunk {
+                merged.merge(bucket);
+            }
+            merged
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(bucket: &str, window: &str) -> HistoryConfig {
+        HistoryConfig::parse(bucket, window).unwrap()
+    }
+
+    #[test]
+    fn test_parse_config() {
+        assert_eq!(config("1s", "1m"), HistoryConfig::default());
+        assert_eq!(config("100ms", "1h").window, Duration::from_secs(3600));
+        assert!(HistoryConfig::parse("10ms", "1m").is_err());
+        assert!(HistoryConfig::parse("3s", "1m").is_err());
+        assert!(HistoryConfig::parse("1s", "30s").is_err());
+        assert!(HistoryConfig::parse("1s", "2h").is_err());
+        let levels: Vec<u64> = config("1s", "15m")
+            .zoom_levels()
+            .iter()
+            .map(Duration::as_secs)
+            .collect();
+        assert_eq!(levels, vec![10, 30, 60, 300, 900]);
+        assert_eq!(config("10s", "1m").zoom_levels().len(), 1);
+    }
+
+    #[test]
+    fn test_sub_second_buckets() {
+        let mut history = History::new(config("100ms", "1m"));
+        // A burst of 5 requests in one 100ms bucket, then quiet
+        history.advance(Duration::from_millis(1_050));
+        for _ in 0..5 {
+            history.add();
+        }
+        history.advance(Duration::from_millis(1_350));
+        let rates = history.rates(Duration::from_secs(10));
+        assert_eq!(rates.len(), 100);
+        assert_eq!(&rates[..4], &[0.0, 0.0, 50.0, 0.0]);
+        assert_eq!(history.last_second(), 5.0);
+    }
+
+    #[test]
+    fn test_gauge_peaks() {
+        let mut history = History::new(config("1s", "1m"));
+        for value in [3, 7, 2] {
+            history.peak(value);
+        }
+        history.advance(Duration::from_secs(1));
+        history.add_n(4);
+        history.advance(Duration::from_secs(2));
+        assert_eq!(&history.values(Duration::from_secs(60))[..2], &[4.0, 7.0]);
+    }
+
+    #[test]
+    fn test_long_windows_are_downsampled() {
+        let mut history = History::new(config("1s", "1h"));
+        history.advance(Duration::from_secs(5));
+        for _ in 0..20 {
+            history.add();
+        }
+        // The full-resolution history only reaches back a minute
+        history.advance(Duration::from_secs(125));
+        assert_eq!(
+            history.rates(Duration::from_secs(60)).iter().sum::<f64>(),
+            0.0
+        );
+        let rates = history.rates(Duration::from_secs(3600));
+        assert_eq!(rates.len(), 360);
+        assert_eq!(
+            history.bucket_width(Duration::from_secs(3600)).as_secs(),
+            10
+        );
+        assert_eq!(rates[11], 2.0);
+        // Nothing survives a gap longer than the window
+        history.advance(Duration::from_secs(2 * 3600));
+        assert!(
+            history
+                .rates(Duration::from_secs(3600))
+                .iter()
+                .all(|&r| r == 0.0)
+        );
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let mut bucket = LatencyBucket::default();
+        assert_eq!(bucket.percentile(0.5), None);
+        for ms in [3.0, 4.0, 4.5, 8.0, 180.0] {
+            bucket.add(ms);
+        }
+        // Percentiles are the upper bound of their bin, capped at the slowest request
+        assert_eq!(bucket.percentile(0.5), Some(5.0));
+        assert_eq!(bucket.percentile(0.99), Some(180.0));
+        bucket.add(9_000.0);
+        assert_eq!(bucket.percentile(1.0), Some(9_000.0));
+        assert_eq!(bucket.counts[LATENCY_BINS_MS.len()], 1);
+
+        let mut history = History::<LatencyBucket>::new(config("1s", "1m"));
+        history.add_latency(12.0);
+        history.advance(Duration::from_secs(2));
+        history.add_latency(40.0);
+        history.advance(Duration::from_secs(3));
+        let buckets = history.buckets(Duration::from_secs(60));
+        assert_eq!(buckets[0].max_ms, 40.0);
+        assert_eq!(buckets[1], LatencyBucket::default());
+        let merged = merge_columns(&buckets, 2);
+        assert_eq!(merged.len(), 30);
+        assert_eq!(merged[0].percentile(0.5), Some(40.0));
+        assert_eq!(merged[1].max_ms, 12.0);
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.percentile(0.5), None);
+        for ms in 1..=1000 {
+            histogram.add(ms as f64);
+        }
+        histogram.add(0.0);
+        let within = |p: f64, expected: f64| {
+            let ms = histogram.percentile(p).unwrap();
+            assert!(
+                (expected..=expected * 1.05).contains(&ms),
+                "p{} = {}",
+                p,
+                ms
+            );
+        };
+        within(0.5, 500.0);
+        within(0.9, 900.0);
+        within(0.999, 999.0);
+        assert_eq!(histogram.percentile(1.0), Some(1000.0));
+        assert_eq!(histogram.percentile(0.0005), Some(FIRST_BIN_MS));
+    }
+}
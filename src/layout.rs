@@ -0,0 +1,367 @@
+use crate::config::LayoutConfig;
+use anyhow::{anyhow, bail};
+
+/// Terminals narrower or shorter than this get the compact layout in `auto` mode
+pub const COMPACT_BELOW: (u16, u16) = (120, 35);
+
+/// A panel of the TUI dashboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Panel {
+    /// One-line overview of rates, errors, latency and connections
+    Summary,
+    RpsStats,
+    DelayStats,
+    ServerStats,
+    RpsChart,
+    /// Latency percentiles, or the heatmap ('h')
+    LatencyChart,
+    ConnectionsChart,
+    Logs,
+    /// Per-route table, swapped for clients ('c') or retries ('r')
+    Routes,
+    Clients,
+    Retries,
+}
+
+/// Panel names as used in layouts
+const PANELS: [(&str, Panel); 11] = [
+    ("summary", Panel::Summary),
+    ("rps-stats", Panel::RpsStats),
+    ("delay-stats", Panel::DelayStats),
+    ("server-stats", Panel::ServerStats),
+    ("rps-chart", Panel::RpsChart),
+    ("latency-chart", Panel::LatencyChart),
+    ("connections-chart", Panel::ConnectionsChart),
+    ("logs", Panel::Logs),
+    ("routes", Panel::Routes),
+    ("clients", Panel::Clients),
+    ("retries", Panel::Retries),
+];
+
+impl std::str::FromStr for Panel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PANELS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|&(_, panel)| panel)
+            .ok_or_else(|| {
+                let names: Vec<&str> = PANELS.iter().map(|(name, _)| *name).collect();
+                anyhow!("Invalid panel '{}'. Valid panels: {}", s, names.join(", "))
+            })
+    }
+}
+
+/// Height of a layout row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowHeight {
+    /// Percentage of the terminal
+    Percent(u16),
+    /// Terminal lines
+    Lines(u16),
+}
+
+/// A row of panels with their widths as percentages of the row
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutRow {
+    pub height: RowHeight,
+    pub panels: Vec<(Panel, u16)>,
+}
+
+impl LayoutRow {
+    fn new(height: RowHeight, panels: &[(Panel, u16)]) -> Self {
+        Self {
+            height,
+            panels: panels.to_vec(),
+        }
+    }
+}
+
+/// Which panels the dashboard shows, and where
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanelLayout {
+    pub name: &'static str,
+    pub rows: Vec<LayoutRow>,
+}
+
+impl PanelLayout {
+    /// Every panel: statistics on top, charts in the middle, logs and routes at the bottom
+    pub fn full() -> Self {
+        use Panel::*;
+        Self {
+            name: "full",
+            rows: vec![
+                LayoutRow::new(
+                    RowHeight::Percent(30),
+                    &[(RpsStats, 33), (DelayStats, 34), (ServerStats, 33)],
+                ),
+                LayoutRow::new(
+                    RowHeight::Percent(40),
+                    &[(RpsChart, 40), (LatencyChart, 35), (ConnectionsChart, 25)],
+                ),
+                LayoutRow::new(RowHeight::Percent(30), &[(Logs, 60), (Routes, 40)]),
+            ],
+        }
+    }
+
+    /// A summary line, the RPS chart and the logs, for small terminals
+    pub fn compact() -> Self {
+        use Panel::*;
+        Self {
+            name: "compact",
+            rows: vec![
+                LayoutRow::new(RowHeight::Lines(3), &[(Summary, 100)]),
+                LayoutRow::new(RowHeight::Percent(50), &[(RpsChart, 100)]),
+                LayoutRow::new(RowHeight::Percent(50), &[(Logs, 100)]),
+            ],
+        }
+    }
+
+    /// A summary line and the charts, large enough to read from across the room
+    pub fn charts() -> Self {
+        use Panel::*;
+        Self {
+            name: "charts",
+            rows: vec![
+                LayoutRow::new(RowHeight::Lines(3), &[(Summary, 100)]),
+                LayoutRow::new(
+                    RowHeight::Percent(50),
+                    &[(RpsChart, 60), (LatencyChart, 40)],
+                ),
+                LayoutRow::new(
+                    RowHeight::Percent(50),
+                    &[(ConnectionsChart, 40), (Routes, 60)],
+                ),
+            ],
+        }
+    }
+
+    /// The `custom` layout from the config file. Rows and panels without a size share
+    /// what the others leave.
+    pub fn from_config(config: &LayoutConfig) -> anyhow::Result<Self> {
+        if config.rows.is_empty() {
+            bail!("The layout has no rows");
+        }
+        if config
+            .rows
+            .iter()
+            .any(|row| row.height.is_some() && row.lines.is_some())
+        {
+            bail!("A layout row has both a height and lines");
+        }
+        // Rows sized in lines take no share of the percentages
+        let percents: Vec<Option<u16>> = config
+            .rows
+            .iter()
+            .map(|row| row.lines.map_or(row.height, |_| Some(0)))
+            .collect();
+        let heights = share("row heights", &percents)?;
+
+        let mut rows = Vec::new();
+        for (row, percent) in config.rows.iter().zip(heights) {
+            if row.panels.is_empty() {
+                bail!("A layout row has no panels");
+            }
+            let widths: Vec<Option<u16>> = row.panels.iter().map(|panel| panel.width).collect();
+            let panels = row
+                .panels
+                .iter()
+                .zip(share("panel widths", &widths)?)
+                .map(|(panel, width)| Ok((panel.panel.parse()?, width)))
+                .collect::<anyhow::Result<_>>()?;
+            let height = row
+                .lines
+                .map_or(RowHeight::Percent(percent), RowHeight::Lines);
+            rows.push(LayoutRow { height, panels });
+        }
+        Ok(Self {
+            name: "custom",
+            rows,
+        })
+    }
+}
+
+/// Fill in the unset percentages with equal shares of what the set ones leave
+fn share(what: &str, percents: &[Option<u16>]) -> anyhow::Result<Vec<u16>> {
+    // Summed wider than the percentages, so large values are rejected instead of overflowing
+    let set: u32 = percents
+        .iter()
+        .flatten()
+        .map(|&percent| u32::from(percent))
+        .sum();
+    if set > 100 {
+        bail!("The layout's {} add up to {}%, over 100%", what, set);
+    }
+    let unset = percents.iter().filter(|percent| percent.is_none()).count();
+    let each = (100 - set as usize).checked_div(unset).unwrap_or(0) as u16;
+    Ok(percents
+        .iter()
+        .map(|percent| percent.unwrap_or(each))
+        .collect())
+}
+
+/// The layouts the dashboard cycles through ('v'), and the one shown
+#[derive(Debug, Clone)]
+pub struct Layouts {
+    layouts: Vec<PanelLayout>,
+    /// Index into `layouts`; `None` picks full or compact by the terminal size
+    selected: Option<usize>,
+}
+
+impl Default for Layouts {
+    fn default() -> Self {
+        Self {
+            layouts: vec![
+                PanelLayout::full(),
+                PanelLayout::compact(),
+                PanelLayout::charts(),
+            ],
+            selected: None,
+        }
+    }
+}
+
+impl Layouts {
+    /// The presets, plus `custom` if configured, starting with the one named `initial`
+    /// (`auto`, `full`, `compact`, `charts` or `custom`)
+    pub fn new(custom: Option<PanelLayout>, initial: &str) -> anyhow::Result<Self> {
+        let mut layouts = Self::default();
+        layouts.layouts.extend(custom);
+        if initial != "auto" {
+            let index = layouts
+                .layouts
+                .iter()
+                .position(|layout| layout.name == initial)
+                .ok_or_else(|| {
+                    let names: Vec<&str> =
+                        layouts.layouts.iter().map(|layout| layout.name).collect();
+                    anyhow!(
+                        "Invalid layout '{}'. Valid layouts: auto, {}",
+                        initial,
+                        names.join(", ")
+                    )
+                })?;
+            layouts.selected = Some(index);
+        }
+        Ok(layouts)
+    }
+
+    /// The layout to draw on a terminal of `width` x `height`
+    pub fn current(&self, width: u16, height: u16) -> &PanelLayout {
+        match self.selected {
+            Some(index) => &self.layouts[index],
+            None if width < COMPACT_BELOW.0 || height < COMPACT_BELOW.1 => &self.layouts[1],
+            None => &self.layouts[0],
+        }
+    }
+
+    /// Name of the selected layout, or `auto`
+    pub fn name(&self) -> &'static str {
+        self.selected
+            .map_or("auto", |index| self.layouts[index].name)
+    }
+
+    /// Switch to the next layout, back to `auto` after the last one
+    pub fn cycle(&mut self) {
+        self.selected = match self.selected {
+            None => Some(0),
+            Some(index) if index + 1 < self.layouts.len() => Some(index + 1),
+            Some(_) => None,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn custom(json: &str) -> anyhow::Result<PanelLayout> {
+        PanelLayout::from_config(&Config::parse(json).unwrap().layout.unwrap())
+    }
+
+    #[test]
+    fn test_custom_layout() {
+        let layout = custom(
+            r#"{"layout": {"rows": [
+                {"lines": 3, "panels": [{"panel": "summary"}]},
+                {"height": 60, "panels": [{"panel": "rps-chart", "width": 50}, {"panel": "latency-chart"}, {"panel": "clients"}]},
+                {"panels": [{"panel": "logs"}]}
+            ]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            layout.rows,
+            vec![
+                LayoutRow::new(RowHeight::Lines(3), &[(Panel::Summary, 100)]),
+                LayoutRow::new(
+                    RowHeight::Percent(60),
+                    &[
+                        (Panel::RpsChart, 50),
+                        (Panel::LatencyChart, 25),
+                        (Panel::Clients, 25)
+                    ]
+                ),
+                // The rest of the height
+                LayoutRow::new(RowHeight::Percent(40), &[(Panel::Logs, 100)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_layouts() {
+        let error = |json: &str| custom(json).unwrap_err().to_string();
+        assert_eq!(
+            error(r#"{"layout": {"rows": [{"panels": [{"panel": "graph"}]}]}}"#),
+            "Invalid panel 'graph'. Valid panels: summary, rps-stats, delay-stats, server-stats, rps-chart, latency-chart, connections-chart, logs, routes, clients, retries"
+        );
+        assert_eq!(
+            error(
+                r#"{"layout": {"rows": [{"height": 70, "panels": [{"panel": "logs"}]}, {"height": 40, "panels": [{"panel": "routes"}]}]}}"#
+            ),
+            "The layout's row heights add up to 110%, over 100%"
+        );
+        assert_eq!(
+            error(
+                r#"{"layout": {"rows": [{"panels": [{"panel": "logs", "width": 65535}, {"panel": "routes", "width": 65535}]}]}}"#
+            ),
+            "The layout's panel widths add up to 131070%, over 100%"
+        );
+        assert_eq!(
+            error(r#"{"layout": {"rows": [{"panels": []}]}}"#),
+            "A layout row has no panels"
+        );
+        assert_eq!(
+            error(r#"{"layout": {"rows": []}}"#),
+            "The layout has no rows"
+        );
+    }
+
+    #[test]
+    fn test_layout_selection() {
+        let mut layouts = Layouts::new(None, "auto").unwrap();
+        assert_eq!(layouts.current(160, 50).name, "full");
+        assert_eq!(layouts.current(100, 50).name, "compact");
+        assert_eq!(layouts.current(160, 30).name, "compact");
+        layouts.cycle();
+        assert_eq!(layouts.current(100, 30).name, "full");
+        for name in ["compact", "charts", "auto"] {
+            layouts.cycle();
+            assert_eq!(layouts.name(), name);
+        }
+
+        let custom = PanelLayout {
+            name: "custom",
+            rows: Vec::new(),
+        };
+        assert_eq!(
+            Layouts::new(Some(custom), "custom").unwrap().name(),
+            "custom"
+        );
+        assert_eq!(
+            Layouts::new(None, "custom").unwrap_err().to_string(),
+            "Invalid layout 'custom'. Valid layouts: auto, full, compact, charts"
+        );
+    }
+}
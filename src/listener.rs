@@ -0,0 +1,479 @@
+use crate::clock::Clock;
+use crate::compression::CompressionStats;
+use crate::concurrency::ConcurrencyLimit;
+use crate::connections::ConnectionStats;
+use crate::controls::{Controls, Pause};
+use crate::scenario::Scenario;
+use crate::state::AppState;
+use anyhow::{Context, anyhow};
+use axum::Router;
+use axum::extract::connect_info::Connected;
+use hyper::server::accept;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use std::task::{self, Poll, ready};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpSocket, TcpStream, UnixListener, UnixStream};
+
+/// A wait on the listener's clock
+type Timer = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Where a listener accepts connections
+#[derive(Debug, Clone, PartialEq)]
+pub enum BindAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl std::str::FromStr for BindAddr {
+    type Err = anyhow::Error;
+
+    /// Parse `host:port` (`[::1]:8080` for IPv6) or `unix:/path/to.sock`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err(anyhow!("Unix socket path must not be empty"));
+            }
+            return Ok(BindAddr::Unix(PathBuf::from(path)));
+        }
+        s.parse::<SocketAddr>().map(BindAddr::Tcp).map_err(|_| {
+            anyhow!(
+                "Invalid bind address '{}'. Expected 'host:port', '[ipv6]:port' or 'unix:/path'",
+                s
+            )
+        })
+    }
+}
+
+impl fmt::Display for BindAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindAddr::Tcp(addr) => write!(f, "http://{}", addr),
+            BindAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Name and address of a listener, as shown in the TUI
+#[derive(Debug, Clone)]
+pub struct ListenerInfo {
+    pub name: String,
+    pub address: String,
+    pub upstream: Option<String>,
+    /// Response body bytes sent, updated as bodies are streamed
+    pub bytes_sent: Arc<AtomicU64>,
+    /// Responses and bytes per content encoding
+    pub compression: Arc<CompressionStats>,
+    /// Worker pool and queue gauges, if concurrency is limited
+    pub concurrency: Option<Arc<ConcurrencyLimit>>,
+    /// Open connections and requests in flight
+    pub connections: Arc<ConnectionStats>,
+    /// Settings changed from the TUI
+    pub controls: Arc<Controls>,
+}
+
+/// The address of the client on the other end of a connection (`None` over Unix sockets)
+#[derive(Debug, Clone, Copy)]
+pub struct PeerAddr(pub Option<SocketAddr>);
+
+impl Connected<&TcpStream> for PeerAddr {
+    fn connect_info(stream: &TcpStream) -> Self {
+        PeerAddr(stream.peer_addr().ok())
+    }
+}
+
+impl Connected<&UnixStream> for PeerAddr {
+    fn connect_info(_: &UnixStream) -> Self {
+        PeerAddr(None)
+    }
+}
+
+/// A listener with its own bind address and profile
+pub struct Listener {
+    pub name: String,
+    pub addr: BindAddr,
+    pub state: AppState,
+}
+
+impl Listener {
+    pub fn info(&self) -> ListenerInfo {
+        ListenerInfo {
+            name: self.name.clone(),
+            address: self.addr.to_string(),
+            upstream: self.state.upstream.as_ref().map(|u| u.to_string()),
+            bytes_sent: self.state.bytes_sent.clone(),
+            compression: self.state.compression_stats.clone(),
+            concurrency: self.state.concurrency.clone(),
+            connections: self.state.connections.clone(),
+            controls: self.state.controls.clone(),
+        }
+    }
+
+    /// Bind the listener and serve `app` until `shutdown` completes
+    pub async fn serve(
+        self,
+        app: Router<AppState>,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> anyhow::Result<()> {
+        let clock = self.state.clock.clone();
+        let mut gate = OutageGate::new(
+            self.state.scenario.clone(),
+            self.state.controls.clone(),
+            clock.clone(),
+        );
+        // Every accepted connection is counted until hyper drops it
+        let connections = self.state.connections.clone();
+        let app = app.with_state(self.state);
+        match self.addr {
+            BindAddr::Tcp(addr) => {
+                let mut listener =
+                    Some(bind_tcp(addr).with_context(|| format!("Failed to bind {}", addr))?);
+                let mut backoff = AcceptBackoff::new(clock.clone());
+                let incoming = accept::poll_fn(move |cx| {
+                    loop {
+                        match (&listener, gate.poll_open(cx)) {
+                            (Some(socket), true) => {
+                                return backoff.poll_accept(cx, |cx| socket.poll_accept(cx)).map(
+                                    |(stream, _)| {
+                                        Some(Ok::<_, io::Error>(connections.track(stream)))
+                                    },
+                                );
+                            }
+                            (Some(_), false) => listener = None,
+                            (None, false) => return Poll::Pending,
+                            (None, true) => match bind_tcp(addr) {
+                                Ok(socket) => listener = Some(socket),
+                                Err(_) => {
+                                    gate.retry_later(cx);
+                                    return Poll::Pending;
+                                }
+                            },
+                        }
+                    }
+                });
+                axum::Server::builder(incoming)
+                    .serve(app.into_make_service_with_connect_info::<PeerAddr>())
+                    .with_graceful_shutdown(shutdown)
+                    .await?;
+            }
+            BindAddr::Unix(path) => {
+                remove_stale_socket(&path)?;
+                let (socket, id) = bind_unix(&path)
+                    .with_context(|| format!("Failed to bind {}", path.display()))?;
+                let mut listener = Some(socket);
+                // The socket file this listener bound, so one bound by someone else isn't removed
+                let bound = Arc::new(Mutex::new(id));
+                let socket_path = path.clone();
+                let bound_by_accept = bound.clone();
+                let mut backoff = AcceptBackoff::new(clock.clone());
+                let incoming = accept::poll_fn(move |cx| {
+                    loop {
+                        match (&listener, gate.poll_open(cx)) {
+                            (Some(socket), true) => {
+                                return backoff.poll_accept(cx, |cx| socket.poll_accept(cx)).map(
+                                    |(stream, _)| {
+                                        Some(Ok::<_, io::Error>(connections.track(stream)))
+                                    },
+                                );
+                            }
+                            (Some(_), false) => {
+                                listener = None;
+                                let id = bound_by_accept.lock().unwrap().take();
+                                remove_own_socket(&socket_path, id);
+                            }
+                            (None, false) => return Poll::Pending,
+                            (None, true) => match bind_unix(&socket_path) {
+                                Ok((socket, id)) => {
+                                    listener = Some(socket);
+                                    *bound_by_accept.lock().unwrap() = id;
+                                }
+                                Err(_) => {
+                                    gate.retry_later(cx);
+                                    return Poll::Pending;
+                                }
+                            },
+                        }
+                    }
+                });
+                let result = axum::Server::builder(incoming)
+                    .serve(app.into_make_service_with_connect_info::<PeerAddr>())
+                    .with_graceful_shutdown(shutdown)
+                    .await;
+                let id = bound.lock().unwrap().take();
+                remove_own_socket(&path, id);
+                result?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Bind a TCP listening socket; `SO_REUSEADDR` lets it rebind after an outage
+fn bind_tcp(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    socket.set_reuseaddr(true)?;
+    socket.bind(addr)?;
+    socket.listen(1024)
+}
+
+/// Remove a socket left behind at `path` by a previous run. Anything else there is kept,
+/// and binding fails on it.
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// The device and inode of a socket file, telling it apart from one bound later at the
+/// same path
+type SocketId = (u64, u64);
+
+/// The identity of the socket file at `path`, if there is one
+fn socket_id(path: &Path) -> Option<SocketId> {
+    std::fs::symlink_metadata(path)
+        .ok()
+        .filter(|m| m.file_type().is_socket())
+        .map(|m| (m.dev(), m.ino()))
+}
+
+/// Bind a Unix socket, returning the identity of the socket file it created
+fn bind_unix(path: &Path) -> io::Result<(UnixListener, Option<SocketId>)> {
+    let listener = UnixListener::bind(path)?;
+    Ok((listener, socket_id(path)))
+}
+
+/// Remove the socket file at `path` if it is still the one with identity `id`; another
+/// process may have bound the path since
+fn remove_own_socket(path: &Path, id: Option<SocketId>) {
+    if id.is_some() && socket_id(path) == id {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// How long a listener waits after an accept error such as running out of file descriptors
+const ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Keeps a listener accepting through accept errors, as hyper's `AddrIncoming` does:
+/// errors from a single connection are skipped, and others are logged and retried after
+/// `ACCEPT_BACKOFF`. Hyper would end the server on any error handed to it.
+struct AcceptBackoff {
+    clock: Arc<dyn Clock>,
+    timer: Option<Timer>,
+}
+
+impl AcceptBackoff {
+    fn new(clock: Arc<dyn Clock>) -> Self {
+        Self { clock, timer: None }
+    }
+
+    /// The next connection from `accept`; `Pending` while backing off
+    fn poll_accept<S>(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        mut accept: impl FnMut(&mut task::Context<'_>) -> Poll<io::Result<S>>,
+    ) -> Poll<S> {
+        if let Some(timer) = &mut self.timer {
+            ready!(timer.as_mut().poll(cx));
+            self.timer = None;
+        }
+        loop {
+            match ready!(accept(cx)) {
+                Ok(stream) => return Poll::Ready(stream),
+                Err(err) if is_connection_error(&err) => continue,
+                Err(err) => {
+                    eprintln!(
+                        "Accept error: {} (retrying in {}s)",
+                        err,
+                        ACCEPT_BACKOFF.as_secs()
+                    );
+                    let mut timer = self.clock.sleep(ACCEPT_BACKOFF);
+                    if timer.as_mut().poll(cx).is_pending() {
+                        self.timer = Some(timer);
+                        return Poll::Pending;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Errors that only concern the connection being accepted
+fn is_connection_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+/// Closes a listener's socket during scenario outages and while paused, so connection
+/// attempts are refused
+struct OutageGate {
+    scenario: Option<Arc<Scenario>>,
+    controls: Arc<Controls>,
+    clock: Arc<dyn Clock>,
+    timer: Option<Timer>,
+}
+
+impl OutageGate {
+    fn new(
+        scenario: Option<Arc<Scenario>>,
+        controls: Arc<Controls>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            scenario,
+            controls,
+            clock,
+            timer: None,
+        }
+    }
+
+    /// Whether the socket should be open now. Wakes the task when the phase changes,
+    /// and every so often while paused from the TUI.
+    fn poll_open(&mut self, cx: &mut task::Context<'_>) -> bool {
+        if self.controls.pause() == Pause::Refused {
+            self.retry_later(cx);
+            return false;
+        }
+        let Some(phase) = self.scenario.as_ref().and_then(|s| s.current()) else {
+            return true;
+        };
+        let open = !phase.conditions.outage;
+        self.wake_after(phase.remaining, cx);
+        open
+    }
+
+    /// Wake the task shortly to try binding again
+    fn retry_later(&mut self, cx: &mut task::Context<'_>) {
+        self.wake_after(Duration::from_millis(100), cx);
+    }
+
+    fn wake_after(&mut self, duration: Duration, cx: &mut task::Context<'_>) {
+        let mut timer = self.clock.sleep(duration);
+        let _ = timer.as_mut().poll(cx);
+        self.timer = Some(timer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::routes::DelayConfig;
+    use crate::scenario::Conditions;
+
+    #[test]
+    fn test_parse_bind_addr() {
+        assert_eq!(
+            "127.0.0.1:8080".parse::<BindAddr>().unwrap(),
+            BindAddr::Tcp(([127, 0, 0, 1], 8080).into())
+        );
+        assert!(matches!(
+            "[::1]:8080".parse::<BindAddr>().unwrap(),
+            BindAddr::Tcp(addr) if addr.is_ipv6()
+        ));
+        assert_eq!(
+            "unix:/tmp/rustrate.sock".parse::<BindAddr>().unwrap(),
+            BindAddr::Unix(PathBuf::from("/tmp/rustrate.sock"))
+        );
+        assert!("unix:".parse::<BindAddr>().is_err());
+        assert!("localhost".parse::<BindAddr>().is_err());
+    }
+
+    #[test]
+    fn test_display_bind_addr() {
+        let addr: BindAddr = "[::1]:8080".parse().unwrap();
+        assert_eq!(addr.to_string(), "http://[::1]:8080");
+    }
+
+    #[test]
+    fn test_remove_stale_socket() {
+        let dir = std::env::temp_dir().join(format!("rustrate-stale-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("stale.sock");
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+        let link = dir.join("link.sock");
+        std::os::unix::fs::symlink(&socket, &link).unwrap();
+
+        // A symlink is left alone, even to a socket
+        remove_stale_socket(&link).unwrap();
+        assert!(std::fs::symlink_metadata(&link).is_ok());
+        remove_stale_socket(&socket).unwrap();
+        assert!(!socket.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_only_own_socket_removed() {
+        let dir = std::env::temp_dir().join(format!("rustrate-own-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("own.sock");
+        let (_ours, ours) = bind_unix(&path).unwrap();
+        assert!(ours.is_some());
+        // Another process replaces the socket while ours is moved out of the way
+        std::fs::rename(&path, dir.join("moved.sock")).unwrap();
+        let (_theirs, theirs) = bind_unix(&path).unwrap();
+        remove_own_socket(&path, ours);
+        assert!(path.exists());
+        remove_own_socket(&path, theirs);
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_accept_backs_off_after_errors() {
+        let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+        let mut backoff = AcceptBackoff::new(clock.clone());
+        let mut results = vec![
+            Ok(1),
+            Err(io::Error::from_raw_os_error(24)),
+            Err(io::ErrorKind::ConnectionAborted.into()),
+            Ok(2),
+        ]
+        .into_iter();
+        let mut accept = |cx: &mut task::Context<'_>| {
+            backoff.poll_accept(cx, |_| Poll::Ready(results.next().unwrap()))
+        };
+        let start = clock.now();
+        assert_eq!(std::future::poll_fn(&mut accept).await, 1);
+        // Out of file descriptors: wait, then skip the aborted connection
+        assert_eq!(std::future::poll_fn(&mut accept).await, 2);
+        assert_eq!(clock.now() - start, ACCEPT_BACKOFF);
+    }
+
+    #[test]
+    fn test_outage_gate_waits_for_the_phase_to_end() {
+        let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+        let scenario = Scenario::parse(
+            r#"{"phases": [{"name": "down", "duration": "10s", "outage": true}, {"name": "up", "duration": "10s"}]}"#,
+            Conditions::base(&DelayConfig::parse("0").unwrap(), 0.0, 500),
+        )
+        .unwrap()
+        .with_clock(clock.clone());
+        let mut gate = OutageGate::new(
+            Some(Arc::new(scenario)),
+            Arc::new(Controls::default()),
+            clock.clone(),
+        );
+        let start = clock.now();
+        let mut cx = task::Context::from_waker(task::Waker::noop());
+        assert!(!gate.poll_open(&mut cx));
+        // The gate's timer runs on the injected clock, until the outage ends
+        assert_eq!(clock.now() - start, Duration::from_secs(10));
+        assert!(gate.poll_open(&mut cx));
+    }
+}
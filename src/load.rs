@@ -0,0 +1,267 @@
+use anyhow::anyhow;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// Utilization beyond which queueing delay stops growing, so saturation stays finite
+const MAX_UTILIZATION: f64 = 0.99;
+
+/// How server load is measured
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LoadMetric {
+    /// Requests being handled right now
+    #[default]
+    InFlight,
+    /// Requests received in the last full second
+    Rps,
+}
+
+impl std::str::FromStr for LoadMetric {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "in-flight" => Ok(Self::InFlight),
+            "rps" => Ok(Self::Rps),
+            _ => Err(anyhow!(
+                "Invalid load metric '{}'. Valid values: in-flight, rps",
+                s
+            )),
+        }
+    }
+}
+
+/// How latency grows with load
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadCurve {
+    /// Extra milliseconds per unit of load
+    Linear(f64),
+    /// An M/M/c queue with the given number of servers: the configured delay is the
+    /// service time, and requests wait longer as utilization approaches 1
+    Mmc(u32),
+    /// Extra milliseconds at the given loads, interpolated in between and held
+    /// after the last point
+    Points(Vec<(f64, f64)>),
+}
+
+impl std::str::FromStr for LoadCurve {
+    type Err = anyhow::Error;
+
+    /// Parse `linear:<ms>`, `mmc:<servers>` or `curve:<load>=<ms>,...`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            anyhow!(
+                "Invalid load latency '{}'. Expected linear:<ms>, mmc:<servers> or curve:<load>=<ms>,...",
+                s
+            )
+        };
+        let (kind, value) = s.split_once(':').ok_or_else(invalid)?;
+        match kind {
+            "linear" => value.parse().map(Self::Linear).map_err(|_| invalid()),
+            "mmc" => match value.parse() {
+                Ok(servers) if servers > 0 => Ok(Self::Mmc(servers)),
+                _ => Err(anyhow!("M/M/c needs at least one server, got '{}'", value)),
+            },
+            "curve" => {
+                let mut points = value
+                    .split(',')
+                    .map(|point| {
+                        let (load, ms) = point.split_once('=')?;
+                        Some((load.trim().parse().ok()?, ms.trim().parse().ok()?))
+                    })
+                    .collect::<Option<Vec<(f64, f64)>>>()
+                    .ok_or_else(invalid)?;
+                points.sort_by(|a, b| a.0.total_cmp(&b.0));
+                Ok(Self::Points(points))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Probability that an arriving request has to wait in an M/M/c queue with
+/// `servers` servers and an offered load of `traffic` (Erlang C)
+fn erlang_c(servers: u32, traffic: f64) -> f64 {
+    // Erlang B by recursion, which stays finite for large server counts
+    let mut blocking = 1.0;
+    for k in 1..=servers {
+        blocking = traffic * blocking / (k as f64 + traffic * blocking);
+    }
+    let servers = servers as f64;
+    servers * blocking / (servers - traffic * (1.0 - blocking))
+}
+
+impl LoadCurve {
+    /// The latency in milliseconds of a request with the given service time under `load`
+    pub fn latency(&self, service_ms: f64, load: f64, metric: LoadMetric) -> f64 {
+        match self {
+            LoadCurve::Linear(per_unit) => service_ms + per_unit * load,
+            LoadCurve::Mmc(servers) => {
+                if service_ms <= 0.0 {
+                    return service_ms;
+                }
+                let servers = *servers as f64;
+                // Requests per millisecond one server can complete
+                let service_rate = 1.0 / service_ms;
+                let utilization = match metric {
+                    LoadMetric::InFlight => load / servers,
+                    LoadMetric::Rps => load / 1000.0 / (servers * service_rate),
+                }
+                .clamp(0.0, MAX_UTILIZATION);
+                let waiting = erlang_c(servers as u32, utilization * servers);
+                service_ms + waiting / (servers * service_rate * (1.0 - utilization))
+            }
+            LoadCurve::Points(points) => {
+                let extra = match points.iter().position(|&(at, _)| at > load) {
+                    None => points.last().map_or(0.0, |&(_, ms)| ms),
+                    Some(0) => points[0].1,
+                    Some(i) => {
+                        let ((x0, y0), (x1, y1)) = (points[i - 1], points[i]);
+                        y0 + (y1 - y0) * (load - x0) / (x1 - x0)
+                    }
+                };
+                service_ms + extra
+            }
+        }
+    }
+}
+
+/// Tracks the load of a listener
+#[derive(Debug)]
+pub struct LoadTracker {
+    pub metric: LoadMetric,
+    in_flight: AtomicU64,
+    started: Instant,
+    /// (second since start, requests in that second, requests in the second before)
+    arrivals: Mutex<(u64, u64, u64)>,
+}
+
+/// Counts a request as in flight until dropped
+pub struct InFlight<'a>(&'a AtomicU64);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl LoadTracker {
+    /// A tracker counting request arrivals from `started`
+    pub fn new(metric: LoadMetric, started: Instant) -> Self {
+        Self {
+            metric,
+            in_flight: AtomicU64::new(0),
+            started,
+            arrivals: Mutex::new((0, 0, 0)),
+        }
+    }
+
+    /// Count an arriving request
+    pub fn arrive(&self, now: Instant) {
+        let second = now.saturating_duration_since(self.started).as_secs();
+        let mut arrivals = self.arrivals.lock().unwrap();
+        match second - arrivals.0.min(second) {
+            0 => arrivals.1 += 1,
+            1 => *arrivals = (second, 1, arrivals.1),
+            _ => *arrivals = (second, 1, 0),
+        }
+    }
+
+    /// Count a request as in flight while the returned guard lives
+    pub fn start(&self) -> InFlight<'_> {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(&self.in_flight)
+    }
+
+    /// The current load by the tracked metric
+    pub fn current(&self, now: Instant) -> f64 {
+        match self.metric {
+            LoadMetric::InFlight => self.in_flight.load(Ordering::Relaxed) as f64,
+            LoadMetric::Rps => {
+                let second = now.saturating_duration_since(self.started).as_secs();
+                let arrivals = self.arrivals.lock().unwrap();
+                match second.saturating_sub(arrivals.0) {
+                    0 => arrivals.2 as f64,
+                    1 => arrivals.1 as f64,
+                    _ => 0.0,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_load_curve() {
+        assert_eq!(
+            "linear:2.5".parse::<LoadCurve>().unwrap(),
+            LoadCurve::Linear(2.5)
+        );
+        assert_eq!("mmc:4".parse::<LoadCurve>().unwrap(), LoadCurve::Mmc(4));
+        assert_eq!(
+            "curve:100=200,0=0".parse::<LoadCurve>().unwrap(),
+            LoadCurve::Points(vec![(0.0, 0.0), (100.0, 200.0)])
+        );
+        assert!("mmc:0".parse::<LoadCurve>().is_err());
+        assert!("curve:10".parse::<LoadCurve>().is_err());
+        assert!("cubic:1".parse::<LoadCurve>().is_err());
+    }
+
+    #[test]
+    fn test_curve_latency() {
+        let metric = LoadMetric::InFlight;
+        assert_eq!(LoadCurve::Linear(2.0).latency(10.0, 5.0, metric), 20.0);
+
+        let curve = LoadCurve::Points(vec![(10.0, 0.0), (20.0, 100.0), (30.0, 1000.0)]);
+        assert_eq!(curve.latency(5.0, 0.0, metric), 5.0);
+        assert_eq!(curve.latency(5.0, 15.0, metric), 55.0);
+        assert_eq!(curve.latency(5.0, 25.0, metric), 555.0);
+        assert_eq!(curve.latency(5.0, 100.0, metric), 1005.0);
+    }
+
+    #[test]
+    fn test_mmc_latency() {
+        // M/M/1 at 50% utilization doubles the service time
+        let mm1 = LoadCurve::Mmc(1);
+        assert!((mm1.latency(10.0, 0.5, LoadMetric::InFlight) - 20.0).abs() < 1e-9);
+        // 50 requests/s against a 10ms service time is also 50% utilization
+        assert!((mm1.latency(10.0, 50.0, LoadMetric::Rps) - 20.0).abs() < 1e-9);
+        // Erlang C for 2 servers at 1 Erlang is 1/3
+        assert!((erlang_c(2, 1.0) - 1.0 / 3.0).abs() < 1e-9);
+
+        // Latency grows steeply towards saturation but stays finite
+        let mmc = LoadCurve::Mmc(8);
+        let light = mmc.latency(10.0, 2.0, LoadMetric::InFlight);
+        let heavy = mmc.latency(10.0, 7.5, LoadMetric::InFlight);
+        let saturated = mmc.latency(10.0, 100.0, LoadMetric::InFlight);
+        assert!(light < 10.1, "{}", light);
+        assert!(heavy > 2.0 * light, "{}", heavy);
+        assert!(saturated.is_finite() && saturated > heavy);
+    }
+
+    #[test]
+    fn test_load_tracker() {
+        let start = Instant::now();
+        let tracker = LoadTracker::new(LoadMetric::InFlight, start);
+        let first = tracker.start();
+        let second = tracker.start();
+        assert_eq!(tracker.current(start), 2.0);
+        drop((first, second));
+        assert_eq!(tracker.current(start), 0.0);
+
+        // RPS counts the last full second
+        let tracker = LoadTracker::new(LoadMetric::Rps, start);
+        for _ in 0..3 {
+            tracker.arrive(start + Duration::from_millis(500));
+        }
+        assert_eq!(tracker.current(start + Duration::from_millis(900)), 0.0);
+        tracker.arrive(start + Duration::from_millis(1500));
+        assert_eq!(tracker.current(start + Duration::from_millis(1600)), 3.0);
+        assert_eq!(tracker.current(start + Duration::from_millis(2100)), 1.0);
+        assert_eq!(tracker.current(start + Duration::from_secs(5)), 0.0);
+    }
+}
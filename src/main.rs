@@ -1,16 +1,67 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use axum::http::HeaderName;
 use clap::Parser;
-use std::net::SocketAddr;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::signal;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinSet;
 
+mod admin;
+mod clock;
+mod compression;
+mod concurrency;
+mod config;
+mod connections;
+mod controls;
+mod formats;
+mod grpc;
+mod history;
+mod layout;
+mod listener;
+mod load;
+mod openapi;
+mod payload;
+mod proxy;
+mod random;
+mod ratelimit;
+mod replay;
+mod report;
+mod retries;
 mod routes;
+mod scenario;
+mod sequence;
 mod state;
+mod templates;
+mod throttle;
 mod tui;
+mod util;
 
-use crate::routes::request_handler;
+use crate::compression::CompressionMode;
+use crate::concurrency::{ConcurrencyLimit, QueueOverflow};
+use crate::config::Config;
+use crate::controls::ControlEvent;
+use crate::grpc::GrpcRegistry;
+use crate::history::HistoryConfig;
+use crate::layout::{Layouts, PanelLayout};
+use crate::listener::{BindAddr, Listener};
+use crate::load::{LoadCurve, LoadMetric};
+use crate::openapi::OpenApi;
+use crate::payload::{BodySize, Payload, PayloadKind};
+use crate::proxy::Upstream;
+use crate::ratelimit::{RateLimitAlgorithm, RateLimitKey, RateLimiter};
+use crate::replay::{Recorder, Replay, ReplayLatency};
+use crate::report::ReportConfig;
+use crate::retries::RetryTracker;
+use crate::routes::{DelayConfig, ErrorConfig, request_handler};
+use crate::scenario::{Conditions, Scenario};
+use crate::sequence::Sequences;
 use crate::state::{AppEvent, AppState};
-use crate::tui::run_tui;
+use crate::templates::ResponseTemplate;
+use crate::throttle::ThrottleConfig;
+use crate::tui::{TuiOptions, run_tui};
+use crate::util::parse_duration;
 
 // ASCII banner
 const BANNER: &str = r#"
@@ -37,10 +88,90 @@ Usage:
     rustrate [OPTIONS]
 Options:
     -p, --port <PORT>      The port number to listen on (default: 31337)
+    -b, --bind <ADDR>      The address to listen on (default: 0.0.0.0)
+                           Use '::' for IPv6 or '127.0.0.1' for localhost only
     -d, --delay <DELAY>    The delay in milliseconds for each request (default: 0)
                            You can specify a range using 'min-max' format (e.g., 30-150)
-    -f, --format <FORMAT>  The HTTP response output format (default: json)
-                           Valid formats: json, text
+    -f, --format <FORMAT>  The HTTP response output format (default: json), used when the
+                           request's Accept header doesn't ask for another one
+                           Valid formats: json, text, xml, msgpack, cbor, protobuf, yaml,
+                           html
+        --error-rate <PCT> Percentage of requests failed with an injected error (default: 0)
+        --error-status <STATUS>
+                           The HTTP status of injected errors (default: 503)
+    -u, --upstream <URL>   Proxy requests to a real upstream (e.g. http://localhost:8080),
+                           applying the delay and error models in front of it
+        --record <FILE>    Append proxied request/response pairs to a JSON Lines file
+                           (requires --upstream)
+        --replay <FILE>    Serve responses recorded with --record
+        --replay-latency <LATENCY>
+                           Latency of replayed responses (default: recorded)
+                           Valid values: recorded, configured
+        --openapi <SPEC>   Mock every operation of an OpenAPI 3 spec (YAML or JSON),
+                           rejecting requests that don't validate with a 400
+        --body-size <SIZE> Size of generated response bodies: fixed (e.g. 256KiB), a range
+                           (1KiB-1MiB) or weighted sizes (1KiB:90,1MiB:10)
+        --payload <KIND>   Content of generated response bodies (default: json, or lorem
+                           for text). Valid values: random, lorem, json, json:<items>,
+                           file:<path>
+        --bandwidth <RATE> Limit the bandwidth of each response body (e.g. 256KiB/s)
+        --trickle <RATE>   Send response bodies in chunks with a pause between them
+                           (e.g. 16B/250ms)
+        --upload-bandwidth <RATE>
+                           Read request bodies no faster than the given rate (e.g. 64KiB/s)
+        --compression <MODE>
+                           Response compression (default: auto, negotiated from
+                           Accept-Encoding). Valid values: auto, off, br, zstd, gzip,
+                           deflate (forced), mislabel:<encoding> (header only)
+        --rate-limit <RATE>
+                           Reply 429 with Retry-After to requests over the given rate
+                           (e.g. 100/s, 1000/m)
+        --rate-limit-algorithm <ALGORITHM>
+                           How requests are counted (default: token-bucket)
+                           Valid values: token-bucket, fixed-window
+        --rate-limit-key <KEY>
+                           What requests share a limit (default: global)
+                           Valid values: global, ip, route, header:<name>
+        --max-concurrency <N>
+                           Serve at most N requests at once, queueing the rest
+        --queue-size <N>   Requests that may wait for a worker (default: 100)
+        --queue-overflow <MODE>
+                           What happens when the queue is full (default: 503)
+                           Valid values: 503, refuse (close the connection), wait
+        --load-latency <MODEL>
+                           Grow the delay with load: linear:<ms per unit of load>,
+                           mmc:<servers> (M/M/c queue, the delay is the service time) or
+                           curve:<load>=<ms>,... (e.g. curve:0=0,50=20,100=500)
+        --load-metric <METRIC>
+                           How load is measured (default: in-flight)
+                           Valid values: in-flight, rps
+        --overload-threshold <LOAD>
+                           Fail requests with --error-status while load is above this
+        --scenario <FILE>  JSON timeline of phases changing the delay, errors and
+                           availability (e.g. healthy, latency spikes, 503s, outage)
+        --seed <N>         Seed every random decision, so runs can be reproduced
+                           (default: a random seed, printed at startup and exit)
+        --client-header <NAME>
+                           Tell clients apart by this header (e.g. X-Client-Id) instead of
+                           their IP address; press 'c' in the TUI for the clients table
+        --retry-key-header <NAME>
+                           Header grouping a request's attempts (default: Idempotency-Key);
+                           without it, the same client, method, path and body are grouped,
+                           and repeat after a failure
+        --retry-window <DURATION>
+                           How soon a repeated request must follow to count as a retry
+                           (default: 30s); press 'r' in the TUI for the retries panel
+        --rps-bucket <WIDTH>
+                           Width of the RPS chart's buckets, 100ms to 10s (default: 1s)
+        --rps-window <WINDOW>
+                           How much RPS history is kept and charted, 1m to 1h (default: 1m)
+                           Press '+'/'-' in the TUI to zoom between windows
+        --layout <LAYOUT>  TUI panels to show: auto, full, compact, charts or custom (from
+                           the config file); 'v' switches in the TUI (default: auto)
+                           Press '?' in the TUI for its keys, including runtime controls
+        --report <FILE>    Write a report of the run at shutdown: .html (one self-contained
+                           page with charts) or .md, from the same stats as the TUI
+    -c, --config <FILE>    JSON config file (e.g. gRPC methods, listeners)
     -r, --run              Run the server (if not set, only shows help)
     -h, --help             Print help information
     -V, --version          Print version information
@@ -65,6 +196,15 @@ struct Args {
     )]
     port: u16,
 
+    /// The address to listen on
+    #[arg(
+        short,
+        long,
+        default_value = "0.0.0.0",
+        help = "The address to listen on (default: 0.0.0.0). Use '::' for IPv6 or '127.0.0.1' for localhost only"
+    )]
+    bind: IpAddr,
+
     /// The delay in milliseconds for each request
     #[arg(
         short,
@@ -79,10 +219,255 @@ struct Args {
         short,
         long,
         default_value = "json",
-        help = "The HTTP response output format (default: json). Valid formats: json, text"
+        help = "The HTTP response output format (default: json), used unless the Accept header asks for another one. Valid formats: json, text, xml, msgpack, cbor, protobuf, yaml, html"
     )]
     format: OutputFormat,
 
+    /// Percentage of requests that fail with an injected error
+    #[arg(
+        long,
+        default_value_t = 0.0,
+        help = "Percentage of requests failed with an injected error (default: 0)"
+    )]
+    error_rate: f64,
+
+    /// The HTTP status of injected errors
+    #[arg(
+        long,
+        default_value_t = 503,
+        help = "The HTTP status of injected errors (default: 503). gRPC calls get the matching gRPC status"
+    )]
+    error_status: u16,
+
+    /// Upstream to proxy requests to
+    #[arg(
+        short,
+        long,
+        help = "Proxy requests to a real upstream (e.g. http://localhost:8080), applying the delay and error models in front of it"
+    )]
+    upstream: Option<String>,
+
+    /// Record proxied traffic to a file
+    #[arg(
+        long,
+        requires = "upstream",
+        help = "Append proxied request/response pairs to a JSON Lines file (requires --upstream)"
+    )]
+    record: Option<PathBuf>,
+
+    /// Replay recorded traffic from a file
+    #[arg(
+        long,
+        conflicts_with_all = ["upstream", "record"],
+        help = "Serve responses recorded with --record, matched by method, path, query and body"
+    )]
+    replay: Option<PathBuf>,
+
+    /// Latency of replayed responses
+    #[arg(
+        long,
+        default_value = "recorded",
+        help = "Latency of replayed responses (default: recorded). Valid values: recorded (as observed when recording), configured (the delay model)"
+    )]
+    replay_latency: ReplayLatency,
+
+    /// OpenAPI spec to mock
+    #[arg(
+        long,
+        conflicts_with_all = ["upstream", "replay"],
+        help = "Mock every operation of an OpenAPI 3 spec (YAML or JSON), rejecting requests that don't validate with a 400"
+    )]
+    openapi: Option<PathBuf>,
+
+    /// Size of generated response bodies
+    #[arg(
+        long,
+        help = "Size of generated response bodies: fixed (e.g. 256KiB), a range (1KiB-1MiB) or weighted sizes (1KiB:90,1MiB:10)"
+    )]
+    body_size: Option<BodySize>,
+
+    /// Content of generated response bodies
+    #[arg(
+        long,
+        help = "Content of generated response bodies (default: json, or lorem for text). Valid values: random, lorem, json, json:<items> (an array of N items), file:<path>"
+    )]
+    payload: Option<PayloadKind>,
+
+    /// Bandwidth limit of response bodies
+    #[arg(
+        long,
+        conflicts_with = "trickle",
+        help = "Limit the bandwidth of each response body (e.g. 256KiB/s)"
+    )]
+    bandwidth: Option<String>,
+
+    /// Send response bodies in small chunks with pauses
+    #[arg(
+        long,
+        help = "Send response bodies in chunks of the given size with a pause between them (e.g. 16B/250ms)"
+    )]
+    trickle: Option<String>,
+
+    /// Bandwidth limit for reading request bodies
+    #[arg(
+        long,
+        help = "Read request bodies no faster than the given rate (e.g. 64KiB/s), applying backpressure to uploads"
+    )]
+    upload_bandwidth: Option<String>,
+
+    /// Response compression
+    #[arg(
+        long,
+        default_value = "auto",
+        help = "Response compression (default: auto, negotiated from Accept-Encoding). Valid values: auto, off, br, zstd, gzip, deflate (always used), mislabel:<encoding> (claims the encoding but sends the body uncompressed)"
+    )]
+    compression: CompressionMode,
+
+    /// Rate limit
+    #[arg(
+        long,
+        help = "Reply 429 with Retry-After and X-RateLimit-* headers to requests over the given rate (e.g. 100/s, 1000/m, 10/5s)"
+    )]
+    rate_limit: Option<String>,
+
+    /// Rate limit algorithm
+    #[arg(
+        long,
+        default_value = "token-bucket",
+        help = "How rate-limited requests are counted (default: token-bucket). Valid values: token-bucket, fixed-window"
+    )]
+    rate_limit_algorithm: RateLimitAlgorithm,
+
+    /// What requests share a rate limit
+    #[arg(
+        long,
+        default_value = "global",
+        help = "What requests share a rate limit (default: global). Valid values: global, ip, route, header:<name> (e.g. header:x-api-key)"
+    )]
+    rate_limit_key: RateLimitKey,
+
+    /// Worker pool size
+    #[arg(
+        long,
+        help = "Serve at most N requests at once; the rest wait in a queue, so latency grows with load"
+    )]
+    max_concurrency: Option<usize>,
+
+    /// Queue size
+    #[arg(
+        long,
+        default_value_t = 100,
+        help = "Requests that may wait for a worker when --max-concurrency is set (default: 100)"
+    )]
+    queue_size: usize,
+
+    /// Queue overflow behavior
+    #[arg(
+        long,
+        default_value = "503",
+        help = "What happens to requests when the queue is full (default: 503). Valid values: 503, refuse (close the connection without a response), wait (no queue bound)"
+    )]
+    queue_overflow: QueueOverflow,
+
+    /// Load-dependent latency
+    #[arg(
+        long,
+        help = "Grow the delay with load: linear:<ms> (per unit of load), mmc:<servers> (M/M/c queue; --delay is the service time) or curve:<load>=<ms>,... (e.g. curve:0=0,50=20,100=500)"
+    )]
+    load_latency: Option<LoadCurve>,
+
+    /// Load metric
+    #[arg(
+        long,
+        default_value = "in-flight",
+        help = "How load is measured for --load-latency and --overload-threshold (default: in-flight). Valid values: in-flight, rps"
+    )]
+    load_metric: LoadMetric,
+
+    /// Overload threshold
+    #[arg(
+        long,
+        help = "Fail requests with --error-status while load is above this threshold"
+    )]
+    overload_threshold: Option<f64>,
+
+    /// Scenario timeline
+    #[arg(
+        long,
+        help = "JSON timeline of phases changing the delay, errors and availability over the run"
+    )]
+    scenario: Option<PathBuf>,
+
+    /// Seed for delays, errors and generated content
+    #[arg(
+        long,
+        help = "Seed every random decision (delays, errors, generated bodies) so runs can be reproduced (default: random, printed at startup and exit)"
+    )]
+    seed: Option<u64>,
+
+    /// Client identity header
+    #[arg(
+        long,
+        help = "Tell clients apart by this header (e.g. X-Client-Id, User-Agent) in the clients table, instead of their IP address"
+    )]
+    client_header: Option<HeaderName>,
+
+    /// Retry grouping header
+    #[arg(
+        long,
+        default_value = "idempotency-key",
+        help = "Header grouping the attempts of a request for the retries panel; requests without it are grouped by client, method, path and body, and only repeats after a failure count as retries"
+    )]
+    retry_key_header: HeaderName,
+
+    /// Retry window
+    #[arg(
+        long,
+        default_value = "30s",
+        help = "How soon a repeated request must follow the previous attempt to count as a retry (e.g. 10s, 1m)"
+    )]
+    retry_window: String,
+
+    /// RPS bucket width
+    #[arg(
+        long,
+        default_value = "1s",
+        help = "Width of the RPS chart's buckets, 100ms to 10s dividing 10s evenly (e.g. 100ms, 1s, 10s)"
+    )]
+    rps_bucket: String,
+
+    /// RPS history window
+    #[arg(
+        long,
+        default_value = "1m",
+        help = "How much RPS history is kept and charted, 1m to 1h; beyond a minute it is kept in 10s buckets"
+    )]
+    rps_window: String,
+
+    /// TUI layout
+    #[arg(
+        long,
+        default_value = "auto",
+        help = "TUI panels to show: auto (compact on terminals under 120x35), full, compact, charts or custom (the config file's layout)"
+    )]
+    layout: String,
+
+    /// Report written at shutdown
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Write a report of the run to FILE at shutdown: .html (self-contained, with charts) or .md"
+    )]
+    report: Option<PathBuf>,
+
+    /// Optional JSON config file
+    #[arg(
+        short,
+        long,
+        help = "JSON config file (e.g. gRPC methods, listeners with their own profiles)"
+    )]
+    config: Option<PathBuf>,
+
     /// Run the server (if not set, only shows help)
     #[arg(short, long)]
     run: bool,
@@ -92,6 +477,12 @@ struct Args {
 pub enum OutputFormat {
     Json,
     Text,
+    Xml,
+    MsgPack,
+    Cbor,
+    Protobuf,
+    Yaml,
+    Html,
 }
 
 impl std::str::FromStr for OutputFormat {
@@ -101,11 +492,251 @@ impl std::str::FromStr for OutputFormat {
         match s.to_lowercase().as_str() {
             "json" => Ok(OutputFormat::Json),
             "text" => Ok(OutputFormat::Text),
-            _ => Err(anyhow::anyhow!("Invalid format. Valid formats: json, text")),
+            "xml" => Ok(OutputFormat::Xml),
+            "msgpack" | "messagepack" => Ok(OutputFormat::MsgPack),
+            "cbor" => Ok(OutputFormat::Cbor),
+            "protobuf" => Ok(OutputFormat::Protobuf),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "html" => Ok(OutputFormat::Html),
+            _ => Err(anyhow::anyhow!(
+                "Invalid format. Valid formats: json, text, xml, msgpack, cbor, protobuf, yaml, html"
+            )),
         }
     }
 }
 
+impl OutputFormat {
+    /// Every format, in the order the TUI cycles through them
+    const ALL: [OutputFormat; 8] = [
+        OutputFormat::Json,
+        OutputFormat::Text,
+        OutputFormat::Xml,
+        OutputFormat::MsgPack,
+        OutputFormat::Cbor,
+        OutputFormat::Protobuf,
+        OutputFormat::Yaml,
+        OutputFormat::Html,
+    ];
+
+    /// The name `--format` accepts for this format
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Text => "text",
+            OutputFormat::Xml => "xml",
+            OutputFormat::MsgPack => "msgpack",
+            OutputFormat::Cbor => "cbor",
+            OutputFormat::Protobuf => "protobuf",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Html => "html",
+        }
+    }
+
+    /// The next format, back to JSON after the last one
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&format| format == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// Build the listeners from the config, or the single `--bind`/`--port` listener
+fn build_listeners(args: &Args, config: &Config, state: &AppState) -> Result<Vec<Listener>> {
+    if config.listeners.is_empty() {
+        return Ok(vec![Listener {
+            name: "default".to_string(),
+            addr: BindAddr::Tcp((args.bind, args.port).into()),
+            state: state.clone(),
+        }]);
+    }
+
+    let mut listeners: Vec<Listener> = Vec::new();
+    for listener in &config.listeners {
+        let addr: BindAddr = listener.bind.parse()?;
+        let name = listener
+            .name
+            .clone()
+            .unwrap_or_else(|| listener.bind.clone());
+        if listeners.iter().any(|other| other.name == name) {
+            return Err(anyhow::anyhow!("Duplicate listener name '{}'", name));
+        }
+        let delay = DelayConfig::parse(listener.delay.as_deref().unwrap_or(&args.delay))?
+            .with_load_curve(match &listener.load_latency {
+                Some(curve) => Some(curve.parse()?),
+                None => args.load_latency.clone(),
+            });
+        let (error_rate, error_status) = (
+            listener.error_rate.unwrap_or(args.error_rate),
+            listener.error_status.unwrap_or(args.error_status),
+        );
+        let errors = ErrorConfig::new(error_rate, error_status)?
+            .with_overload(listener.overload_threshold.or(args.overload_threshold));
+        let scenario = state.scenario.as_ref().map(|scenario| {
+            Arc::new(scenario.with_base(Conditions::base(&delay, error_rate, error_status)))
+        });
+        let load_metric = match &listener.load_metric {
+            Some(metric) => metric.parse()?,
+            None => args.load_metric,
+        };
+        let format = match &listener.format {
+            Some(format) => format.parse()?,
+            None => args.format,
+        };
+        let upstream = match &listener.upstream {
+            Some(url) => Some(Upstream::new(url)?),
+            None => args.upstream.as_deref().map(Upstream::new).transpose()?,
+        };
+        let template = match &listener.response {
+            Some(response) => Some(Arc::new(ResponseTemplate::new(response).with_context(
+                || format!("Invalid response template for listener '{}'", name),
+            )?)),
+            None => state.template.clone(),
+        };
+        let payload = Payload::new(
+            listener
+                .payload
+                .as_deref()
+                .map(str::parse)
+                .transpose()?
+                .as_ref()
+                .or(args.payload.as_ref()),
+            listener
+                .body_size
+                .as_deref()
+                .map(str::parse)
+                .transpose()?
+                .as_ref()
+                .or(args.body_size.as_ref()),
+            format,
+        )?;
+        if template.is_some() && payload.is_some() {
+            return Err(anyhow::anyhow!(
+                "Listener '{}' can't use both a response template and a payload",
+                name
+            ));
+        }
+        // A listener's bandwidth or trickle replaces both command-line settings
+        let (bandwidth, trickle) = match (&listener.bandwidth, &listener.trickle) {
+            (None, None) => (args.bandwidth.as_deref(), args.trickle.as_deref()),
+            (bandwidth, trickle) => (bandwidth.as_deref(), trickle.as_deref()),
+        };
+        let throttle = ThrottleConfig::new(
+            bandwidth,
+            trickle,
+            listener
+                .upload_bandwidth
+                .as_deref()
+                .or(args.upload_bandwidth.as_deref()),
+        )
+        .and_then(|throttle| throttle.with_routes(&config.routes))
+        .with_context(|| format!("Invalid bandwidth for listener '{}'", name))?;
+        // A listener with its own rate limit settings counts its requests separately
+        let rate_limiter = match (
+            &listener.rate_limit,
+            &listener.rate_limit_algorithm,
+            &listener.rate_limit_key,
+        ) {
+            (None, None, None) => state.rate_limiter.clone(),
+            (rate, algorithm, key) => rate
+                .as_deref()
+                .or(args.rate_limit.as_deref())
+                .map(|rate| {
+                    RateLimiter::new(
+                        rate,
+                        algorithm
+                            .as_deref()
+                            .map(str::parse)
+                            .transpose()?
+                            .unwrap_or(args.rate_limit_algorithm),
+                        key.as_deref()
+                            .map(str::parse)
+                            .transpose()?
+                            .unwrap_or_else(|| args.rate_limit_key.clone()),
+                    )
+                })
+                .transpose()
+                .with_context(|| format!("Invalid rate limit for listener '{}'", name))?
+                .map(Arc::new),
+        };
+        let state = state
+            .clone()
+            .with_listener(&name)
+            .with_template(template)
+            .with_payload(payload.map(Arc::new))
+            .with_throttle(throttle)
+            .with_rate_limit(rate_limiter)
+            .with_concurrency(
+                listener
+                    .max_concurrency
+                    .or(args.max_concurrency)
+                    .map(|max| {
+                        ConcurrencyLimit::new(
+                            max,
+                            listener.queue_size.unwrap_or(args.queue_size),
+                            listener
+                                .queue_overflow
+                                .as_deref()
+                                .map(str::parse)
+                                .transpose()?
+                                .unwrap_or(args.queue_overflow),
+                        )
+                    })
+                    .transpose()
+                    .with_context(|| format!("Invalid concurrency for listener '{}'", name))?,
+            )
+            .with_compression(match &listener.compression {
+                Some(mode) => mode.parse()?,
+                None => args.compression,
+            })
+            .with_upstream(upstream)
+            .with_load_metric(load_metric)
+            .with_delay(delay)
+            .with_errors(errors)
+            .with_scenario(scenario)
+            .with_format(format);
+        listeners.push(Listener { name, addr, state });
+    }
+    Ok(listeners)
+}
+
+/// The settings of the run for the report: the command line, and what it configured
+fn report_settings(args: &Args) -> Vec<(String, String)> {
+    let command: Vec<String> = std::env::args().collect();
+    let mut settings = vec![
+        ("Command line".to_string(), command.join(" ")),
+        ("Delay".to_string(), format!("{} ms", args.delay)),
+        (
+            "Errors".to_string(),
+            format!("{}% ({})", args.error_rate, args.error_status),
+        ),
+        ("Format".to_string(), args.format.name().to_string()),
+    ];
+    let files = [
+        ("Config file", &args.config),
+        ("Scenario", &args.scenario),
+        ("OpenAPI spec", &args.openapi),
+        ("Replay", &args.replay),
+        ("Record", &args.record),
+    ];
+    for (name, path) in files {
+        if let Some(path) = path {
+            settings.push((name.to_string(), path.display().to_string()));
+        }
+    }
+    if let Some(upstream) = &args.upstream {
+        settings.push(("Upstream".to_string(), upstream.clone()));
+    }
+    if let Some(rate_limit) = &args.rate_limit {
+        settings.push(("Rate limit".to_string(), rate_limit.clone()));
+    }
+    if let Some(workers) = args.max_concurrency {
+        settings.push((
+            "Concurrency".to_string(),
+            format!("{} workers, queue of {}", workers, args.queue_size),
+        ));
+    }
+    settings
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
@@ -117,62 +748,230 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
-    let port = args.port;
+    let config = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let grpc = GrpcRegistry::from_config(&config.grpc)?;
+    let grpc_mode = !grpc.is_empty();
 
     // Create a channel for sending request events to the TUI
     let (tx, rx) = mpsc::channel::<AppEvent>(1024);
 
-    // Build our shared (atomic) state
-    let state = AppState::new(tx.clone(), &args.delay, args.format)?;
+    // Build our shared (atomic) state; listeners override its profile
+    // One clock for the handlers, the scenario and the TUI
+    let clock = clock::system();
+    let state = AppState::new(tx.clone(), &args.delay, args.format)?
+        .with_clock(clock.clone())
+        .with_delay(DelayConfig::parse(&args.delay)?.with_load_curve(args.load_latency.clone()))
+        .with_errors(
+            ErrorConfig::new(args.error_rate, args.error_status)?
+                .with_overload(args.overload_threshold),
+        )
+        .with_load_metric(args.load_metric)
+        .with_grpc(grpc)
+        .with_upstream(args.upstream.as_deref().map(Upstream::new).transpose()?)
+        .with_recorder(
+            args.record
+                .as_deref()
+                .map(Recorder::create)
+                .transpose()?
+                .map(Arc::new),
+        )
+        .with_replay(
+            args.replay
+                .as_deref()
+                .map(|path| Replay::load(path, args.replay_latency))
+                .transpose()?
+                .map(Arc::new),
+        )
+        .with_openapi(
+            args.openapi
+                .as_deref()
+                .map(OpenApi::load)
+                .transpose()?
+                .map(Arc::new),
+        )
+        .with_template(
+            config
+                .response
+                .as_ref()
+                .map(ResponseTemplate::new)
+                .transpose()?
+                .map(Arc::new),
+        )
+        .with_payload(
+            Payload::new(args.payload.as_ref(), args.body_size.as_ref(), args.format)?
+                .map(Arc::new),
+        );
+    let state = state.with_compression(args.compression).with_throttle(
+        ThrottleConfig::new(
+            args.bandwidth.as_deref(),
+            args.trickle.as_deref(),
+            args.upload_bandwidth.as_deref(),
+        )?
+        .with_routes(&config.routes)?,
+    );
+    let state = state.with_rate_limit(
+        args.rate_limit
+            .as_deref()
+            .map(|rate| {
+                RateLimiter::new(rate, args.rate_limit_algorithm, args.rate_limit_key.clone())
+            })
+            .transpose()?
+            .map(Arc::new),
+    );
+    let state = state.with_sequences(Sequences::new(&config.sequences)?);
+    let state = state.with_concurrency(
+        args.max_concurrency
+            .map(|max| ConcurrencyLimit::new(max, args.queue_size, args.queue_overflow))
+            .transpose()?,
+    );
+    if state.template.is_some() && state.payload.is_some() {
+        return Err(anyhow::anyhow!(
+            "A response template and a payload can't be combined"
+        ));
+    }
+
+    // Scenario phases fall back to the command-line delay and error settings, or to those of
+    // each listener in the config file
+    let scenario = args
+        .scenario
+        .as_deref()
+        .map(|path| {
+            Scenario::load(
+                path,
+                Conditions::base(
+                    &DelayConfig::parse(&args.delay)?,
+                    args.error_rate,
+                    args.error_status,
+                ),
+            )
+            .map(|scenario| scenario.with_clock(clock.clone()))
+        })
+        .transpose()?
+        .map(Arc::new);
+    let state = state
+        .with_scenario(scenario.clone())
+        .with_seed(args.seed)
+        .with_client_header(args.client_header.clone())
+        .with_retries(RetryTracker::new(
+            args.retry_key_header.clone(),
+            parse_duration(&args.retry_window).context("Invalid --retry-window")?,
+        ));
+    let seed = state.seeder.seed;
+
+    let listeners = build_listeners(&args, &config, &state)?;
+    let history = HistoryConfig::parse(&args.rps_bucket, &args.rps_window)?;
+    let custom_layout = config
+        .layout
+        .as_ref()
+        .map(PanelLayout::from_config)
+        .transpose()
+        .context("Invalid layout in the config file")?;
+    let layouts = Layouts::new(custom_layout, &args.layout)?;
+    let report = args
+        .report
+        .clone()
+        .map(|path| ReportConfig::new(path, report_settings(&args)))
+        .transpose()?;
+    let listener_infos = listeners.iter().map(Listener::info).collect();
+
+    // Apply commands from the TUI to the listeners they are meant for
+    let (control_tx, mut control_rx) = mpsc::channel::<ControlEvent>(16);
+    let listener_states: Vec<AppState> = listeners.iter().map(|l| l.state.clone()).collect();
+    tokio::spawn(async move {
+        while let Some(event) = control_rx.recv().await {
+            for state in &listener_states {
+                if event
+                    .listener
+                    .as_deref()
+                    .is_none_or(|name| *state.listener == *name)
+                {
+                    state.control(event.command);
+                }
+            }
+        }
+    });
 
     // Build our Axum router
     let app = axum::Router::new()
         // Catch all paths, any method
-        .fallback(request_handler)
-        .with_state(state.clone());
+        .fallback(request_handler);
 
-    // Prepare server
-    let addr: SocketAddr = ([0, 0, 0, 0], port).into();
-    let server = axum::Server::bind(&addr).serve(app.into_make_service());
-
-    println!(
-        "Server listening on http://{} (press 'q' in TUI or Ctrl+C to quit)",
-        addr
-    );
-
-    // Graceful shutdown signal
-    let shutdown_signal = async {
+    // Graceful shutdown signal, shared by all listeners
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let stop_tui = shutdown_tx.clone();
+    tokio::spawn(async move {
         // Wait for Ctrl+C
         signal::ctrl_c()
             .await
             .expect("failed to install Ctrl+C handler");
         println!("Received Ctrl+C, shutting down...");
-    };
-
-    // Run server with shutdown
-    let server_handle = tokio::spawn(async move {
-        if let Err(err) = server.with_graceful_shutdown(shutdown_signal).await {
-            eprintln!("Server error: {}", err);
-        }
+        let _ = shutdown_tx.send(true);
     });
 
+    // Run one server per listener
+    println!(
+        "Random seed: {} (rerun with --seed {} to reproduce)",
+        seed, seed
+    );
+    let mut servers = JoinSet::new();
+    for listener in listeners {
+        println!(
+            "Server '{}' listening on {} (press 'q' in TUI or Ctrl+C to quit)",
+            listener.name, listener.addr
+        );
+        let mut shutdown_rx = shutdown_rx.clone();
+        let shutdown = async move {
+            let _ = shutdown_rx.wait_for(|&stop| stop).await;
+        };
+        let app = app.clone();
+        servers.spawn(async move {
+            let name = listener.name.clone();
+            if let Err(err) = listener.serve(app, shutdown).await {
+                eprintln!("Server '{}' error: {:#}", name, err);
+            }
+        });
+    }
+
     // Spawn the TUI in a blocking thread via tokio
-    let tui_handle = tokio::spawn(async move {
+    let tui_shutdown = shutdown_rx.clone();
+    let mut tui_handle = tokio::spawn(async move {
         // We'll run the TUI in a blocking context
         // because crossterm + ratatui are synchronous
-        tokio::task::spawn_blocking(move || run_tui(rx, port))
-            .await
-            .expect("Failed to run TUI blocking task")?;
+        tokio::task::spawn_blocking(move || {
+            let options = TuiOptions {
+                grpc_mode,
+                scenario,
+                seed,
+                clock,
+                history,
+                layouts,
+                control_tx,
+                report,
+                shutdown: tui_shutdown,
+            };
+            run_tui(rx, listener_infos, options)
+        })
+        .await
+        .expect("Failed to run TUI blocking task")?;
         Ok::<(), anyhow::Error>(())
     });
 
-    // If either task finishes, we exit
-    tokio::select! {
-        _ = server_handle => { /* server finished or crashed */ }
-        _ = tui_handle => { /* TUI finished */ }
-    }
-
-    Ok(())
+    // If any server or the TUI finishes, we exit
+    let tui_result = tokio::select! {
+        _ = servers.join_next() => {
+            // Server finished or crashed: close the TUI so it restores the terminal and
+            // writes the report
+            let _ = stop_tui.send(true);
+            tui_handle.await
+        }
+        result = &mut tui_handle => result,
+    };
+    println!("Random seed: {}", seed);
+    // A TUI failure, such as a report that couldn't be written, fails the run
+    tui_result?
 }
 
 #[cfg(test)]
@@ -183,6 +982,13 @@ mod tests {
     fn test_output_format_from_str() {
         assert_eq!("json".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
         assert_eq!("text".parse::<OutputFormat>().unwrap(), OutputFormat::Text);
-        assert!("xml".parse::<OutputFormat>().is_err());
+        assert_eq!("xml".parse::<OutputFormat>().unwrap(), OutputFormat::Xml);
+        assert_eq!(
+            "MessagePack".parse::<OutputFormat>().unwrap(),
+            OutputFormat::MsgPack
+        );
+        assert_eq!("YAML".parse::<OutputFormat>().unwrap(), OutputFormat::Yaml);
+        assert_eq!("html".parse::<OutputFormat>().unwrap(), OutputFormat::Html);
+        assert!("csv".parse::<OutputFormat>().is_err());
     }
 }
@@ -0,0 +1,850 @@
+use crate::state::{AppState, Outcome, RequestLog};
+use crate::util::{parse_query, percent_decode};
+use anyhow::{Context, anyhow};
+use axum::{
+    body::{Bytes, boxed},
+    http::{Method, StatusCode, Uri},
+    response::Response,
+};
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// Maximum depth when following `$ref`s or generating nested examples
+const MAX_DEPTH: usize = 16;
+
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Location {
+    Path,
+    Query,
+}
+
+#[derive(Debug, Clone)]
+struct Parameter {
+    name: String,
+    location: Location,
+    required: bool,
+    schema: Value,
+}
+
+/// The example response served for an operation
+#[derive(Debug, Clone)]
+struct MockResponse {
+    status: StatusCode,
+    content_type: Option<String>,
+    body: Option<String>,
+}
+
+/// One operation (method + path template) of the spec
+#[derive(Debug, Clone)]
+struct Operation {
+    method: Method,
+    template: String,
+    segments: Vec<Segment>,
+    parameters: Vec<Parameter>,
+    body_required: bool,
+    /// JSON schema of the request body, if it accepts JSON
+    body_schema: Option<Value>,
+    response: MockResponse,
+}
+
+enum RouteMatch<'a> {
+    NotFound,
+    MethodNotAllowed,
+    Found(&'a Operation, HashMap<String, String>),
+}
+
+/// A mock server generated from an OpenAPI 3 spec
+#[derive(Debug)]
+pub struct OpenApi {
+    root: Value,
+    operations: Vec<Operation>,
+}
+
+impl OpenApi {
+    /// Load a spec from a YAML or JSON file
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read OpenAPI spec {}", path.display()))?;
+        Self::parse(&contents)
+            .with_context(|| format!("Failed to load OpenAPI spec {}", path.display()))
+    }
+
+    /// Parse a spec from a YAML or JSON string
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        let root: Value = serde_yaml::from_str(contents)?;
+        if !root
+            .get("openapi")
+            .and_then(Value::as_str)
+            .is_some_and(|v| v.starts_with('3'))
+        {
+            return Err(anyhow!("Only OpenAPI 3.x specs are supported"));
+        }
+        let paths = root
+            .get("paths")
+            .and_then(Value::as_object)
+            .ok_or_else(|| anyhow!("Spec has no 'paths'"))?;
+
+        let mut operations = Vec::new();
+        for (template, item) in paths {
+            let item = resolve(&root, item)?;
+            let shared = item.get("parameters").cloned().unwrap_or(json!([]));
+            for method in METHODS {
+                let Some(operation) = item.get(method) else {
+                    continue;
+                };
+                let operation = Self::build_operation(&root, template, method, &shared, operation)
+                    .with_context(|| format!("Invalid operation {} {}", method, template))?;
+                operations.push(operation);
+            }
+        }
+        if operations.is_empty() {
+            return Err(anyhow!("Spec declares no operations"));
+        }
+        Ok(Self { root, operations })
+    }
+
+    fn build_operation(
+        root: &Value,
+        template: &str,
+        method: &str,
+        shared: &Value,
+        operation: &Value,
+    ) -> anyhow::Result<Operation> {
+        let segments = template
+            .trim_matches('/')
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(
+                |s| match s.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                    Some(name) => Segment::Param(name.to_string()),
+                    None => Segment::Literal(s.to_string()),
+                },
+            )
+            .collect();
+
+        // Operation parameters override path-level ones with the same name and location
+        let mut parameters: Vec<Parameter> = Vec::new();
+        let declared = shared.as_array().into_iter().flatten().chain(
+            operation
+                .get("parameters")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten(),
+        );
+        for parameter in declared {
+            let parameter = resolve(root, parameter)?;
+            let location = match parameter.get("in").and_then(Value::as_str) {
+                Some("path") => Location::Path,
+                Some("query") => Location::Query,
+                _ => continue,
+            };
+            let name = parameter
+                .get("name")
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow!("Parameter without a name"))?
+                .to_string();
+            let required = location == Location::Path
+                || parameter.get("required").and_then(Value::as_bool) == Some(true);
+            let schema = parameter.get("schema").cloned().unwrap_or(json!({}));
+            parameters.retain(|p| !(p.name == name && p.location == location));
+            parameters.push(Parameter {
+                name,
+                location,
+                required,
+                schema,
+            });
+        }
+
+        let (body_required, body_schema) = match operation.get("requestBody") {
+            Some(body) => {
+                let body = resolve(root, body)?;
+                let schema = body
+                    .get("content")
+                    .and_then(Value::as_object)
+                    .and_then(|content| {
+                        content
+                            .iter()
+                            .find(|(content_type, _)| content_type.contains("json"))
+                    })
+                    .map(|(_, media)| media.get("schema").cloned().unwrap_or(json!({})));
+                (
+                    body.get("required").and_then(Value::as_bool) == Some(true),
+                    schema,
+                )
+            }
+            None => (false, None),
+        };
+
+        Ok(Operation {
+            method: method.to_uppercase().parse()?,
+            template: template.to_string(),
+            segments,
+            parameters,
+            body_required,
+            body_schema,
+            response: Self::build_response(root, operation)?,
+        })
+    }
+
+    /// Pick the lowest 2xx response (or `default`) and its example body
+    fn build_response(root: &Value, operation: &Value) -> anyhow::Result<MockResponse> {
+        let responses = operation.get("responses").and_then(Value::as_object);
+        let chosen = responses.and_then(|responses| {
+            let mut success: Vec<_> = responses
+                .iter()
+                .filter_map(|(code, r)| Some((code.parse::<u16>().ok()?, r)))
+                .filter(|(code, _)| (200..300).contains(code))
+                .collect();
+            success.sort_by_key(|(code, _)| *code);
+            success
+                .into_iter()
+                .next()
+                .or_else(|| responses.get("default").map(|r| (200, r)))
+        });
+        let Some((code, response)) = chosen else {
+            return Ok(MockResponse {
+                status: StatusCode::OK,
+                content_type: None,
+                body: None,
+            });
+        };
+        let response = resolve(root, response)?;
+        let status = StatusCode::from_u16(code)?;
+
+        let content = response.get("content").and_then(Value::as_object);
+        let media = content.and_then(|content| {
+            content
+                .iter()
+                .find(|(content_type, _)| content_type.contains("json"))
+                .or_else(|| content.iter().next())
+        });
+        let Some((content_type, media)) = media else {
+            return Ok(MockResponse {
+                status,
+                content_type: None,
+                body: None,
+            });
+        };
+
+        let example = media
+            .get("example")
+            .cloned()
+            .or_else(|| {
+                let examples = media.get("examples")?.as_object()?;
+                let example = resolve(root, examples.values().next()?).ok()?;
+                example.get("value").cloned()
+            })
+            .or_else(|| {
+                media
+                    .get("schema")
+                    .map(|schema| generate_example(root, schema, 0))
+            })
+            .unwrap_or(Value::Null);
+        let body = match (&example, content_type.contains("json")) {
+            (Value::String(text), false) => text.clone(),
+            _ => example.to_string(),
+        };
+        Ok(MockResponse {
+            status,
+            content_type: Some(content_type.clone()),
+            body: Some(body),
+        })
+    }
+
+    /// Find the operation for a request, preferring templates with more literal segments
+    fn find(&self, method: &Method, path: &str) -> RouteMatch<'_> {
+        let segments: Vec<&str> = path
+            .trim_matches('/')
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect();
+        let mut best: Option<(usize, &Operation, HashMap<String, String>)> = None;
+        let mut path_matched = false;
+        for operation in &self.operations {
+            if operation.segments.len() != segments.len() {
+                continue;
+            }
+            let mut params = HashMap::new();
+            let mut literals = 0;
+            let matched = operation
+                .segments
+                .iter()
+                .zip(&segments)
+                .all(|(expected, actual)| match expected {
+                    Segment::Literal(literal) => {
+                        literals += 1;
+                        literal == actual
+                    }
+                    Segment::Param(name) => {
+                        params.insert(name.clone(), percent_decode(actual));
+                        true
+                    }
+                });
+            if !matched {
+                continue;
+            }
+            path_matched = true;
+            if operation.method == *method && best.as_ref().is_none_or(|(n, _, _)| literals > *n) {
+                best = Some((literals, operation, params));
+            }
+        }
+        match best {
+            Some((_, operation, params)) => RouteMatch::Found(operation, params),
+            None if path_matched => RouteMatch::MethodNotAllowed,
+            None => RouteMatch::NotFound,
+        }
+    }
+
+    /// Validate a request against its operation, returning the violations
+    fn validate(
+        &self,
+        operation: &Operation,
+        params: &HashMap<String, String>,
+        query: Option<&str>,
+        body: &[u8],
+    ) -> Vec<String> {
+        let mut violations = Vec::new();
+        let query = parse_query(query.unwrap_or(""));
+
+        for parameter in &operation.parameters {
+            let value = match parameter.location {
+                Location::Path => params.get(&parameter.name),
+                Location::Query => query.get(&parameter.name),
+            };
+            let kind = match parameter.location {
+                Location::Path => "path",
+                Location::Query => "query",
+            };
+            match value {
+                None if parameter.required => violations.push(format!(
+                    "missing required {} parameter '{}'",
+                    kind, parameter.name
+                )),
+                None => {}
+                Some(raw) => {
+                    let value = coerce(&self.root, &parameter.schema, raw);
+                    validate_schema(
+                        &self.root,
+                        &parameter.schema,
+                        &value,
+                        &format!("{} parameter '{}'", kind, parameter.name),
+                        &mut violations,
+                        0,
+                    );
+                }
+            }
+        }
+
+        if body.is_empty() {
+            if operation.body_required {
+                violations.push("missing required request body".to_string());
+            }
+        } else if let Some(schema) = &operation.body_schema {
+            match serde_json::from_slice::<Value>(body) {
+                Ok(value) => {
+                    validate_schema(&self.root, schema, &value, "body", &mut violations, 0)
+                }
+                Err(err) => violations.push(format!("body is not valid JSON: {}", err)),
+            }
+        }
+        violations
+    }
+}
+
+/// Follow a local `$ref` (`#/components/...`)
+fn resolve<'a>(root: &'a Value, mut value: &'a Value) -> anyhow::Result<&'a Value> {
+    for _ in 0..MAX_DEPTH {
+        let Some(reference) = value.get("$ref").and_then(Value::as_str) else {
+            return Ok(value);
+        };
+        let pointer = reference
+            .strip_prefix('#')
+            .ok_or_else(|| anyhow!("Only local $refs are supported: {}", reference))?;
+        value = root
+            .pointer(pointer)
+            .ok_or_else(|| anyhow!("Unresolved $ref {}", reference))?;
+    }
+    Err(anyhow!("$ref chain too deep"))
+}
+
+/// Convert a path or query string to the JSON type its schema expects
+fn coerce(root: &Value, schema: &Value, raw: &str) -> Value {
+    let schema = resolve(root, schema).unwrap_or(schema);
+    match schema.get("type").and_then(Value::as_str) {
+        Some("integer") => raw
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::String(raw.to_string())),
+        Some("number") => raw
+            .parse::<f64>()
+            .ok()
+            .and_then(|n| serde_json::Number::from_f64(n).map(Value::Number))
+            .unwrap_or_else(|| Value::String(raw.to_string())),
+        Some("boolean") => match raw {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => Value::String(raw.to_string()),
+        },
+        _ => Value::String(raw.to_string()),
+    }
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    }
+}
+
+/// Validate a value against the subset of JSON schema used by OpenAPI specs
+fn validate_schema(
+    root: &Value,
+    schema: &Value,
+    value: &Value,
+    at: &str,
+    violations: &mut Vec<String>,
+    depth: usize,
+) {
+    let Ok(schema) = resolve(root, schema) else {
+        return;
+    };
+    if depth > MAX_DEPTH {
+        return;
+    }
+    if value.is_null() && schema.get("nullable").and_then(Value::as_bool) == Some(true) {
+        return;
+    }
+
+    if let Some(all_of) = schema.get("allOf").and_then(Value::as_array) {
+        for sub in all_of {
+            validate_schema(root, sub, value, at, violations, depth + 1);
+        }
+    }
+    for key in ["anyOf", "oneOf"] {
+        if let Some(options) = schema.get(key).and_then(Value::as_array) {
+            let any_valid = options.iter().any(|sub| {
+                let mut sub_violations = Vec::new();
+                validate_schema(root, sub, value, at, &mut sub_violations, depth + 1);
+                sub_violations.is_empty()
+            });
+            if !any_valid {
+                violations.push(format!("{} does not match any allowed schema", at));
+            }
+        }
+    }
+
+    if let Some(expected) = schema.get("type").and_then(Value::as_str)
+        && !type_matches(expected, value)
+    {
+        violations.push(format!("{} must be of type {}", at, expected));
+        return;
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        violations.push(format!(
+            "{} must be one of {}",
+            at,
+            Value::from(allowed.clone())
+        ));
+    }
+
+    match value {
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64)
+                && n < min
+            {
+                violations.push(format!("{} must be >= {}", at, min));
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64)
+                && n > max
+            {
+                violations.push(format!("{} must be <= {}", at, max));
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+                && len < min
+            {
+                violations.push(format!("{} must be at least {} characters", at, min));
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+                && len > max
+            {
+                violations.push(format!("{} must be at most {} characters", at, max));
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    let at = format!("{}[{}]", at, i);
+                    validate_schema(root, item_schema, item, &at, violations, depth + 1);
+                }
+            }
+        }
+        Value::Object(fields) => {
+            for required in schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                if !fields.contains_key(required) {
+                    violations.push(format!("{} is missing required field '{}'", at, required));
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, field) in fields {
+                let at = format!("{}.{}", at, name);
+                match properties.and_then(|p| p.get(name)) {
+                    Some(field_schema) => {
+                        validate_schema(root, field_schema, field, &at, violations, depth + 1)
+                    }
+                    None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                        violations.push(format!("{} is not an allowed field", at))
+                    }
+                    None => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Generate placeholder data matching a schema
+fn generate_example(root: &Value, schema: &Value, depth: usize) -> Value {
+    let Ok(schema) = resolve(root, schema) else {
+        return Value::Null;
+    };
+    if depth > MAX_DEPTH {
+        return Value::Null;
+    }
+    if let Some(example) = schema.get("example") {
+        return example.clone();
+    }
+    if let Some(first) = schema
+        .get("enum")
+        .and_then(Value::as_array)
+        .and_then(|e| e.first())
+    {
+        return first.clone();
+    }
+    if let Some(sub) = ["allOf", "anyOf", "oneOf"]
+        .iter()
+        .find_map(|key| schema.get(*key).and_then(Value::as_array))
+    {
+        // Merge object examples of allOf; use the first option otherwise
+        let mut merged = Map::new();
+        for example in sub.iter().map(|s| generate_example(root, s, depth + 1)) {
+            match example {
+                Value::Object(fields) => merged.extend(fields),
+                other if merged.is_empty() => return other,
+                _ => {}
+            }
+        }
+        return Value::Object(merged);
+    }
+    let kind = schema.get("type").and_then(Value::as_str).unwrap_or(
+        if schema.get("properties").is_some() {
+            "object"
+        } else {
+            "string"
+        },
+    );
+    match kind {
+        "object" => Value::Object(
+            schema
+                .get("properties")
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+                .map(|(name, field)| (name.clone(), generate_example(root, field, depth + 1)))
+                .collect(),
+        ),
+        "array" => Value::Array(
+            schema
+                .get("items")
+                .map(|items| vec![generate_example(root, items, depth + 1)])
+                .unwrap_or_default(),
+        ),
+        "integer" => json!(schema.get("minimum").and_then(Value::as_i64).unwrap_or(1)),
+        "number" => json!(schema.get("minimum").and_then(Value::as_f64).unwrap_or(1.5)),
+        "boolean" => json!(true),
+        _ => json!(match schema.get("format").and_then(Value::as_str) {
+            Some("date-time") => "2024-01-01T00:00:00Z",
+            Some("date") => "2024-01-01",
+            Some("uuid") => "00000000-0000-4000-8000-000000000000",
+            Some("email") => "user@example.com",
+            Some("uri") => "https://example.com",
+            _ => "string",
+        }),
+    }
+}
+
+/// Serve a request from the OpenAPI spec.
+///
+/// Requests that don't validate get a 400 listing the violations and are
+/// logged as client bugs, separately from injected errors.
+pub async fn openapi_handler(
+    state: &AppState,
+    api: &OpenApi,
+    method: &Method,
+    uri: &Uri,
+    body: &Bytes,
+) -> Response {
+    let start = state.clock.now();
+    let now = state.now_timestamp();
+    state.increment_requests();
+
+    let delay_ms = state.get_delay();
+    if delay_ms > 0 {
+        state.clock.sleep(Duration::from_millis(delay_ms)).await;
+    }
+
+    let mut log = RequestLog {
+        timestamp: now,
+        ..RequestLog::new(state, method.as_str(), uri.path(), 0)
+    };
+
+    let json_response = |status: StatusCode, body: Value| {
+        Response::builder()
+            .status(status)
+            .header("content-type", "application/json")
+            .body(boxed(body.to_string()))
+            .unwrap()
+    };
+
+    let response = match api.find(method, uri.path()) {
+        RouteMatch::NotFound => json_response(
+            StatusCode::NOT_FOUND,
+            json!({ "error": "no operation matches this path" }),
+        ),
+        RouteMatch::MethodNotAllowed => json_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            json!({ "error": "method not allowed for this path" }),
+        ),
+        RouteMatch::Found(operation, params) => {
+            let violations = api.validate(operation, &params, uri.query(), body);
+            if !violations.is_empty() {
+                log.outcome = Outcome::ValidationError(violations.join("; "));
+                json_response(
+                    StatusCode::BAD_REQUEST,
+                    json!({
+                        "error": "request does not match the OpenAPI spec",
+                        "operation": format!("{} {}", operation.method, operation.template),
+                        "violations": violations,
+                    }),
+                )
+            } else if let Some(status) = state.get_error() {
+                log.outcome = Outcome::InjectedError;
+                let status =
+                    StatusCode::from_u16(status).unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+                json_response(status, json!({ "error": "injected error" }))
+            } else {
+                let mock = &operation.response;
+                let mut response = Response::builder().status(mock.status);
+                if let Some(content_type) = &mock.content_type {
+                    response = response.header("content-type", content_type);
+                }
+                response
+                    .body(boxed(mock.body.clone().unwrap_or_default()))
+                    .unwrap()
+            }
+        }
+    };
+
+    log.status = response.status().as_u16();
+    log.duration_ms = state.elapsed_ms(start);
+    state.record(log).await;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = r##"
+openapi: 3.0.3
+info: { title: Pets, version: "1" }
+paths:
+  /pets:
+    get:
+      parameters:
+        - { name: limit, in: query, required: true, schema: { type: integer, maximum: 100 } }
+      responses:
+        "200":
+          description: ok
+          content:
+            application/json:
+              schema: { type: array, items: { $ref: "#/components/schemas/Pet" } }
+    post:
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema: { $ref: "#/components/schemas/Pet" }
+      responses:
+        "201":
+          description: created
+          content:
+            application/json:
+              example: { id: 7, name: rex }
+  /pets/{id}:
+    get:
+      parameters:
+        - { name: id, in: path, schema: { type: integer } }
+      responses:
+        "204": { description: empty }
+  /pets/mine:
+    get:
+      responses:
+        "200":
+          description: ok
+          content:
+            text/plain:
+              example: mine
+components:
+  schemas:
+    Pet:
+      type: object
+      required: [id, name]
+      properties:
+        id: { type: integer }
+        name: { type: string, minLength: 1 }
+        tag: { type: string, enum: [dog, cat] }
+"##;
+
+    fn operation<'a>(
+        api: &'a OpenApi,
+        method: &str,
+        path: &str,
+    ) -> (&'a Operation, HashMap<String, String>) {
+        match api.find(&method.parse().unwrap(), path) {
+            RouteMatch::Found(operation, params) => (operation, params),
+            _ => panic!("no operation for {} {}", method, path),
+        }
+    }
+
+    #[test]
+    fn test_route_matching() {
+        let api = OpenApi::parse(SPEC).unwrap();
+        let (op, params) = operation(&api, "GET", "/pets/42");
+        assert_eq!(op.template, "/pets/{id}");
+        assert_eq!(params["id"], "42");
+        // Literal segments win over parameters
+        assert_eq!(
+            operation(&api, "GET", "/pets/mine").0.template,
+            "/pets/mine"
+        );
+        assert!(matches!(
+            api.find(&Method::DELETE, "/pets"),
+            RouteMatch::MethodNotAllowed
+        ));
+        assert!(matches!(
+            api.find(&Method::GET, "/owners"),
+            RouteMatch::NotFound
+        ));
+    }
+
+    #[test]
+    fn test_validate_parameters() {
+        let api = OpenApi::parse(SPEC).unwrap();
+        let (op, params) = operation(&api, "GET", "/pets");
+        assert!(api.validate(op, &params, Some("limit=10"), b"").is_empty());
+        assert_eq!(
+            api.validate(op, &params, None, b""),
+            ["missing required query parameter 'limit'"]
+        );
+        assert_eq!(
+            api.validate(op, &params, Some("limit=500"), b""),
+            ["query parameter 'limit' must be <= 100"]
+        );
+        let (op, params) = operation(&api, "GET", "/pets/abc");
+        assert_eq!(
+            api.validate(op, &params, None, b""),
+            ["path parameter 'id' must be of type integer"]
+        );
+    }
+
+    #[test]
+    fn test_validate_body() {
+        let api = OpenApi::parse(SPEC).unwrap();
+        let (op, params) = operation(&api, "POST", "/pets");
+        assert!(
+            api.validate(op, &params, None, br#"{"id": 1, "name": "rex"}"#)
+                .is_empty()
+        );
+        assert_eq!(
+            api.validate(op, &params, None, b""),
+            ["missing required request body"]
+        );
+        let violations = api.validate(op, &params, None, br#"{"id": "x", "tag": "fish"}"#);
+        assert_eq!(
+            violations,
+            [
+                "body is missing required field 'name'",
+                "body.id must be of type integer",
+                "body.tag must be one of [\"dog\",\"cat\"]"
+            ]
+        );
+        assert!(api.validate(op, &params, None, b"{").len() == 1);
+    }
+
+    #[test]
+    fn test_example_responses() {
+        let api = OpenApi::parse(SPEC).unwrap();
+        let (op, _) = operation(&api, "POST", "/pets");
+        assert_eq!(op.response.status, StatusCode::CREATED);
+        assert_eq!(
+            op.response.body.as_deref(),
+            Some(r#"{"id":7,"name":"rex"}"#)
+        );
+
+        // Generated from the schema when there is no example
+        let (op, _) = operation(&api, "GET", "/pets");
+        assert_eq!(
+            op.response.body.as_deref(),
+            Some(r#"[{"id":1,"name":"string","tag":"dog"}]"#)
+        );
+
+        let (op, _) = operation(&api, "GET", "/pets/mine");
+        assert_eq!(op.response.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(op.response.body.as_deref(), Some("mine"));
+
+        let (op, _) = operation(&api, "GET", "/pets/1");
+        assert_eq!(op.response.status, StatusCode::NO_CONTENT);
+        assert!(op.response.body.is_none());
+    }
+
+    #[test]
+    fn test_rejects_invalid_specs() {
+        assert!(OpenApi::parse("swagger: '2.0'\npaths: {}").is_err());
+        assert!(OpenApi::parse("openapi: 3.0.0\npaths: {}").is_err());
+        assert!(
+            OpenApi::parse(
+                "openapi: 3.0.0\npaths:\n  /a:\n    get:\n      parameters: [{$ref: '#/nope'}]"
+            )
+            .is_err()
+        );
+    }
+}
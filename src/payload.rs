@@ -0,0 +1,590 @@
+use crate::OutputFormat;
+use crate::random::RequestRng;
+use anyhow::{Context, anyhow};
+use axum::{
+    body::{Bytes, HttpBody, boxed},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::Response,
+};
+use rand::Rng;
+use rand::rngs::StdRng;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context as TaskContext, Poll};
+
+/// Size of the chunks generated payloads are streamed in
+const CHUNK_SIZE: usize = 16 * 1024;
+
+const LOREM: &str = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod \
+tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud \
+exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat. ";
+
+/// Parse a byte count such as `512`, `64KB`, `256KiB` or `1.5MiB`
+pub fn parse_byte_size(s: &str) -> anyhow::Result<u64> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid size '{}'", s))?;
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "kib" => 1 << 10,
+        "mb" => 1_000_000,
+        "mib" => 1 << 20,
+        "gb" => 1_000_000_000,
+        "gib" => 1 << 30,
+        _ => {
+            return Err(anyhow!(
+                "Invalid size unit in '{}'. Valid units: B, KB, KiB, MB, MiB, GB, GiB",
+                s
+            ));
+        }
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// How large generated response bodies are
+#[derive(Debug, Clone, PartialEq)]
+pub enum BodySize {
+    Fixed(u64),
+    /// Uniformly distributed between the bounds (inclusive)
+    Range(u64, u64),
+    /// Sizes picked with the given relative weights
+    Weighted(Vec<(u64, u32)>),
+}
+
+impl std::str::FromStr for BodySize {
+    type Err = anyhow::Error;
+
+    /// Parse `10KiB`, `1KiB-1MiB` or `1KiB:90,1MiB:10`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(':') {
+            let sizes = s
+                .split(',')
+                .map(|part| {
+                    let (size, weight) = part
+                        .split_once(':')
+                        .ok_or_else(|| anyhow!("Expected 'size:weight', got '{}'", part))?;
+                    let weight: u32 = weight
+                        .trim()
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid weight '{}'", weight))?;
+                    Ok((parse_byte_size(size)?, weight))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            if sizes.iter().all(|(_, weight)| *weight == 0) {
+                return Err(anyhow!("At least one body size needs a positive weight"));
+            }
+            return Ok(BodySize::Weighted(sizes));
+        }
+        if let Some((min, max)) = s.split_once('-') {
+            let (min, max) = (parse_byte_size(min)?, parse_byte_size(max)?);
+            if min >= max {
+                return Err(anyhow!("Minimum body size must be less than maximum"));
+            }
+            return Ok(BodySize::Range(min, max));
+        }
+        Ok(BodySize::Fixed(parse_byte_size(s)?))
+    }
+}
+
+impl BodySize {
+    /// Pick the size of the next body
+    pub fn sample(&self, rng: &mut impl Rng) -> u64 {
+        match self {
+            BodySize::Fixed(size) => *size,
+            BodySize::Range(min, max) => rng.random_range(*min..=*max),
+            BodySize::Weighted(sizes) => {
+                let total: u64 = sizes.iter().map(|(_, weight)| u64::from(*weight)).sum();
+                let mut pick = rng.random_range(0..total);
+                for (size, weight) in sizes {
+                    if pick < u64::from(*weight) {
+                        return *size;
+                    }
+                    pick -= u64::from(*weight);
+                }
+                sizes.last().map_or(0, |(size, _)| *size)
+            }
+        }
+    }
+
+    /// The smallest size `sample` can return
+    fn smallest(&self) -> u64 {
+        match self {
+            BodySize::Fixed(size) | BodySize::Range(size, _) => *size,
+            BodySize::Weighted(sizes) => sizes
+                .iter()
+                .filter(|(_, weight)| *weight > 0)
+                .map(|(size, _)| *size)
+                .min()
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// What generated response bodies contain, as given on the command line
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayloadKind {
+    Random,
+    Lorem,
+    /// A JSON array, of the given number of items or filling the body size
+    Json(Option<u64>),
+    File(PathBuf),
+}
+
+impl std::str::FromStr for PayloadKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("file:") {
+            return Ok(PayloadKind::File(PathBuf::from(path)));
+        }
+        if let Some(items) = s.strip_prefix("json:") {
+            let items = items
+                .parse()
+                .map_err(|_| anyhow!("Invalid JSON item count '{}'", items))?;
+            return Ok(PayloadKind::Json(Some(items)));
+        }
+        match s.to_lowercase().as_str() {
+            "random" => Ok(PayloadKind::Random),
+            "lorem" => Ok(PayloadKind::Lorem),
+            "json" => Ok(PayloadKind::Json(None)),
+            _ => Err(anyhow!(
+                "Invalid payload. Valid payloads: random, lorem, json, json:<items>, file:<path>"
+            )),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Source {
+    Random,
+    Lorem,
+    Json(Option<u64>),
+    /// File contents, read once at startup
+    File(Bytes),
+}
+
+/// Generated bodies for successful responses
+#[derive(Debug)]
+pub struct Payload {
+    source: Source,
+    size: Option<BodySize>,
+    content_type: &'static str,
+}
+
+impl Payload {
+    /// The payload for the given options, or `None` if neither is set.
+    /// Without a kind, the body is lorem text for the text format and a JSON array otherwise.
+    pub fn new(
+        kind: Option<&PayloadKind>,
+        size: Option<&BodySize>,
+        format: OutputFormat,
+    ) -> anyhow::Result<Option<Self>> {
+        let kind = match (kind, size) {
+            (None, None) => return Ok(None),
+            (Some(kind), _) => kind.clone(),
+            (None, Some(_)) => match format {
+                OutputFormat::Text => PayloadKind::Lorem,
+                _ => PayloadKind::Json(None),
+            },
+        };
+        let (source, content_type) = match kind {
+            PayloadKind::Random => (Source::Random, "application/octet-stream"),
+            PayloadKind::Lorem => (Source::Lorem, "text/plain"),
+            PayloadKind::Json(items) => (Source::Json(items), "application/json"),
+            PayloadKind::File(path) => {
+                let data = std::fs::read(&path)
+                    .with_context(|| format!("Failed to read payload file {}", path.display()))?;
+                let content_type = match path.extension().and_then(|ext| ext.to_str()) {
+                    Some("json") => "application/json",
+                    Some("txt") => "text/plain",
+                    Some("html") => "text/html",
+                    Some("xml") => "application/xml",
+                    _ => "application/octet-stream",
+                };
+                (Source::File(Bytes::from(data)), content_type)
+            }
+        };
+        let needs_size = matches!(source, Source::Random | Source::Lorem | Source::Json(None));
+        if needs_size && size.is_none() {
+            return Err(anyhow!("This payload needs a body size (--body-size)"));
+        }
+        if let (Source::File(data), Some(_)) = (&source, size)
+            && data.is_empty()
+        {
+            return Err(anyhow!("An empty payload file can't fill a body size"));
+        }
+        // The brackets of a JSON array alone take two bytes
+        if let (Source::Json(_), Some(size)) = (&source, size)
+            && size.smallest() < 2
+        {
+            return Err(anyhow!("JSON bodies need a body size of at least 2 bytes"));
+        }
+        Ok(Some(Self {
+            source,
+            size: size.cloned(),
+            content_type,
+        }))
+    }
+
+    /// A successful response with a freshly generated body
+    pub fn response(&self, rng: &RequestRng) -> Response {
+        let mut response = Response::new(boxed(self.body(rng)));
+        *response.status_mut() = StatusCode::OK;
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(self.content_type),
+        );
+        response
+    }
+
+    fn body(&self, rng: &RequestRng) -> PayloadBody {
+        let size = self
+            .size
+            .as_ref()
+            .map(|size| rng.with(|rng| size.sample(rng)));
+        let generator = match &self.source {
+            Source::Random => Generator::Random(Box::new(rng.fork())),
+            Source::Lorem => Generator::Repeat(Bytes::from_static(LOREM.as_bytes()), 0),
+            Source::File(data) => Generator::Repeat(data.clone(), 0),
+            Source::Json(items) => Generator::Json {
+                next: 0,
+                items: *items,
+                started: false,
+            },
+        };
+        let remaining = match (&self.source, size) {
+            (_, Some(size)) => Some(size),
+            (Source::File(data), None) => Some(data.len() as u64),
+            _ => None,
+        };
+        PayloadBody {
+            generator,
+            remaining,
+            done: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Generator {
+    Random(Box<StdRng>),
+    /// Cycle through the data, starting at the offset
+    Repeat(Bytes, usize),
+    Json {
+        next: u64,
+        items: Option<u64>,
+        started: bool,
+    },
+}
+
+fn json_item(id: u64) -> String {
+    format!(
+        r#"{{"id":{},"name":"item-{}","active":{},"description":"{}"}}"#,
+        id,
+        id,
+        id.is_multiple_of(2),
+        &LOREM[..56]
+    )
+}
+
+/// A response body generated chunk by chunk, so large bodies are never held in memory
+#[derive(Debug)]
+pub struct PayloadBody {
+    generator: Generator,
+    /// Bytes left to send, if the size is fixed
+    remaining: Option<u64>,
+    done: bool,
+}
+
+impl PayloadBody {
+    fn next_chunk(&mut self) -> Option<Bytes> {
+        if self.done || self.remaining == Some(0) {
+            return None;
+        }
+        let limit = self
+            .remaining
+            .map_or(CHUNK_SIZE, |left| left.min(CHUNK_SIZE as u64) as usize);
+        let chunk = match &mut self.generator {
+            Generator::Random(rng) => {
+                let mut chunk = vec![0u8; limit];
+                rng.fill(&mut chunk[..]);
+                Bytes::from(chunk)
+            }
+            Generator::Repeat(data, offset) => {
+                let mut chunk = Vec::with_capacity(limit);
+                while chunk.len() < limit {
+                    let take = (data.len() - *offset).min(limit - chunk.len());
+                    chunk.extend_from_slice(&data[*offset..*offset + take]);
+                    *offset = (*offset + take) % data.len();
+                }
+                Bytes::from(chunk)
+            }
+            Generator::Json {
+                next,
+                items,
+                started,
+            } => {
+                let mut chunk = String::new();
+                if !*started {
+                    chunk.push('[');
+                    *started = true;
+                }
+                loop {
+                    if items.is_some_and(|items| *next >= items) {
+                        break;
+                    }
+                    let separator = if *next > 0 { "," } else { "" };
+                    let item = json_item(*next);
+                    // Leave room for the closing bracket when filling a size
+                    let needed = chunk.len() + separator.len() + item.len() + 1;
+                    if self.remaining.is_some_and(|left| needed as u64 > left) {
+                        break;
+                    }
+                    if chunk.len() + separator.len() + item.len() + 1 > CHUNK_SIZE {
+                        return Some(self.take(Bytes::from(chunk)));
+                    }
+                    chunk.push_str(separator);
+                    chunk.push_str(&item);
+                    *next += 1;
+                }
+                // Pad with whitespace to hit the exact size, then close the array
+                if let Some(left) = self.remaining {
+                    let padding = left.saturating_sub(chunk.len() as u64 + 1);
+                    let room = (CHUNK_SIZE - 1).saturating_sub(chunk.len()) as u64;
+                    chunk.push_str(&" ".repeat(padding.min(room) as usize));
+                    if padding > room {
+                        return Some(self.take(Bytes::from(chunk)));
+                    }
+                }
+                chunk.push(']');
+                self.done = true;
+                Bytes::from(chunk)
+            }
+        };
+        Some(self.take(chunk))
+    }
+
+    fn take(&mut self, chunk: Bytes) -> Bytes {
+        if let Some(left) = &mut self.remaining {
+            *left = left.saturating_sub(chunk.len() as u64);
+        }
+        chunk
+    }
+}
+
+impl HttpBody for PayloadBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        _cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Poll::Ready(self.get_mut().next_chunk().map(Ok))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _cx: &mut TaskContext<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done || self.remaining == Some(0)
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        match self.remaining {
+            Some(left) => hyper::body::SizeHint::with_exact(left),
+            None => hyper::body::SizeHint::default(),
+        }
+    }
+}
+
+/// Wraps a response body and adds the bytes sent to a counter
+pub struct CountingBody<B> {
+    inner: B,
+    counter: Arc<AtomicU64>,
+}
+
+impl<B> CountingBody<B> {
+    pub fn new(inner: B, counter: Arc<AtomicU64>) -> Self {
+        Self { inner, counter }
+    }
+}
+
+impl<B> HttpBody for CountingBody<B>
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_data(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &poll {
+            this.counter
+                .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(mut body: PayloadBody) -> Vec<u8> {
+        let mut out = Vec::new();
+        while let Some(chunk) = body.next_chunk() {
+            assert!(chunk.len() <= CHUNK_SIZE);
+            out.extend_from_slice(&chunk);
+        }
+        out
+    }
+
+    fn payload(kind: &str, size: Option<&str>) -> Payload {
+        Payload::new(
+            Some(&kind.parse().unwrap()),
+            size.map(|size| size.parse().unwrap()).as_ref(),
+            OutputFormat::Json,
+        )
+        .unwrap()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("512").unwrap(), 512);
+        assert_eq!(parse_byte_size("64KB").unwrap(), 64_000);
+        assert_eq!(parse_byte_size("256KiB").unwrap(), 256 * 1024);
+        assert_eq!(parse_byte_size("1.5mib").unwrap(), 1024 * 1024 * 3 / 2);
+        assert!(parse_byte_size("10 parsecs").is_err());
+        assert!(parse_byte_size("KiB").is_err());
+    }
+
+    #[test]
+    fn test_parse_body_size() {
+        assert_eq!("1KiB".parse::<BodySize>().unwrap(), BodySize::Fixed(1024));
+        assert_eq!(
+            "1KB-2KB".parse::<BodySize>().unwrap(),
+            BodySize::Range(1000, 2000)
+        );
+        assert_eq!(
+            "1KB:90,1MB:10".parse::<BodySize>().unwrap(),
+            BodySize::Weighted(vec![(1000, 90), (1_000_000, 10)])
+        );
+        assert!("2KB-1KB".parse::<BodySize>().is_err());
+        assert!("1KB:0".parse::<BodySize>().is_err());
+        for _ in 0..100 {
+            let size = BodySize::Range(10, 20).sample(&mut rand::rng());
+            assert!((10..=20).contains(&size));
+            assert_eq!(
+                BodySize::Weighted(vec![(1, 0), (2, 5)]).sample(&mut rand::rng()),
+                2
+            );
+        }
+    }
+
+    #[test]
+    fn test_generated_bodies_have_exact_size() {
+        for kind in ["random", "lorem", "json"] {
+            let body = collect(payload(kind, Some("100KiB")).body(&RequestRng::default()));
+            assert_eq!(body.len(), 100 * 1024, "{}", kind);
+        }
+    }
+
+    #[test]
+    fn test_seeded_bodies_repeat() {
+        let payload = payload("random", Some("1KB-64KB"));
+        let body = |seed| collect(payload.body(&RequestRng::seeded(seed)));
+        assert_eq!(body(9), body(9));
+        assert_ne!(body(9), body(10));
+    }
+
+    #[test]
+    fn test_json_payload_is_valid() {
+        let body = collect(payload("json", Some("40000")).body(&RequestRng::default()));
+        let items: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        assert!(!items.is_empty());
+        assert_eq!(items[1]["id"], 1);
+
+        let body = collect(payload("json:3000", None).body(&RequestRng::default()));
+        let items: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(items.len(), 3000);
+
+        // A fixed item count is padded out to the body size
+        let body = collect(payload("json:2", Some("50KB")).body(&RequestRng::default()));
+        assert_eq!(body.len(), 50_000);
+        let items: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(items.len(), 2);
+    }
+
+    #[test]
+    fn test_payload_needs_a_size() {
+        assert!(
+            Payload::new(None, None, OutputFormat::Json)
+                .unwrap()
+                .is_none()
+        );
+        assert!(Payload::new(Some(&PayloadKind::Random), None, OutputFormat::Json).is_err());
+        let size = BodySize::Fixed(10);
+        let payload = Payload::new(None, Some(&size), OutputFormat::Text)
+            .unwrap()
+            .unwrap();
+        assert_eq!(payload.content_type, "text/plain");
+    }
+
+    #[test]
+    fn test_json_payload_needs_room_for_brackets() {
+        for size in ["0", "1", "1-10", "1:1,1KB:10"] {
+            let size: BodySize = size.parse().unwrap();
+            assert!(Payload::new(None, Some(&size), OutputFormat::Json).is_err());
+            assert!(
+                Payload::new(
+                    Some(&PayloadKind::Json(Some(3))),
+                    Some(&size),
+                    OutputFormat::Json
+                )
+                .is_err()
+            );
+        }
+        // Sizes that are never picked don't count
+        let size: BodySize = "1:0,2:1".parse().unwrap();
+        assert!(Payload::new(None, Some(&size), OutputFormat::Json).is_ok());
+        let body = collect(payload("json", Some("2")).body(&RequestRng::default()));
+        assert_eq!(body, b"[]");
+        // Weights that add up past u32::MAX still sample
+        let size = BodySize::Weighted(vec![(1, u32::MAX), (2, u32::MAX)]);
+        assert!([1, 2].contains(&size.sample(&mut rand::rng())));
+    }
+}
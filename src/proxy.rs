@@ -0,0 +1,258 @@
+use crate::replay::{Recording, body_to_string};
+use crate::routes::build_response;
+use crate::state::{AppState, Outcome, RequestLog};
+use anyhow::anyhow;
+use axum::{
+    body::{BoxBody, Full, boxed},
+    http::{HeaderMap, HeaderValue, Method, Request, StatusCode, Uri, header},
+    response::Response,
+};
+use hyper::client::HttpConnector;
+use std::time::Duration;
+
+/// Headers that apply to a single connection and must not be forwarded
+const HOP_BY_HOP_HEADERS: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// A real upstream service that requests are forwarded to
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    client: hyper::Client<HttpConnector, BoxBody>,
+    base: Uri,
+}
+
+impl Upstream {
+    /// Parse an upstream URL such as `http://localhost:8080` or `http://localhost:8080/api`
+    pub fn new(url: &str) -> anyhow::Result<Self> {
+        let base: Uri = url
+            .parse()
+            .map_err(|_| anyhow!("Invalid upstream URL '{}'", url))?;
+        if base.scheme_str() != Some("http") || base.authority().is_none() {
+            return Err(anyhow!(
+                "Upstream URL must look like 'http://host:port', got '{}'",
+                url
+            ));
+        }
+        Ok(Self {
+            client: hyper::Client::builder().build_http(),
+            base,
+        })
+    }
+
+    /// The upstream URI for a request URI, keeping the base path as a prefix
+    fn target_uri(&self, uri: &Uri) -> Uri {
+        let prefix = self.base.path().trim_end_matches('/');
+        let path_and_query = uri.path_and_query().map_or("/", |pq| pq.as_str());
+        Uri::builder()
+            .scheme("http")
+            .authority(self.base.authority().unwrap().clone())
+            .path_and_query(format!("{}{}", prefix, path_and_query))
+            .build()
+            .unwrap()
+    }
+}
+
+impl std::fmt::Display for Upstream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.base)
+    }
+}
+
+/// Remove the hop-by-hop headers, including any the `Connection` header names
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    let named: Vec<String> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+    for name in named.iter().map(String::as_str).chain(HOP_BY_HOP_HEADERS) {
+        headers.remove(name);
+    }
+}
+
+/// A plain-text error response from the proxy itself
+fn proxy_error(status: StatusCode, message: String) -> Response {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"))
+        .body(boxed(message))
+        .unwrap()
+}
+
+/// Forward a request to the upstream.
+///
+/// The delay and error models are applied before forwarding; injected errors
+/// never reach the upstream. Request and response bodies are streamed through
+/// without buffering, except in record mode where both are buffered so the
+/// exchange can be saved. The logged duration covers the time until the
+/// upstream response headers arrive.
+pub async fn proxy_handler(
+    state: &AppState,
+    upstream: &Upstream,
+    method: Method,
+    uri: &Uri,
+    mut headers: HeaderMap,
+    body: BoxBody,
+) -> Response {
+    let start = state.clock.now();
+    let now = state.now_timestamp();
+    state.increment_requests();
+
+    let delay_ms = state.get_delay();
+    if delay_ms > 0 {
+        state.clock.sleep(Duration::from_millis(delay_ms)).await;
+    }
+
+    let mut log = RequestLog {
+        timestamp: now,
+        ..RequestLog::new(state, method.as_str(), uri.path(), 0)
+    };
+
+    if let Some(status) = state.get_error() {
+        let status = StatusCode::from_u16(status).unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+        let elapsed_ms = state.elapsed_ms(start);
+        log.status = status.as_u16();
+        log.outcome = Outcome::InjectedError;
+        log.duration_ms = elapsed_ms;
+        state.record(log).await;
+        return build_response(
+            state,
+            status,
+            uri.path(),
+            method.as_str(),
+            now,
+            elapsed_ms,
+            delay_ms,
+        );
+    }
+
+    // In record mode, buffer the request body so it can be saved
+    let (body, request_body) = match &state.recorder {
+        Some(_) => match hyper::body::to_bytes(body).await {
+            Ok(bytes) => (boxed(Full::from(bytes.clone())), Some(bytes)),
+            Err(err) => {
+                let response = proxy_error(
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to read request body: {}", err),
+                );
+                log.status = response.status().as_u16();
+                log.duration_ms = state.elapsed_ms(start);
+                state.record(log).await;
+                return response;
+            }
+        },
+        None => (body, None),
+    };
+
+    strip_hop_by_hop(&mut headers);
+    headers.remove(header::HOST);
+    let mut request = Request::new(body);
+    *request.method_mut() = method.clone();
+    *request.uri_mut() = upstream.target_uri(uri);
+    *request.headers_mut() = headers;
+
+    let upstream_start = state.clock.now();
+    let response = match upstream.client.request(request).await {
+        Ok(response) => {
+            let (mut parts, body) = response.into_parts();
+            strip_hop_by_hop(&mut parts.headers);
+            match (&state.recorder, request_body) {
+                // A response that fails midway isn't recorded
+                (Some(recorder), Some(request_body)) => match hyper::body::to_bytes(body).await {
+                    Err(err) => Err(format!("Upstream response failed: {}", err)),
+                    Ok(bytes) => {
+                        let mut recording = Recording {
+                            method: method.to_string(),
+                            path: uri.path().to_string(),
+                            query: uri.query().map(String::from),
+                            request_body: body_to_string(&request_body),
+                            status: parts.status.as_u16(),
+                            headers: parts
+                                .headers
+                                .iter()
+                                .filter_map(|(name, value)| {
+                                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                                })
+                                .collect(),
+                            body: String::new(),
+                            body_hex: None,
+                            latency_ms: state.elapsed_ms(upstream_start),
+                        };
+                        recording.set_body(&bytes);
+                        recorder.record(&recording).await;
+                        Ok(Response::from_parts(parts, boxed(Full::from(bytes))))
+                    }
+                },
+                _ => Ok(Response::from_parts(parts, boxed(body))),
+            }
+        }
+        Err(err) => Err(format!("Upstream request failed: {}", err)),
+    };
+    let response = response.unwrap_or_else(|message| {
+        log.outcome = Outcome::UpstreamError;
+        proxy_error(StatusCode::BAD_GATEWAY, message)
+    });
+
+    log.status = response.status().as_u16();
+    log.duration_ms = state.elapsed_ms(start);
+    state.record(log).await;
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upstream_rejects_invalid_urls() {
+        assert!(Upstream::new("http://localhost:8080").is_ok());
+        assert!(Upstream::new("https://localhost:8080").is_err());
+        assert!(Upstream::new("localhost:8080").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_target_uri_keeps_base_path_and_query() {
+        let upstream = Upstream::new("http://localhost:8080/api/").unwrap();
+        let uri: Uri = "/users?id=1".parse().unwrap();
+        assert_eq!(
+            upstream.target_uri(&uri).to_string(),
+            "http://localhost:8080/api/users?id=1"
+        );
+
+        let upstream = Upstream::new("http://localhost:8080").unwrap();
+        assert_eq!(
+            upstream.target_uri(&uri).to_string(),
+            "http://localhost:8080/users?id=1"
+        );
+    }
+
+    #[test]
+    fn test_strip_hop_by_hop() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "connection",
+            HeaderValue::from_static("keep-alive, X-Trace"),
+        );
+        headers.append("connection", HeaderValue::from_static("x-hop"));
+        headers.insert("x-trace", HeaderValue::from_static("1"));
+        headers.insert("x-hop", HeaderValue::from_static("2"));
+        headers.insert("x-request-id", HeaderValue::from_static("abc"));
+        strip_hop_by_hop(&mut headers);
+        assert!(headers.get("connection").is_none());
+        assert!(headers.get("x-trace").is_none());
+        assert!(headers.get("x-hop").is_none());
+        assert!(headers.get("x-request-id").is_some());
+    }
+}
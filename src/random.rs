@@ -0,0 +1,122 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Mix two values into a well-distributed seed (SplitMix64)
+fn mix(a: u64, b: u64) -> u64 {
+    let mut z = a ^ b.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A hash of a name that stays the same across runs and builds (FNV-1a)
+fn stable_hash(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Hands out the random number generator of each request.
+///
+/// Request N of a listener always gets the same generator for a given seed,
+/// however requests are scheduled.
+#[derive(Debug)]
+pub struct Seeder {
+    pub seed: u64,
+    /// Seed of this listener's stream, derived from `seed` and its name
+    stream: u64,
+    requests: AtomicU64,
+}
+
+impl Seeder {
+    /// Use the given seed, or pick one at random
+    pub fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| rand::rng().random());
+        Self {
+            seed,
+            stream: seed,
+            requests: AtomicU64::new(0),
+        }
+    }
+
+    /// A separate stream for the named listener, so listeners don't shift each other's numbers
+    pub fn for_listener(&self, name: &str) -> Self {
+        Self {
+            seed: self.seed,
+            stream: mix(self.seed, stable_hash(name)),
+            requests: AtomicU64::new(0),
+        }
+    }
+
+    /// The generator of the next request
+    pub fn next_rng(&self) -> RequestRng {
+        let request = self.requests.fetch_add(1, Ordering::Relaxed);
+        RequestRng::seeded(mix(self.stream, request))
+    }
+}
+
+/// The random number generator of one request, shared by everything that handles it
+#[derive(Debug, Clone)]
+pub struct RequestRng(Arc<Mutex<StdRng>>);
+
+impl Default for RequestRng {
+    fn default() -> Self {
+        Self::seeded(rand::rng().random())
+    }
+}
+
+impl RequestRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self(Arc::new(Mutex::new(StdRng::seed_from_u64(seed))))
+    }
+
+    /// Draw from the generator
+    pub fn with<T>(&self, f: impl FnOnce(&mut StdRng) -> T) -> T {
+        f(&mut self.0.lock().unwrap())
+    }
+
+    /// An independent generator seeded from this one, e.g. for a streamed body
+    pub fn fork(&self) -> StdRng {
+        self.with(|rng| StdRng::seed_from_u64(rng.random()))
+    }
+}
+
+impl minijinja::value::Object for RequestRng {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draws(seeder: &Seeder) -> Vec<u64> {
+        (0..3)
+            .map(|_| seeder.next_rng().with(|rng| rng.random()))
+            .collect()
+    }
+
+    #[test]
+    fn test_same_seed_same_requests() {
+        let a = Seeder::new(Some(42));
+        let b = Seeder::new(Some(42));
+        assert_eq!(draws(&a), draws(&b));
+        assert_ne!(draws(&a), draws(&Seeder::new(Some(43))));
+        // Every request gets its own numbers
+        let values = draws(&Seeder::new(Some(42)));
+        assert_ne!(values[0], values[1]);
+    }
+
+    #[test]
+    fn test_listeners_have_separate_streams() {
+        let seeder = Seeder::new(Some(7));
+        let payments = seeder.for_listener("payments");
+        // Requests to another listener don't shift this one's numbers
+        draws(&seeder.for_listener("users"));
+        assert_eq!(draws(&payments), draws(&seeder.for_listener("payments")));
+        assert_ne!(
+            draws(&seeder.for_listener("payments")),
+            draws(&seeder.for_listener("users"))
+        );
+        assert_eq!(payments.seed, 7);
+    }
+}
@@ -0,0 +1,365 @@
+use crate::util::parse_duration;
+use anyhow::anyhow;
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Clients tracked before the least recently seen are forgotten
+const MAX_CLIENTS: usize = 10_000;
+
+/// How a rate limit counts requests
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum RateLimitAlgorithm {
+    /// A bucket of `limit` tokens refilled evenly over the window; allows short bursts
+    #[default]
+    TokenBucket,
+    /// At most `limit` requests per window; the count resets when the window ends
+    FixedWindow,
+}
+
+impl std::str::FromStr for RateLimitAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "token-bucket" => Ok(Self::TokenBucket),
+            "fixed-window" => Ok(Self::FixedWindow),
+            _ => Err(anyhow!(
+                "Invalid rate limit algorithm '{}'. Valid values: token-bucket, fixed-window",
+                s
+            )),
+        }
+    }
+}
+
+/// What requests share a limit
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum RateLimitKey {
+    /// One limit for all requests
+    #[default]
+    Global,
+    /// One limit per client IP address
+    Ip,
+    /// One limit per value of a header, such as an API key
+    Header(HeaderName),
+    /// One limit per request path
+    Route,
+}
+
+impl RateLimitKey {
+    /// The key of a request: empty for `global` and for requests without the header
+    pub fn of(&self, peer: Option<IpAddr>, path: &str, headers: &HeaderMap) -> String {
+        match self {
+            RateLimitKey::Global => String::new(),
+            RateLimitKey::Ip => peer.map_or_else(|| "local".to_string(), |ip| ip.to_string()),
+            RateLimitKey::Header(name) => headers
+                .get(name)
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                .unwrap_or_default(),
+            RateLimitKey::Route => path.to_string(),
+        }
+    }
+}
+
+impl std::str::FromStr for RateLimitKey {
+    type Err = anyhow::Error;
+
+    /// Parse `global`, `ip`, `route` or `header:<name>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(name) = s.strip_prefix("header:") {
+            return HeaderName::from_bytes(name.trim().as_bytes())
+                .map(Self::Header)
+                .map_err(|_| anyhow!("Invalid header name '{}'", name));
+        }
+        match s.to_lowercase().as_str() {
+            "global" => Ok(Self::Global),
+            "ip" => Ok(Self::Ip),
+            "route" => Ok(Self::Route),
+            _ => Err(anyhow!(
+                "Invalid rate limit key '{}'. Valid values: global, ip, route, header:<name>",
+                s
+            )),
+        }
+    }
+}
+
+/// The outcome of checking a request against a rate limit
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Time until the limit is fully available again
+    pub reset: Duration,
+    /// Time until the next request would be allowed (zero when allowed)
+    pub retry_after: Duration,
+    /// When the client was throttled before this request: how long it waited,
+    /// and whether it retried before the limit allowed it to
+    pub backoff: Option<(Duration, bool)>,
+}
+
+impl Decision {
+    /// Add the `X-RateLimit-*` headers, and `Retry-After` if the request was throttled
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        let seconds = |duration: Duration| HeaderValue::from(duration.as_secs_f64().ceil() as u64);
+        headers.insert("x-ratelimit-limit", HeaderValue::from(self.limit));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from(self.remaining));
+        headers.insert("x-ratelimit-reset", seconds(self.reset));
+        if !self.allowed {
+            headers.insert(
+                "retry-after",
+                seconds(self.retry_after.max(Duration::from_secs(1))),
+            );
+        }
+    }
+}
+
+/// Limit state of one client (or route, or the whole server)
+#[derive(Debug)]
+struct Client {
+    /// Tokens left (token bucket) or requests counted (fixed window)
+    level: f64,
+    /// When `level` was last refilled, or the current window started
+    since: Instant,
+    /// When the client was last throttled and how long it had to wait
+    throttled: Option<(Instant, Duration)>,
+    /// The check that last saw the client
+    seen: u64,
+}
+
+/// Limit state per key, forgetting the least recently seen keys beyond `MAX_CLIENTS`
+#[derive(Debug, Default)]
+struct Clients {
+    clients: HashMap<String, Client>,
+    /// Keys by the check that last saw them, oldest first
+    recent: BTreeMap<u64, String>,
+    checks: u64,
+}
+
+/// Enforces a request rate limit per key
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    algorithm: RateLimitAlgorithm,
+    key: RateLimitKey,
+    clients: Mutex<Clients>,
+}
+
+impl RateLimiter {
+    /// A limit such as `100/s`, `1000/m` or `10/5s`
+    pub fn new(
+        rate: &str,
+        algorithm: RateLimitAlgorithm,
+        key: RateLimitKey,
+    ) -> anyhow::Result<Self> {
+        let (limit, window) = rate.split_once('/').ok_or_else(|| {
+            anyhow!(
+                "Expected '<requests>/<duration>' (e.g. 100/s), got '{}'",
+                rate
+            )
+        })?;
+        let limit: u32 = limit
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Invalid request count in rate limit '{}'", rate))?;
+        if limit == 0 {
+            return Err(anyhow!(
+                "Rate limit '{}' must allow at least one request",
+                rate
+            ));
+        }
+        Ok(Self {
+            limit,
+            window: parse_duration(window)?,
+            algorithm,
+            key,
+            clients: Mutex::new(Clients::default()),
+        })
+    }
+
+    /// The key a request is limited under. Requests without the keyed header share one limit.
+    pub fn key(&self, peer: Option<IpAddr>, path: &str, headers: &HeaderMap) -> String {
+        self.key.of(peer, path, headers)
+    }
+
+    /// Count a request under `key` at time `now`
+    pub fn check(&self, key: &str, now: Instant) -> Decision {
+        let mut guard = self.clients.lock().unwrap();
+        let Clients {
+            clients,
+            recent,
+            checks,
+        } = &mut *guard;
+        *checks += 1;
+        if clients.len() >= MAX_CLIENTS
+            && !clients.contains_key(key)
+            && let Some((_, oldest)) = recent.pop_first()
+        {
+            clients.remove(&oldest);
+        }
+        let client = clients.entry(key.to_string()).or_insert_with(|| Client {
+            level: match self.algorithm {
+                RateLimitAlgorithm::TokenBucket => self.limit as f64,
+                RateLimitAlgorithm::FixedWindow => 0.0,
+            },
+            since: now,
+            throttled: None,
+            seen: 0,
+        });
+        recent.remove(&client.seen);
+        client.seen = *checks;
+        recent.insert(*checks, key.to_string());
+
+        let limit = self.limit as f64;
+        let (allowed, remaining, reset, retry_after) = match self.algorithm {
+            RateLimitAlgorithm::TokenBucket => {
+                let per_token = self.window.div_f64(limit);
+                let refilled = now.saturating_duration_since(client.since).as_secs_f64()
+                    / per_token.as_secs_f64();
+                client.level = (client.level + refilled).min(limit);
+                client.since = now;
+                let allowed = client.level >= 1.0;
+                if allowed {
+                    client.level -= 1.0;
+                }
+                (
+                    allowed,
+                    client.level.floor(),
+                    per_token.mul_f64(limit - client.level),
+                    per_token.mul_f64((1.0 - client.level).max(0.0)),
+                )
+            }
+            RateLimitAlgorithm::FixedWindow => {
+                if now.saturating_duration_since(client.since) >= self.window {
+                    client.level = 0.0;
+                    client.since = now;
+                }
+                let allowed = client.level < limit;
+                if allowed {
+                    client.level += 1.0;
+                }
+                let reset = self
+                    .window
+                    .saturating_sub(now.saturating_duration_since(client.since));
+                (allowed, limit - client.level, reset, reset)
+            }
+        };
+
+        let backoff = client.throttled.take().map(|(at, retry_after)| {
+            let waited = now.saturating_duration_since(at);
+            (waited, waited < retry_after)
+        });
+        if !allowed {
+            client.throttled = Some((now, retry_after));
+        }
+        Decision {
+            allowed,
+            limit: self.limit,
+            remaining: remaining as u32,
+            reset,
+            retry_after: if allowed { Duration::ZERO } else { retry_after },
+            backoff,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rate_limit() {
+        let limiter =
+            RateLimiter::new("10/5s", RateLimitAlgorithm::default(), RateLimitKey::Ip).unwrap();
+        assert_eq!(limiter.limit, 10);
+        assert_eq!(limiter.window, Duration::from_secs(5));
+        assert!(RateLimiter::new("0/s", RateLimitAlgorithm::default(), RateLimitKey::Ip).is_err());
+        assert!(RateLimiter::new("100", RateLimitAlgorithm::default(), RateLimitKey::Ip).is_err());
+
+        assert_eq!(
+            "header:X-Api-Key".parse::<RateLimitKey>().unwrap(),
+            RateLimitKey::Header(HeaderName::from_static("x-api-key"))
+        );
+        assert!("client".parse::<RateLimitKey>().is_err());
+        assert_eq!(
+            "fixed-window".parse::<RateLimitAlgorithm>().unwrap(),
+            RateLimitAlgorithm::FixedWindow
+        );
+    }
+
+    #[test]
+    fn test_token_bucket_refills_gradually() {
+        let limiter =
+            RateLimiter::new("2/s", RateLimitAlgorithm::TokenBucket, RateLimitKey::Global).unwrap();
+        let start = Instant::now();
+        assert!(limiter.check("", start).allowed);
+        assert_eq!(limiter.check("", start).remaining, 0);
+        let throttled = limiter.check("", start);
+        assert!(!throttled.allowed);
+        assert_eq!(throttled.retry_after, Duration::from_millis(500));
+
+        // Half a second refills one token; the client waited long enough
+        let retry = limiter.check("", start + Duration::from_millis(500));
+        assert!(retry.allowed);
+        assert_eq!(retry.backoff, Some((Duration::from_millis(500), false)));
+        assert_eq!(retry.reset, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_fixed_window_resets_and_tracks_early_retries() {
+        let limiter =
+            RateLimiter::new("1/m", RateLimitAlgorithm::FixedWindow, RateLimitKey::Global).unwrap();
+        let start = Instant::now();
+        assert!(limiter.check("a", start).allowed);
+        let throttled = limiter.check("a", start + Duration::from_secs(20));
+        assert!(!throttled.allowed);
+        assert_eq!(throttled.retry_after, Duration::from_secs(40));
+        // Other keys have their own window
+        assert!(limiter.check("b", start + Duration::from_secs(20)).allowed);
+
+        // Retrying after 10s instead of 40s is early
+        let early = limiter.check("a", start + Duration::from_secs(30));
+        assert!(!early.allowed);
+        assert_eq!(early.backoff, Some((Duration::from_secs(10), true)));
+        let later = limiter.check("a", start + Duration::from_secs(60));
+        assert!(later.allowed);
+        assert_eq!(later.backoff, Some((Duration::from_secs(30), false)));
+    }
+
+    #[test]
+    fn test_headers() {
+        let limiter =
+            RateLimiter::new("1/s", RateLimitAlgorithm::FixedWindow, RateLimitKey::Global).unwrap();
+        let start = Instant::now();
+        limiter.check("", start);
+        let mut headers = HeaderMap::new();
+        limiter
+            .check("", start + Duration::from_millis(300))
+            .apply_headers(&mut headers);
+        assert_eq!(headers["x-ratelimit-limit"], "1");
+        assert_eq!(headers["x-ratelimit-remaining"], "0");
+        assert_eq!(headers["x-ratelimit-reset"], "1");
+        assert_eq!(headers["retry-after"], "1");
+    }
+
+    #[test]
+    fn test_least_recently_seen_clients_are_forgotten() {
+        let limiter =
+            RateLimiter::new("1/m", RateLimitAlgorithm::FixedWindow, RateLimitKey::Ip).unwrap();
+        let now = Instant::now();
+        for i in 0..MAX_CLIENTS {
+            limiter.check(&i.to_string(), now);
+        }
+        // Seeing client 0 again makes client 1 the oldest, even inside the window
+        assert!(!limiter.check("0", now).allowed);
+        assert!(limiter.check("new", now).allowed);
+        let clients = limiter.clients.lock().unwrap();
+        assert_eq!(clients.clients.len(), MAX_CLIENTS);
+        assert_eq!(clients.recent.len(), MAX_CLIENTS);
+        assert!(clients.clients.contains_key("0"));
+        assert!(!clients.clients.contains_key("1"));
+    }
+}
@@ -0,0 +1,436 @@
+use crate::grpc::decode_hex;
+use crate::routes::build_response;
+use crate::state::{AppState, Outcome, RequestLog};
+use anyhow::{Context, anyhow};
+use axum::{
+    body::{Bytes, Full, boxed},
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri},
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A recorded request/response pair, stored as one JSON object per line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub request_body: String,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    /// Response body, if it is valid UTF-8
+    #[serde(default)]
+    pub body: String,
+    /// Response body as hex, for binary bodies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_hex: Option<String>,
+    /// Time the upstream took to produce the full response
+    pub latency_ms: f64,
+}
+
+impl Recording {
+    /// Store a response body as text, or as hex if it is not valid UTF-8
+    pub fn set_body(&mut self, body: &[u8]) {
+        match std::str::from_utf8(body) {
+            Ok(text) => {
+                self.body = text.to_string();
+                self.body_hex = None;
+            }
+            Err(_) => {
+                self.body = String::new();
+                self.body_hex = Some(body.iter().map(|b| format!("{:02x}", b)).collect());
+            }
+        }
+    }
+
+    /// The recorded response body
+    pub fn body_bytes(&self) -> anyhow::Result<Bytes> {
+        match &self.body_hex {
+            Some(hex) => Ok(decode_hex(hex).context("Invalid body_hex")?.into()),
+            None => Ok(self.body.clone().into()),
+        }
+    }
+}
+
+/// Request bodies are matched as text; binary bodies are lossily converted
+pub fn body_to_string(body: &[u8]) -> String {
+    String::from_utf8_lossy(body).into_owned()
+}
+
+/// Appends recordings to a JSON Lines file
+pub struct Recorder {
+    file: Arc<Mutex<File>>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open record file {}", path.display()))?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// Append a recording, writing on the blocking pool so the runtime's workers aren't held up
+    pub async fn record(&self, recording: &Recording) {
+        let Ok(line) = serde_json::to_string(recording) else {
+            return;
+        };
+        let file = self.file.clone();
+        let _ = tokio::task::spawn_blocking(move || {
+            let mut file = file.lock().unwrap();
+            writeln!(file, "{}", line)
+        })
+        .await;
+    }
+}
+
+/// How long replayed responses take
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReplayLatency {
+    /// Sleep for the latency observed when recording
+    #[default]
+    Recorded,
+    /// Use the configured delay model
+    Configured,
+}
+
+impl std::str::FromStr for ReplayLatency {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "recorded" => Ok(ReplayLatency::Recorded),
+            "configured" => Ok(ReplayLatency::Configured),
+            _ => Err(anyhow!(
+                "Invalid replay latency. Valid values: recorded, configured"
+            )),
+        }
+    }
+}
+
+/// Recordings matching one key, replayed in order and then from the start again
+#[derive(Debug, Default)]
+struct Candidates {
+    indices: Vec<usize>,
+    next: AtomicUsize,
+}
+
+impl Candidates {
+    fn next(&self) -> usize {
+        let i = self.next.fetch_add(1, Ordering::Relaxed);
+        self.indices[i % self.indices.len()]
+    }
+}
+
+/// Serves recorded responses.
+///
+/// Requests are matched on method, path, query and body; if nothing matches
+/// exactly, on method, path and query; and finally on method and path.
+#[derive(Debug)]
+pub struct Replay {
+    /// The recordings, with their response bodies decoded
+    recordings: Vec<(Recording, Bytes)>,
+    exact: HashMap<(String, String, Option<String>, String), Candidates>,
+    by_query: HashMap<(String, String, Option<String>), Candidates>,
+    by_path: HashMap<(String, String), Candidates>,
+    latency: ReplayLatency,
+}
+
+impl Replay {
+    pub fn load(path: &Path, latency: ReplayLatency) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read replay file {}", path.display()))?;
+        let recordings = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let recording: Recording = serde_json::from_str(line)
+                    .with_context(|| format!("Invalid recording on line {}", i + 1))?;
+                let body = recording
+                    .body_bytes()
+                    .with_context(|| format!("Invalid recording on line {}", i + 1))?;
+                Ok((recording, body))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if recordings.is_empty() {
+            return Err(anyhow!("Replay file {} is empty", path.display()));
+        }
+        Ok(Self::new(recordings, latency))
+    }
+
+    pub fn new(recordings: Vec<(Recording, Bytes)>, latency: ReplayLatency) -> Self {
+        let mut replay = Self {
+            recordings: Vec::new(),
+            exact: HashMap::new(),
+            by_query: HashMap::new(),
+            by_path: HashMap::new(),
+            latency,
+        };
+        for (i, (r, _)) in recordings.iter().enumerate() {
+            let (method, path, query) = (r.method.clone(), r.path.clone(), r.query.clone());
+            replay
+                .exact
+                .entry((
+                    method.clone(),
+                    path.clone(),
+                    query.clone(),
+                    r.request_body.clone(),
+                ))
+                .or_default()
+                .indices
+                .push(i);
+            replay
+                .by_query
+                .entry((method.clone(), path.clone(), query))
+                .or_default()
+                .indices
+                .push(i);
+            replay
+                .by_path
+                .entry((method, path))
+                .or_default()
+                .indices
+                .push(i);
+        }
+        replay.recordings = recordings;
+        replay
+    }
+
+    /// Find the next recording for a request
+    fn find(
+        &self,
+        method: &str,
+        path: &str,
+        query: Option<&str>,
+        body: &str,
+    ) -> Option<&(Recording, Bytes)> {
+        let (method, path, query) = (
+            method.to_string(),
+            path.to_string(),
+            query.map(String::from),
+        );
+        let candidates = self
+            .exact
+            .get(&(
+                method.clone(),
+                path.clone(),
+                query.clone(),
+                body.to_string(),
+            ))
+            .or_else(|| self.by_query.get(&(method.clone(), path.clone(), query)))
+            .or_else(|| self.by_path.get(&(method, path)))?;
+        self.recordings.get(candidates.next())
+    }
+}
+
+/// Serve a recorded response, applying the error model and the replay latency
+pub async fn replay_handler(
+    state: &AppState,
+    replay: &Replay,
+    method: &Method,
+    uri: &Uri,
+    body: &Bytes,
+) -> Response {
+    let start = state.clock.now();
+    let now = state.now_timestamp();
+    state.increment_requests();
+
+    let recording = replay.find(
+        method.as_str(),
+        uri.path(),
+        uri.query(),
+        &body_to_string(body),
+    );
+    let delay = match (replay.latency, recording) {
+        (ReplayLatency::Recorded, Some((recording, _))) => {
+            Duration::from_secs_f64(recording.latency_ms.max(0.0) / 1000.0)
+        }
+        _ => Duration::from_millis(state.get_delay()),
+    };
+    if !delay.is_zero() {
+        state.clock.sleep(delay).await;
+    }
+
+    let injected = state.get_error();
+    let response = match (injected, recording) {
+        (Some(status), _) => build_response(
+            state,
+            StatusCode::from_u16(status).unwrap_or(StatusCode::SERVICE_UNAVAILABLE),
+            uri.path(),
+            method.as_str(),
+            now,
+            state.elapsed_ms(start),
+            delay.as_millis() as u64,
+        ),
+        (None, None) => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .header("content-type", "text/plain")
+            .body(boxed(format!(
+                "No recorded response for {} {}",
+                method,
+                uri.path()
+            )))
+            .unwrap(),
+        (None, Some((recording, body))) => {
+            let mut headers = HeaderMap::new();
+            for (name, value) in &recording.headers {
+                if let (Ok(name), Ok(value)) = (
+                    HeaderName::from_bytes(name.as_bytes()),
+                    HeaderValue::from_str(value),
+                ) {
+                    headers.append(name, value);
+                }
+            }
+            headers.remove("content-length");
+            let mut response = Response::new(boxed(Full::from(body.clone())));
+            *response.status_mut() =
+                StatusCode::from_u16(recording.status).unwrap_or(StatusCode::OK);
+            *response.headers_mut() = headers;
+            response
+        }
+    };
+
+    let log = RequestLog {
+        outcome: match injected {
+            Some(_) => Outcome::InjectedError,
+            None => Outcome::Served,
+        },
+        timestamp: now,
+        duration_ms: state.elapsed_ms(start),
+        ..RequestLog::new(
+            state,
+            method.as_str(),
+            uri.path(),
+            response.status().as_u16(),
+        )
+    };
+    state.record(log).await;
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(path: &str, query: Option<&str>, request_body: &str, body: &str) -> Recording {
+        Recording {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            query: query.map(String::from),
+            request_body: request_body.to_string(),
+            status: 200,
+            headers: vec![("content-type".to_string(), "text/plain".to_string())],
+            body: body.to_string(),
+            body_hex: None,
+            latency_ms: 12.5,
+        }
+    }
+
+    fn replay(recordings: Vec<Recording>) -> Replay {
+        let recordings = recordings
+            .into_iter()
+            .map(|recording| {
+                let body = recording.body_bytes().unwrap();
+                (recording, body)
+            })
+            .collect();
+        Replay::new(recordings, ReplayLatency::Recorded)
+    }
+
+    #[test]
+    fn test_find_prefers_exact_match() {
+        let replay = replay(vec![
+            recording("/users", Some("id=1"), "", "one"),
+            recording("/users", Some("id=2"), "", "two"),
+            recording("/users", None, "{\"a\":1}", "body"),
+        ]);
+        assert_eq!(
+            replay.find("GET", "/users", Some("id=2"), "").unwrap().1,
+            "two"
+        );
+        assert_eq!(
+            replay.find("GET", "/users", None, "{\"a\":1}").unwrap().1,
+            "body"
+        );
+        // Unknown query falls back to matching on the path only
+        assert!(replay.find("GET", "/users", Some("id=3"), "").is_some());
+        assert!(replay.find("POST", "/users", None, "").is_none());
+    }
+
+    #[test]
+    fn test_find_cycles_through_duplicates() {
+        let replay = replay(vec![
+            recording("/pay", None, "", "first"),
+            recording("/pay", None, "", "second"),
+        ]);
+        let bodies: Vec<_> = (0..3)
+            .map(|_| replay.find("GET", "/pay", None, "").unwrap().1.clone())
+            .collect();
+        assert_eq!(bodies, ["first", "second", "first"]);
+    }
+
+    #[test]
+    fn test_recording_round_trip() {
+        let original = recording("/a", Some("x=1"), "", "ok");
+        let line = serde_json::to_string(&original).unwrap();
+        assert_eq!(serde_json::from_str::<Recording>(&line).unwrap(), original);
+    }
+
+    #[test]
+    fn test_binary_body_stored_as_hex() {
+        let mut recording = recording("/bin", None, "", "");
+        recording.set_body(&[0xff, 0x00, 0x41]);
+        assert_eq!(recording.body_hex.as_deref(), Some("ff0041"));
+        assert_eq!(recording.body_bytes().unwrap(), vec![0xff, 0x00, 0x41]);
+        recording.set_body(b"text");
+        assert_eq!(recording.body, "text");
+        assert_eq!(recording.body_bytes().unwrap(), "text");
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_hex_with_line_number() {
+        let dir = std::env::temp_dir().join(format!("rustrate-replay-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("traffic.jsonl");
+        let mut bad = recording("/bin", None, "", "");
+        bad.body_hex = Some("zz".to_string());
+        let lines = [recording("/ok", None, "", "ok"), bad]
+            .iter()
+            .map(|r| serde_json::to_string(r).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        std::fs::write(&path, lines).unwrap();
+        let err = Replay::load(&path, ReplayLatency::Recorded).unwrap_err();
+        assert!(format!("{:#}", err).contains("line 3"), "{:#}", err);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_replay_latency() {
+        assert_eq!(
+            "recorded".parse::<ReplayLatency>().unwrap(),
+            ReplayLatency::Recorded
+        );
+        assert_eq!(
+            "configured".parse::<ReplayLatency>().unwrap(),
+            ReplayLatency::Configured
+        );
+        assert!("fast".parse::<ReplayLatency>().is_err());
+    }
+}
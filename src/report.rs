@@ -0,0 +1,565 @@
+use anyhow::{Context, bail};
+use std::fmt::Write;
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+
+/// Size of the SVG charts, in pixels
+const CHART_SIZE: (f64, f64) = (800.0, 260.0);
+
+/// Space around the plot for the axis labels: left, right, top, bottom
+const CHART_MARGIN: (f64, f64, f64, f64) = (60.0, 20.0, 24.0, 36.0);
+
+/// Markdown sparklines are merged down to this many columns
+const SPARKLINE_WIDTH: usize = 100;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// How a report is written, by the file's extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A single page with inline SVG charts
+    Html,
+    /// Tables, with the timelines as sparklines
+    Markdown,
+}
+
+impl ReportFormat {
+    /// The format of a report written to `path`: `.html`/`.htm` or `.md`
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("html" | "htm") => Ok(ReportFormat::Html),
+            Some("md" | "markdown") => Ok(ReportFormat::Markdown),
+            _ => bail!(
+                "Invalid report file '{}'. Use a .html or .md file",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Where the report goes at the end of the run, and the settings the run was started with
+#[derive(Debug, Clone)]
+pub struct ReportConfig {
+    pub path: PathBuf,
+    pub format: ReportFormat,
+    /// Run configuration as (setting, value)
+    pub settings: Vec<(String, String)>,
+}
+
+impl ReportConfig {
+    /// Checks the report can be written, so a bad path fails at startup and not after the run
+    pub fn new(path: PathBuf, settings: Vec<(String, String)>) -> anyhow::Result<Self> {
+        let format = ReportFormat::from_path(&path)?;
+        let existed = path.exists();
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .with_context(|| format!("Cannot write report {}", path.display()))?;
+        if !existed {
+            let _ = fs::remove_file(&path);
+        }
+        Ok(Self {
+            path,
+            format,
+            settings,
+        })
+    }
+}
+
+/// A line of a chart, with gaps where a bucket has no value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub name: &'static str,
+    /// CSS color
+    pub color: &'static str,
+    pub values: Vec<Option<f64>>,
+}
+
+/// A timeline of values in fixed-width buckets
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chart {
+    pub title: String,
+    pub unit: &'static str,
+    /// Seconds from the start of the run to the first bucket
+    pub start: f64,
+    /// Width of the buckets in seconds
+    pub bucket: f64,
+    pub lines: Vec<Line>,
+    /// Scenario phase boundaries, as (seconds from the start, phase that began)
+    pub markers: Vec<(f64, String)>,
+}
+
+impl Chart {
+    fn buckets(&self) -> usize {
+        self.lines
+            .iter()
+            .map(|line| line.values.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn max_value(&self) -> f64 {
+        self.lines
+            .iter()
+            .flat_map(|line| line.values.iter().flatten())
+            .copied()
+            .fold(0.0, f64::max)
+    }
+}
+
+/// A table with a header row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub title: String,
+    /// Shown under the title, e.g. how values were measured
+    pub note: Option<String>,
+    pub headers: Vec<&'static str>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// What a run served, as written to `--report`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// When the report was written
+    pub generated: String,
+    pub settings: Vec<(String, String)>,
+    pub summary: Vec<(String, String)>,
+    pub charts: Vec<Chart>,
+    pub tables: Vec<Table>,
+}
+
+impl Report {
+    /// Write the report to `config.path` in its format
+    pub fn write(&self, config: &ReportConfig) -> anyhow::Result<()> {
+        let contents = match config.format {
+            ReportFormat::Html => self.to_html(),
+            ReportFormat::Markdown => self.to_markdown(),
+        };
+        fs::write(&config.path, contents)
+            .with_context(|| format!("Failed to write report {}", config.path.display()))
+    }
+
+    /// A standalone page: styles and charts are inline, nothing is loaded from elsewhere
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>rustrate report</title>\n<style>\n\
+             body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 900px; color: #222; }\n\
+             table { border-collapse: collapse; margin-bottom: 1em; }\n\
+             th, td { border: 1px solid #ccc; padding: 4px 10px; text-align: left; }\n\
+             th { background: #f4f4f4; }\n\
+             td.number { text-align: right; font-variant-numeric: tabular-nums; }\n\
+             .note { color: #666; font-size: 0.9em; }\n\
+             svg text { font: 12px system-ui, sans-serif; fill: #444; }\n\
+             </style>\n</head>\n<body>\n<h1>rustrate report</h1>\n",
+        );
+        let _ = writeln!(
+            html,
+            "<p class=\"note\">Generated {}</p>",
+            escape_html(&self.generated)
+        );
+        for (title, pairs) in [
+            ("Run configuration", &self.settings),
+            ("Summary", &self.summary),
+        ] {
+            let _ = writeln!(html, "<h2>{}</h2>\n<table>", title);
+            for (name, value) in pairs {
+                let _ = writeln!(
+                    html,
+                    "<tr><th>{}</th><td>{}</td></tr>",
+                    escape_html(name),
+                    escape_html(value)
+                );
+            }
+            html.push_str("</table>\n");
+        }
+        if !self.charts.is_empty() {
+            html.push_str("<h2>Timeline</h2>\n");
+        }
+        for chart in &self.charts {
+            let _ = writeln!(
+                html,
+                "<h3>{} ({})</h3>\n{}",
+                escape_html(&chart.title),
+                chart.unit,
+                chart_svg(chart)
+            );
+        }
+        for table in &self.tables {
+            let _ = writeln!(html, "<h2>{}</h2>", escape_html(&table.title));
+            if let Some(note) = &table.note {
+                let _ = writeln!(html, "<p class=\"note\">{}</p>", escape_html(note));
+            }
+            html.push_str("<table>\n<tr>");
+            for header in &table.headers {
+                let _ = write!(html, "<th>{}</th>", header);
+            }
+            html.push_str("</tr>\n");
+            for row in &table.rows {
+                html.push_str("<tr>");
+                for cell in row {
+                    let class = if is_number(cell) {
+                        " class=\"number\""
+                    } else {
+                        ""
+                    };
+                    let _ = write!(html, "<td{}>{}</td>", class, escape_html(cell));
+                }
+                html.push_str("</tr>\n");
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// Markdown tables, with each chart's lines as sparklines
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# rustrate report\n\nGenerated {}\n", self.generated);
+        for (title, header, pairs) in [
+            ("Run configuration", "Setting", &self.settings),
+            ("Summary", "Measure", &self.summary),
+        ] {
+            let rows: Vec<Vec<String>> = pairs
+                .iter()
+                .map(|(name, value)| vec![name.clone(), value.clone()])
+                .collect();
+            let _ = write!(
+                markdown,
+                "\n## {}\n\n{}",
+                title,
+                markdown_table(&[header, "Value"], &rows)
+            );
+        }
+        if !self.charts.is_empty() {
+            markdown.push_str("\n## Timeline\n");
+        }
+        for chart in &self.charts {
+            let _ = writeln!(markdown, "\n### {} ({})\n", chart.title, chart.unit);
+            let _ = writeln!(
+                markdown,
+                "From {} to {}, in {} buckets.\n",
+                format_seconds(chart.start),
+                format_seconds(chart.start + chart.bucket * chart.buckets() as f64),
+                format_seconds(chart.bucket)
+            );
+            markdown.push_str("```text\n");
+            let max = chart.max_value();
+            let width = chart.lines.iter().map(|line| line.name.len()).max();
+            for line in &chart.lines {
+                let peak = line.values.iter().flatten().copied().fold(0.0, f64::max);
+                let _ = writeln!(
+                    markdown,
+                    "{:>width$} {} max {}",
+                    line.name,
+                    sparkline(&line.values, max),
+                    format_value(peak),
+                    width = width.unwrap_or(0)
+                );
+            }
+            markdown.push_str("```\n");
+            if !chart.markers.is_empty() {
+                let markers: Vec<String> = chart
+                    .markers
+                    .iter()
+                    .map(|(at, name)| format!("{} at {}", name, format_seconds(*at)))
+                    .collect();
+                let _ = writeln!(markdown, "\nPhases: {}", markers.join(", "));
+            }
+        }
+        for table in &self.tables {
+            let _ = writeln!(markdown, "\n## {}\n", table.title);
+            if let Some(note) = &table.note {
+                let _ = writeln!(markdown, "{}\n", note);
+            }
+            markdown.push_str(&markdown_table(&table.headers, &table.rows));
+        }
+        markdown
+    }
+}
+
+/// The chart as an inline SVG: lines over a grid, with the scenario phases marked
+fn chart_svg(chart: &Chart) -> String {
+    let (width, height) = CHART_SIZE;
+    let (left, right, top, bottom) = CHART_MARGIN;
+    let (plot_width, plot_height) = (width - left - right, height - top - bottom);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+        width, height
+    );
+    let buckets = chart.buckets();
+    if buckets == 0 {
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\">No complete buckets yet</text>\n</svg>",
+            left,
+            top + plot_height / 2.0
+        );
+        return svg;
+    }
+
+    let span = chart.bucket * buckets as f64;
+    let y_max = nice_ceiling(chart.max_value() * 1.1);
+    let x = |seconds: f64| left + (seconds - chart.start) / span * plot_width;
+    let y = |value: f64| top + plot_height - value / y_max * plot_height;
+
+    // Grid and axis labels
+    for i in 0..=4 {
+        let value = y_max * i as f64 / 4.0;
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{0}\" x2=\"{1}\" y1=\"{2:.1}\" y2=\"{2:.1}\" stroke=\"#e4e4e4\"/>\
+             <text x=\"{3}\" y=\"{4:.1}\" text-anchor=\"end\">{5}</text>",
+            left,
+            left + plot_width,
+            y(value),
+            left - 6.0,
+            y(value) + 4.0,
+            format_value(value)
+        );
+        let seconds = chart.start + span * i as f64 / 4.0;
+        let _ = writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            x(seconds),
+            height - bottom + 18.0,
+            format_seconds(seconds)
+        );
+    }
+
+    for (at, name) in &chart.markers {
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{0:.1}\" x2=\"{0:.1}\" y1=\"{1}\" y2=\"{2}\" stroke=\"#888\" stroke-dasharray=\"4 3\"/>\
+             <text x=\"{3:.1}\" y=\"{4}\">{5}</text>",
+            x(*at),
+            top,
+            top + plot_height,
+            x(*at) + 3.0,
+            top - 6.0,
+            escape_html(name)
+        );
+    }
+
+    // Lines through the middle of the buckets, broken where buckets have no value
+    for line in &chart.lines {
+        let mut segment = Vec::new();
+        let points = line.values.iter().enumerate().map(|(i, value)| {
+            value.map(|value| (x(chart.start + (i as f64 + 0.5) * chart.bucket), y(value)))
+        });
+        for point in points.chain([None]) {
+            match point {
+                Some((px, py)) => segment.push(format!("{:.1},{:.1}", px, py)),
+                None if segment.len() == 1 => {
+                    let (px, py) = segment[0].split_once(',').unwrap();
+                    let _ = writeln!(
+                        svg,
+                        "<circle cx=\"{}\" cy=\"{}\" r=\"2\" fill=\"{}\"/>",
+                        px, py, line.color
+                    );
+                    segment.clear();
+                }
+                None if !segment.is_empty() => {
+                    let _ = writeln!(
+                        svg,
+                        "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" points=\"{}\"/>",
+                        line.color,
+                        segment.join(" ")
+                    );
+                    segment.clear();
+                }
+                None => {}
+            }
+        }
+    }
+
+    // Legend, top right
+    for (i, line) in chart.lines.iter().rev().enumerate() {
+        let lx = left + plot_width - 70.0 * (i + 1) as f64;
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{}\" width=\"10\" height=\"10\" fill=\"{}\"/>\
+             <text x=\"{:.1}\" y=\"{}\">{}</text>",
+            lx,
+            top + 4.0,
+            line.color,
+            lx + 14.0,
+            top + 13.0,
+            line.name
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// The smallest 1, 2 or 5 times a power of ten at or above `value`, at least 1
+fn nice_ceiling(value: f64) -> f64 {
+    if value <= 1.0 {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(value.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|step| step * magnitude)
+        .find(|&nice| nice >= value)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// Values scaled to `max` as block characters, blank where there is no value. Long series
+/// are merged down to `SPARKLINE_WIDTH` columns, keeping each column's peak.
+fn sparkline(values: &[Option<f64>], max: f64) -> String {
+    let per_column = values.len().div_ceil(SPARKLINE_WIDTH).max(1);
+    values
+        .chunks(per_column)
+        .map(|chunk| {
+            let peak = chunk.iter().flatten().copied().reduce(f64::max);
+            match peak {
+                None => ' ',
+                Some(_) if max <= 0.0 => SPARKS[0],
+                Some(value) => {
+                    let level = (value / max * (SPARKS.len() - 1) as f64).round() as usize;
+                    SPARKS[level.min(SPARKS.len() - 1)]
+                }
+            }
+        })
+        .collect()
+}
+
+fn markdown_table<H: AsRef<str>>(headers: &[H], rows: &[Vec<String>]) -> String {
+    let cell = |text: &str| text.replace('|', "\\|").replace('\n', " ");
+    let headers: Vec<String> = headers.iter().map(|h| cell(h.as_ref())).collect();
+    let mut table = format!(
+        "| {} |\n|{}|\n",
+        headers.join(" | "),
+        vec!["---"; headers.len()].join("|")
+    );
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|text| cell(text)).collect();
+        let _ = writeln!(table, "| {} |", cells.join(" | "));
+    }
+    table
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn is_number(text: &str) -> bool {
+    text.trim_end_matches('%').parse::<f64>().is_ok()
+}
+
+/// A value without trailing zeros, e.g. `12`, `0.5`, `2.25`
+fn format_value(value: f64) -> String {
+    let text = format!("{:.2}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Seconds from the start, e.g. `45s`, `2m30s`
+pub fn format_seconds(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    match seconds {
+        0..120 => format!("{}s", seconds),
+        _ if seconds.is_multiple_of(60) => format!("{}m", seconds / 60),
+        _ => format!("{}m{:02}s", seconds / 60, seconds % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> Report {
+        Report {
+            generated: "2026-10-18 12:00:00 UTC".to_string(),
+            settings: vec![("Seed".to_string(), "42".to_string())],
+            summary: vec![("Requests".to_string(), "6".to_string())],
+            charts: vec![Chart {
+                title: "Requests per second".to_string(),
+                unit: "req/s",
+                start: 0.0,
+                bucket: 1.0,
+                lines: vec![Line {
+                    name: "rps",
+                    color: "#2a7ae2",
+                    values: vec![Some(1.0), Some(4.0), None, Some(2.0)],
+                }],
+                markers: vec![(2.0, "outage".to_string())],
+            }],
+            tables: vec![Table {
+                title: "Routes".to_string(),
+                note: None,
+                headers: vec!["Route", "Status", "Requests"],
+                rows: vec![vec![
+                    "/a|<b>".to_string(),
+                    "200".to_string(),
+                    "6".to_string(),
+                ]],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_report_format() {
+        let format = |path: &str| ReportFormat::from_path(Path::new(path));
+        assert_eq!(format("out/report.HTML").unwrap(), ReportFormat::Html);
+        assert_eq!(format("report.md").unwrap(), ReportFormat::Markdown);
+        assert_eq!(
+            format("report.pdf").unwrap_err().to_string(),
+            "Invalid report file 'report.pdf'. Use a .html or .md file"
+        );
+    }
+
+    #[test]
+    fn test_report_path_checked_at_startup() {
+        let dir = std::env::temp_dir().join(format!("rustrate-report-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("report.md");
+        assert!(ReportConfig::new(path.clone(), Vec::new()).is_ok());
+        // The check leaves no empty report behind
+        assert!(!path.exists());
+        assert!(ReportConfig::new(dir.join("missing/report.md"), Vec::new()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_markdown_report() {
+        let markdown = report().to_markdown();
+        assert!(markdown.contains("| Seed | 42 |"));
+        assert!(markdown.contains("From 0s to 4s, in 1s buckets."));
+        assert!(markdown.contains("\nrps ▃█ ▅ max 4\n"));
+        assert!(markdown.contains("Phases: outage at 2s"));
+        assert!(markdown.contains("| /a\\|<b> | 200 | 6 |"));
+    }
+
+    #[test]
+    fn test_html_report() {
+        let html = report().to_html();
+        // Self-contained: nothing is fetched
+        assert!(!html.contains("src=") && !html.contains("href="));
+        assert!(html.contains("<td>/a|&lt;b&gt;</td><td class=\"number\">200</td>"));
+        // The gap splits the line; the last point stands alone
+        assert_eq!(html.matches("<polyline").count(), 1);
+        assert_eq!(html.matches("<circle").count(), 1);
+        assert!(html.contains(">outage</text>"));
+    }
+
+    #[test]
+    fn test_chart_helpers() {
+        assert_eq!(nice_ceiling(0.3), 1.0);
+        assert_eq!(nice_ceiling(13.0), 20.0);
+        assert_eq!(nice_ceiling(450.0), 500.0);
+        assert_eq!(format_seconds(90.0), "90s");
+        assert_eq!(format_seconds(150.0), "2m30s");
+        assert_eq!(format_seconds(600.0), "10m");
+        let values: Vec<Option<f64>> = (0..300).map(|i| Some(i as f64)).collect();
+        assert_eq!(sparkline(&values, 299.0).chars().count(), 100);
+    }
+}
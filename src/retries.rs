@@ -0,0 +1,522 @@
+use axum::body::{Body, Bytes, HttpBody};
+use axum::http::{HeaderMap, HeaderName, Method, Uri, header};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Bodies up to this size are hashed to tell requests without an idempotency key apart
+const MAX_FINGERPRINT_BODY: u64 = 64 * 1024;
+
+/// Requests tracked at once before the least recently attempted ones are forgotten
+const MAX_TRACKED: usize = 10_000;
+
+/// One attempt of a request, as seen by the server
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attempt {
+    /// What groups the attempts of one request: the idempotency key, or a fingerprint
+    pub key: String,
+    /// 1 for the first attempt, 2 for the first retry, ...
+    pub number: u32,
+    /// Time since the previous attempt arrived, for retries
+    pub interval_ms: Option<f64>,
+    /// Position in arrival order across all requests, identifying the attempt
+    pub order: u64,
+}
+
+/// What groups the attempts of a request
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetryKey {
+    /// The client's idempotency key: every repeat is a retry
+    Idempotency(String),
+    /// Client, method, path and query: a repeat is a retry only after a failed attempt
+    /// with the same body, since clients also send identical requests on purpose
+    Fingerprint(String),
+}
+
+/// The latest attempt of a request
+#[derive(Debug, Clone, Copy)]
+struct LastAttempt {
+    arrived: Instant,
+    /// Position in arrival order, across all requests
+    order: u64,
+    number: u32,
+    /// `None` while the attempt is in flight
+    failed: Option<bool>,
+    /// Hash of the body, once it has been read
+    body: Option<u64>,
+}
+
+/// The latest attempts, with the order they arrived in so the oldest can be forgotten
+#[derive(Debug, Default)]
+struct Attempts {
+    last: HashMap<String, LastAttempt>,
+    /// Keys by the arrival of their latest attempt, oldest first
+    arrivals: BTreeMap<u64, String>,
+    count: u64,
+}
+
+/// Recognizes retries: requests with the same idempotency key, or from the same client
+/// with the same method, path and body after a failure, within a window of the previous
+/// attempt
+#[derive(Debug)]
+pub struct RetryTracker {
+    header: HeaderName,
+    window: Duration,
+    /// The latest attempt of each request
+    attempts: Mutex<Attempts>,
+}
+
+impl Default for RetryTracker {
+    fn default() -> Self {
+        Self::new(
+            HeaderName::from_static("idempotency-key"),
+            Duration::from_secs(30),
+        )
+    }
+}
+
+impl RetryTracker {
+    pub fn new(header: HeaderName, window: Duration) -> Self {
+        Self {
+            header,
+            window,
+            attempts: Mutex::new(Attempts::default()),
+        }
+    }
+
+    /// The key grouping a request's attempts: its idempotency key if it has one,
+    /// otherwise its client, method, path and query
+    pub fn key(&self, client: &str, method: &Method, uri: &Uri, headers: &HeaderMap) -> RetryKey {
+        if let Some(id) = headers
+            .get(&self.header)
+            .and_then(|value| value.to_str().ok())
+        {
+            return RetryKey::Idempotency(id.to_string());
+        }
+        let path = uri.path_and_query().map_or("/", |p| p.as_str());
+        RetryKey::Fingerprint(format!("{} {} {}", client, method, path))
+    }
+
+    /// Whether a request's body must be read before it is observed: only when it could be
+    /// the retry of a failed attempt whose body is known, to tell the two bodies apart
+    pub fn needs_body(&self, key: &RetryKey, now: Instant) -> bool {
+        let RetryKey::Fingerprint(key) = key else {
+            return false;
+        };
+        self.attempts
+            .lock()
+            .unwrap()
+            .last
+            .get(key)
+            .is_some_and(|last| {
+                now.saturating_duration_since(last.arrived) <= self.window
+                    && last.failed == Some(true)
+                    && last.body.is_some()
+            })
+    }
+
+    /// Count an attempt of the request with `key`, arriving `now`. A fingerprinted request
+    /// is only a retry if its body matches the failed attempt's, when both are known.
+    pub fn observe(&self, key: RetryKey, body: Option<&[u8]>, now: Instant) -> Attempt {
+        let (key, after_success_too) = match key {
+            RetryKey::Idempotency(key) => (key, true),
+            RetryKey::Fingerprint(key) => (key, false),
+        };
+        let body = body.and_then(hash_body);
+        let mut attempts = self.attempts.lock().unwrap();
+        let (number, interval_ms) = match attempts.last.get(&key) {
+            Some(last)
+                if now.saturating_duration_since(last.arrived) <= self.window
+                    && (after_success_too
+                        || last.failed == Some(true)
+                            && (body.is_none() || last.body.is_none() || body == last.body)) =>
+            {
+                let interval = now.saturating_duration_since(last.arrived);
+                (last.number + 1, Some(interval.as_secs_f64() * 1000.0))
+            }
+            _ => (1, None),
+        };
+        attempts.count += 1;
+        let order = attempts.count;
+        let previous = attempts.last.insert(
+            key.clone(),
+            LastAttempt {
+                arrived: now,
+                order,
+                number,
+                failed: None,
+                body,
+            },
+        );
+        if let Some(previous) = previous {
+            attempts.arrivals.remove(&previous.order);
+        }
+        attempts.arrivals.insert(order, key.clone());
+        // Forget requests last attempted outside the window, and the oldest beyond the cap
+        while let Some((_, oldest)) = attempts.arrivals.first_key_value() {
+            let expired = attempts
+                .last
+                .get(oldest)
+                .is_none_or(|last| now.saturating_duration_since(last.arrived) > self.window);
+            if !expired && attempts.last.len() <= MAX_TRACKED {
+                break;
+            }
+            if let Some((_, oldest)) = attempts.arrivals.pop_first() {
+                attempts.last.remove(&oldest);
+            }
+        }
+        Attempt {
+            key,
+            number,
+            interval_ms,
+            order,
+        }
+    }
+
+    /// Remember the body of `attempt` once the handler has read it, so a retry can be
+    /// compared with it
+    pub fn record_body(&self, attempt: &Attempt, body: &[u8]) {
+        if let Some(last) = self.attempts.lock().unwrap().last.get_mut(&attempt.key)
+            && last.order == attempt.order
+            && last.body.is_none()
+        {
+            last.body = hash_body(body);
+        }
+    }
+
+    /// Record whether `attempt` failed, unless a later attempt has arrived since
+    pub fn finish(&self, attempt: &Attempt, failed: bool) {
+        if let Some(last) = self.attempts.lock().unwrap().last.get_mut(&attempt.key)
+            && last.order == attempt.order
+        {
+            last.failed = Some(failed);
+        }
+    }
+
+    /// Count `attempt` as failed if it is dropped before it finishes, e.g. because the
+    /// client timed out and the handler was cancelled
+    pub fn guard(self: &Arc<Self>, attempt: Attempt) -> AttemptGuard {
+        AttemptGuard {
+            tracker: self.clone(),
+            attempt,
+        }
+    }
+}
+
+/// Fails an attempt that never finished when dropped
+pub struct AttemptGuard {
+    tracker: Arc<RetryTracker>,
+    attempt: Attempt,
+}
+
+impl Drop for AttemptGuard {
+    fn drop(&mut self) {
+        if let Some(last) = self
+            .tracker
+            .attempts
+            .lock()
+            .unwrap()
+            .last
+            .get_mut(&self.attempt.key)
+            && last.order == self.attempt.order
+            && last.failed.is_none()
+        {
+            last.failed = Some(true);
+        }
+    }
+}
+
+/// Hash of a body that tells requests apart, if it is small enough and not empty
+fn hash_body(body: &[u8]) -> Option<u64> {
+    if body.is_empty() || body.len() as u64 > MAX_FINGERPRINT_BODY {
+        return None;
+    }
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+/// Buffer a small request body so it can be fingerprinted, returning a body with the same
+/// content. Large, streamed or empty bodies are passed on untouched without a fingerprint.
+pub async fn fingerprint_body(
+    body: Body,
+    headers: &HeaderMap,
+) -> Result<(Body, Option<Bytes>), hyper::Error> {
+    let length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    match length {
+        Some(1..=MAX_FINGERPRINT_BODY) if !body.is_end_stream() => {
+            let bytes = hyper::body::to_bytes(body).await?;
+            Ok((Body::from(bytes.clone()), Some(bytes)))
+        }
+        _ => Ok((body, None)),
+    }
+}
+
+/// How a client spaces its retries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Backoff {
+    /// Retries follow each other within a few milliseconds
+    Immediate,
+    /// Retries are spaced evenly
+    Constant,
+    /// Every wait is at least 1.5 times longer than the one before
+    Exponential,
+    /// Waits neither stay level nor grow steadily
+    Irregular,
+}
+
+/// The backoff of a chain of retries, and whether its waits are randomized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BackoffPattern {
+    pub backoff: Backoff,
+    pub jitter: bool,
+}
+
+impl fmt::Display for BackoffPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.backoff {
+            Backoff::Immediate => "immediate",
+            Backoff::Constant => "constant",
+            Backoff::Exponential => "exponential",
+            Backoff::Irregular => "irregular",
+        };
+        if self.jitter {
+            write!(f, "{} with jitter", name)
+        } else {
+            f.write_str(name)
+        }
+    }
+}
+
+/// Classify the waits between the attempts of one request; `None` until there are two
+pub fn classify(intervals_ms: &[f64]) -> Option<BackoffPattern> {
+    if intervals_ms.len() < 2 {
+        return None;
+    }
+    let mean = intervals_ms.iter().sum::<f64>() / intervals_ms.len() as f64;
+    if mean < 10.0 {
+        return Some(BackoffPattern {
+            backoff: Backoff::Immediate,
+            jitter: false,
+        });
+    }
+    let ratios: Vec<f64> = intervals_ms
+        .windows(2)
+        .map(|pair| pair[1] / pair[0].max(1.0))
+        .collect();
+    // How far values stray from their mean, relative to it
+    let spread = |values: &[f64]| {
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        values
+            .iter()
+            .map(|value| (value - mean).abs())
+            .fold(0.0, f64::max)
+            / mean.max(f64::EPSILON)
+    };
+    let pattern = if ratios.iter().all(|&ratio| ratio >= 1.5) {
+        BackoffPattern {
+            backoff: Backoff::Exponential,
+            jitter: ratios.len() > 1 && spread(&ratios) > 0.1,
+        }
+    } else if ratios.iter().all(|&ratio| (0.5..=1.5).contains(&ratio)) {
+        BackoffPattern {
+            backoff: Backoff::Constant,
+            jitter: spread(intervals_ms) > 0.1,
+        }
+    } else {
+        BackoffPattern {
+            backoff: Backoff::Irregular,
+            jitter: false,
+        }
+    };
+    Some(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attempts_within_window() {
+        let tracker = RetryTracker::new(
+            HeaderName::from_static("idempotency-key"),
+            Duration::from_secs(5),
+        );
+        let start = Instant::now();
+        let attempt = |key: &str, secs: f64| {
+            tracker.observe(
+                RetryKey::Idempotency(key.to_string()),
+                None,
+                start + Duration::from_secs_f64(secs),
+            )
+        };
+        assert_eq!(attempt("a", 0.0).number, 1);
+        let retry = attempt("a", 0.25);
+        assert_eq!((retry.number, retry.interval_ms), (2, Some(250.0)));
+        assert_eq!(attempt("b", 0.5).number, 1);
+        assert_eq!(attempt("a", 4.0).number, 3);
+        // Too long after the previous attempt to be a retry
+        assert_eq!(attempt("a", 10.0).number, 1);
+    }
+
+    #[test]
+    fn test_fingerprints_retry_after_failures() {
+        let tracker = RetryTracker::default();
+        let start = Instant::now();
+        let attempt = |secs: u64| {
+            let key = RetryKey::Fingerprint("10.0.0.1 GET /orders".to_string());
+            tracker.observe(key, None, start + Duration::from_secs(secs))
+        };
+        let first = attempt(0);
+        tracker.finish(&first, false);
+        // The same request again after a success is a new request
+        let second = attempt(1);
+        assert_eq!(second.number, 1);
+        tracker.finish(&second, true);
+        let retry = attempt(2);
+        assert_eq!((retry.number, retry.interval_ms), (2, Some(1000.0)));
+        // An earlier attempt finishing late doesn't overwrite the latest one
+        tracker.finish(&second, false);
+        tracker.finish(&retry, true);
+        assert_eq!(attempt(3).number, 3);
+    }
+
+    #[test]
+    fn test_unfinished_attempts_count_as_failed() {
+        let tracker = Arc::new(RetryTracker::default());
+        let start = Instant::now();
+        let attempt = |secs: u64| {
+            let key = RetryKey::Fingerprint("10.0.0.1 GET /slow".to_string());
+            tracker.observe(key, None, start + Duration::from_secs(secs))
+        };
+        // A request still in flight isn't failed, so an identical one is a new request
+        let first = attempt(0);
+        let guard = tracker.guard(first);
+        assert_eq!(attempt(1).number, 1);
+        drop(guard);
+        // The client timed out, so the handler was dropped before the attempt finished
+        let timed_out = attempt(2);
+        drop(tracker.guard(timed_out));
+        assert_eq!(attempt(3).number, 2);
+        // A finished attempt keeps its outcome when its guard is dropped
+        let answered = attempt(30);
+        let guard = tracker.guard(answered.clone());
+        tracker.finish(&answered, false);
+        drop(guard);
+        assert_eq!(attempt(31).number, 1);
+    }
+
+    #[test]
+    fn test_forgets_oldest_requests_beyond_cap() {
+        let tracker = RetryTracker::new(
+            HeaderName::from_static("idempotency-key"),
+            Duration::from_secs(60),
+        );
+        let start = Instant::now();
+        let attempt =
+            |key: &str| tracker.observe(RetryKey::Idempotency(key.to_string()), None, start);
+        attempt("first");
+        attempt("second");
+        for i in 0..MAX_TRACKED - 2 {
+            attempt(&i.to_string());
+        }
+        // Attempting a request again moves it to the back of the line
+        assert_eq!(attempt("first").number, 2);
+        attempt("new");
+        assert_eq!(tracker.attempts.lock().unwrap().last.len(), MAX_TRACKED);
+        assert_eq!(attempt("second").number, 1);
+        assert_eq!(attempt("first").number, 3);
+        // Requests outside the window are forgotten as new ones arrive
+        tracker.observe(
+            RetryKey::Idempotency("late".to_string()),
+            None,
+            start + Duration::from_secs(61),
+        );
+        let attempts = tracker.attempts.lock().unwrap();
+        assert_eq!((attempts.last.len(), attempts.arrivals.len()), (1, 1));
+    }
+
+    #[test]
+    fn test_retry_key() {
+        let tracker = RetryTracker::default();
+        let uri: Uri = "/orders?page=2".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        let key = |headers: &HeaderMap| tracker.key("10.0.0.1", &Method::POST, &uri, headers);
+        assert_eq!(
+            key(&headers),
+            RetryKey::Fingerprint("10.0.0.1 POST /orders?page=2".to_string())
+        );
+        headers.insert("idempotency-key", "order-42".parse().unwrap());
+        assert_eq!(key(&headers), RetryKey::Idempotency("order-42".to_string()));
+    }
+
+    #[test]
+    fn test_bodies_tell_retries_apart() {
+        let tracker = RetryTracker::default();
+        let start = Instant::now();
+        let key = RetryKey::Fingerprint("10.0.0.1 POST /orders".to_string());
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        // Nothing failed yet, so the body isn't needed up front
+        assert!(!tracker.needs_body(&key, at(0)));
+        let first = tracker.observe(key.clone(), None, at(0));
+        tracker.record_body(&first, b"{\"id\":1}");
+        tracker.finish(&first, true);
+        // A failed attempt with a known body: the next one is compared with it
+        assert!(tracker.needs_body(&key, at(1)));
+        assert!(!tracker.needs_body(&RetryKey::Idempotency("a".to_string()), at(1)));
+        let other = tracker.observe(key.clone(), Some(b"{\"id\":2}"), at(1));
+        assert_eq!(other.number, 1);
+        tracker.finish(&other, true);
+        let retry = tracker.observe(key.clone(), Some(b"{\"id\":2}"), at(2));
+        assert_eq!(retry.number, 2);
+        tracker.finish(&retry, true);
+        // A body that was never read, e.g. of a rate-limited attempt, matches any other
+        let rejected = tracker.observe(key.clone(), None, at(3));
+        assert_eq!(rejected.number, 3);
+        tracker.finish(&rejected, true);
+        assert!(!tracker.needs_body(&key, at(4)));
+        assert_eq!(tracker.observe(key, Some(b"{\"id\":3}"), at(4)).number, 4);
+    }
+
+    #[tokio::test]
+    async fn test_fingerprint_body() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_LENGTH, "5".parse().unwrap());
+        let (body, fingerprint) = fingerprint_body(Body::from("hello"), &headers)
+            .await
+            .unwrap();
+        assert_eq!(fingerprint.as_deref(), Some(&b"hello"[..]));
+        // The handler still gets the whole body
+        assert_eq!(hyper::body::to_bytes(body).await.unwrap(), "hello");
+        // Bodies of unknown length are streamed on
+        let (_, fingerprint) = fingerprint_body(Body::from("hello"), &HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(fingerprint, None);
+        // A body that breaks off fails instead of counting as empty
+        let (sender, body) = Body::channel();
+        sender.abort();
+        assert!(fingerprint_body(body, &headers).await.is_err());
+    }
+
+    #[test]
+    fn test_classify_backoff() {
+        let pattern = |intervals: &[f64]| classify(intervals).unwrap().to_string();
+        assert_eq!(classify(&[100.0]), None);
+        assert_eq!(pattern(&[1.0, 2.0, 1.0]), "immediate");
+        assert_eq!(pattern(&[100.0, 200.0, 400.0, 800.0]), "exponential");
+        assert_eq!(
+            pattern(&[100.0, 260.0, 420.0, 1300.0]),
+            "exponential with jitter"
+        );
+        assert_eq!(pattern(&[500.0, 500.0, 500.0]), "constant");
+        assert_eq!(pattern(&[500.0, 400.0, 600.0]), "constant with jitter");
+        assert_eq!(pattern(&[100.0, 1000.0, 50.0]), "irregular");
+    }
+}
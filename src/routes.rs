@@ -1,21 +1,42 @@
 use crate::OutputFormat;
-use crate::state::{AppEvent, AppState, RequestLog};
+use crate::admin::{ADMIN_PREFIX, admin_handler};
+use crate::compression::compress;
+use crate::concurrency::{QueueOverflow, RefusedBody, WorkerBody};
+use crate::connections::client_identity;
+use crate::controls::Pause;
+use crate::formats::{content_type, negotiate, render};
+use crate::grpc::{GrpcCode, grpc_error, grpc_handler, is_grpc_request};
+use crate::listener::PeerAddr;
+use crate::load::{LoadCurve, LoadMetric};
+use crate::openapi::openapi_handler;
+use crate::payload::CountingBody;
+use crate::proxy::proxy_handler;
+use crate::ratelimit::Decision;
+use crate::replay::replay_handler;
+use crate::retries::fingerprint_body;
+use crate::sequence::Action;
+use crate::state::{AppState, Outcome, RequestLog};
+use crate::templates::{ResponseTemplate, TemplateRequest};
+use crate::throttle::ThrottledBody;
 use anyhow::anyhow;
 use axum::{
-    extract::{OriginalUri, State},
-    http::StatusCode,
+    body::{Body, BoxBody, Full, boxed},
+    extract::{ConnectInfo, OriginalUri, State},
+    http::{HeaderMap, HeaderValue, Request, StatusCode, header},
     response::Response,
 };
 use serde_json::json;
 
 use rand::Rng;
+use std::sync::atomic::Ordering;
 use std::time::Duration;
-use tokio::time::sleep;
 
 #[derive(Debug, Clone)]
 pub(crate) struct DelayConfig {
     min: u64,
     max: u64,
+    /// How the delay grows with server load
+    load_curve: Option<LoadCurve>,
 }
 
 impl DelayConfig {
@@ -34,7 +55,11 @@ impl DelayConfig {
             if min >= max {
                 return Err(anyhow!("Minimum delay must be less than maximum delay"));
             }
-            Ok(Self { min, max })
+            Ok(Self {
+                min,
+                max,
+                load_curve: None,
+            })
         } else {
             let delay = delay_str
                 .parse::<u64>()
@@ -42,26 +67,441 @@ impl DelayConfig {
             Ok(Self {
                 min: delay,
                 max: delay,
+                load_curve: None,
             })
         }
     }
 
-    pub fn get_delay(&self) -> u64 {
-        if self.min == self.max {
+    /// Grow the delay with load along the given curve
+    pub fn with_load_curve(mut self, load_curve: Option<LoadCurve>) -> Self {
+        self.load_curve = load_curve;
+        self
+    }
+
+    /// The (min, max) delay in milliseconds
+    pub fn range(&self) -> (u64, u64) {
+        (self.min, self.max)
+    }
+
+    /// The same delay model with a different range
+    pub fn with_range(&self, min: u64, max: u64) -> Self {
+        Self {
+            min,
+            max,
+            load_curve: self.load_curve.clone(),
+        }
+    }
+
+    /// The delay of a request that arrived under `load`, measured by `metric`
+    pub fn get_delay(&self, rng: &mut impl Rng, load: f64, metric: LoadMetric) -> u64 {
+        let delay = if self.min == self.max {
             self.min
         } else {
-            rand::rng().random_range(self.min..=self.max)
+            rng.random_range(self.min..=self.max)
+        };
+        self.apply_load(delay, load, metric)
+    }
+
+    /// Add the load-dependent latency to a delay
+    pub fn apply_load(&self, delay: u64, load: f64, metric: LoadMetric) -> u64 {
+        match &self.load_curve {
+            Some(curve) => curve.latency(delay as f64, load, metric).round() as u64,
+            None => delay,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ErrorConfig {
+    rate: f64,
+    status: u16,
+    /// Load above which every request fails
+    overload_threshold: Option<f64>,
+}
+
+impl ErrorConfig {
+    /// Fail `rate` percent of requests with the given HTTP status
+    pub fn new(rate: f64, status: u16) -> anyhow::Result<Self> {
+        if !(0.0..=100.0).contains(&rate) {
+            return Err(anyhow!("Error rate must be between 0 and 100"));
+        }
+        if !(400..=599).contains(&status) {
+            return Err(anyhow!("Error status must be between 400 and 599"));
+        }
+        Ok(Self {
+            rate,
+            status,
+            overload_threshold: None,
+        })
+    }
+
+    /// Fail every request that arrives with a load above `threshold`
+    pub fn with_overload(mut self, threshold: Option<f64>) -> Self {
+        self.overload_threshold = threshold;
+        self
+    }
+
+    /// The error status of a request that arrived with a load above the threshold
+    pub fn overload(&self, load: f64) -> Option<u16> {
+        self.overload_threshold
+            .is_some_and(|threshold| load > threshold)
+            .then_some(self.status)
+    }
+
+    /// The injected error status of a request that arrived under `load`, if any
+    pub fn get_error(&self, rng: &mut impl Rng, load: f64) -> Option<u16> {
+        if let Some(status) = self.overload(load) {
+            return Some(status);
+        }
+        if self.rate > 0.0 && rng.random::<f64>() * 100.0 < self.rate {
+            Some(self.status)
+        } else {
+            None
         }
     }
 }
 
 /// A fallback handler that catches all requests
 pub async fn request_handler(
-    State(state): State<AppState>,
+    State(mut state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<PeerAddr>,
     uri: OriginalUri,
     method: axum::http::Method,
-) -> Response<String> {
-    let start = std::time::Instant::now();
+    headers: HeaderMap,
+    request: Request<Body>,
+) -> Response {
+    // During an outage, close connections that were opened before it began
+    if state.in_outage() {
+        return Response::new(boxed(RefusedBody));
+    }
+    if uri.0.path().starts_with(ADMIN_PREFIX) {
+        return admin_handler(&state, &method, &uri.0);
+    }
+    let peer_ip = peer.0.map(|addr| addr.ip());
+    state.context.client = client_identity(state.client_header.as_ref(), &headers, peer_ip);
+    let _request = state.connections.request(&state.context.client);
+    // Count retries before rate limiting, so retries of rejected requests are seen too.
+    // Bodies are only buffered up front to compare them with a failed attempt of the same
+    // request, and never when they are throttled or streamed to an upstream; otherwise the
+    // handler remembers the body once it has read it.
+    let (download, upload) = state.throttle.for_path(uri.0.path());
+    let retry_key = state
+        .retries
+        .key(&state.context.client, &method, &uri.0, &headers);
+    let mut body = request.into_body();
+    let mut fingerprint = None;
+    if upload.is_none()
+        && state.upstream.is_none()
+        && state.retries.needs_body(&retry_key, state.clock.now())
+    {
+        match fingerprint_body(body, &headers).await {
+            Ok((buffered, bytes)) => (body, fingerprint) = (buffered, bytes),
+            Err(err) => return body_error_response(&state, &uri, &method, err).await,
+        }
+    }
+    let attempt = state
+        .retries
+        .observe(retry_key, fingerprint.as_deref(), state.clock.now());
+    let _attempt = state.retries.guard(attempt.clone());
+    state.context.attempt = Some(attempt);
+    if let Some(format) = state.controls.format() {
+        state.output_format = format;
+    }
+    if state.controls.pause() == Pause::Unavailable {
+        return paused_response(&state, &uri, &method, &headers).await;
+    }
+    state.rng = state.seeder.next_rng();
+    // Templates and payloads produce their own bodies, so only negotiate the built-in one
+    if state.template.is_none() && state.payload.is_none() {
+        let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok());
+        state.output_format = negotiate(accept, state.output_format);
+    }
+    let accept_encoding = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    let (compression, compression_stats) = (state.compression, state.compression_stats.clone());
+    let counter = state.bytes_sent.clone();
+    let clock = state.clock.clone();
+    let load = state.load.clone();
+    load.arrive(state.clock.now());
+    let rate_limit = state.rate_limiter.as_ref().map(|limiter| {
+        let key = limiter.key(peer_ip, uri.0.path(), &headers);
+        limiter.check(&key, state.clock.now())
+    });
+    if let Some((waited, early)) = rate_limit.as_ref().and_then(|decision| decision.backoff) {
+        state.context.backoff_ms = Some(waited.as_secs_f64() * 1000.0);
+        state.context.early_retry = early;
+    }
+    // Requests within the rate limit wait for a worker when concurrency is limited
+    let mut worker = None;
+    if rate_limit.as_ref().is_none_or(|decision| decision.allowed)
+        && let Some(limit) = state.concurrency.clone()
+    {
+        let queued_at = state.clock.now();
+        match limit.acquire().await {
+            Some(acquired) => worker = Some(acquired),
+            None => {
+                return queue_full_response(&state, &uri, &method, &headers, limit.overflow).await;
+            }
+        }
+        state.context.queue_ms = state.elapsed_ms(queued_at);
+    }
+    // The load a request sees excludes itself
+    state.context.load = load.current(state.clock.now());
+    let _in_flight = load.start();
+    if rate_limit.as_ref().is_none_or(|decision| decision.allowed) {
+        state.context.sequence = state
+            .sequences
+            .next(&method, uri.0.path(), peer_ip, &headers);
+        if let Some(action @ (Action::Timeout | Action::Drop)) =
+            state.context.sequence.as_ref().and_then(|step| step.action)
+        {
+            unanswered_response(&state, &uri, &method).await;
+            if action == Action::Timeout {
+                // Hold the worker until the client gives up
+                std::future::pending::<()>().await;
+            }
+            return Response::new(boxed(RefusedBody));
+        }
+    }
+    let body = match upload {
+        Some(throttle) => boxed(ThrottledBody::new(body, throttle, clock.clone())),
+        None => boxed(body),
+    };
+    let response = match &rate_limit {
+        Some(decision) if !decision.allowed => {
+            rate_limited_response(&state, &uri, &method, &headers, decision).await
+        }
+        _ => handle_request(state, uri, method.clone(), headers, body).await,
+    };
+    let mut response = compress(
+        response,
+        compression,
+        &method,
+        accept_encoding.as_deref(),
+        &compression_stats,
+    )
+    .map(|body| match download {
+        Some(throttle) => boxed(CountingBody::new(
+            ThrottledBody::new(body, throttle, clock),
+            counter,
+        )),
+        None => boxed(CountingBody::new(body, counter)),
+    });
+    if let Some(decision) = &rate_limit {
+        decision.apply_headers(response.headers_mut());
+    }
+    match worker {
+        Some(worker) => response.map(|body| boxed(WorkerBody::new(body, worker))),
+        None => response,
+    }
+}
+
+/// Reply 400 when the request body can't be read, e.g. because the client sent a broken
+/// chunked body or went away while sending it
+async fn body_error_response(
+    state: &AppState,
+    uri: &OriginalUri,
+    method: &axum::http::Method,
+    err: impl std::fmt::Display,
+) -> Response {
+    let log = RequestLog::new(
+        state,
+        method.as_str(),
+        uri.0.path(),
+        StatusCode::BAD_REQUEST.as_u16(),
+    );
+    state.record(log).await;
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .header(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"))
+        .body(boxed(format!("Failed to read request body: {}", err)))
+        .unwrap()
+}
+
+/// Log a request that a sequence step leaves without a response
+async fn unanswered_response(state: &AppState, uri: &OriginalUri, method: &axum::http::Method) {
+    let log = RequestLog {
+        outcome: Outcome::InjectedError,
+        ..RequestLog::new(state, method.as_str(), uri.0.path(), 0)
+    };
+    state.record(log).await;
+}
+
+/// Reply 503 while the server is paused from the TUI (`UNAVAILABLE` for gRPC)
+async fn paused_response(
+    state: &AppState,
+    uri: &OriginalUri,
+    method: &axum::http::Method,
+    headers: &HeaderMap,
+) -> Response {
+    let grpc = is_grpc_request(headers);
+    let log = RequestLog {
+        grpc_status: grpc.then_some(GrpcCode::Unavailable as u8),
+        outcome: Outcome::Paused,
+        ..RequestLog::new(
+            state,
+            method.as_str(),
+            uri.0.path(),
+            if grpc {
+                200
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE.as_u16()
+            },
+        )
+    };
+    state.record(log).await;
+
+    if grpc {
+        return grpc_error(&state.clock, GrpcCode::Unavailable, "Server paused");
+    }
+
+    let value = json!({
+        "status": "error",
+        "code": StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+        "error": "server paused"
+    });
+    Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .header("content-type", content_type(state.output_format))
+        .body(boxed(Full::from(render(
+            state.output_format,
+            &value,
+            "Server paused",
+        ))))
+        .unwrap()
+}
+
+/// Turn away a request because every worker is busy and the queue is full: with a 503
+/// (`UNAVAILABLE` for gRPC), or by closing the connection
+async fn queue_full_response(
+    state: &AppState,
+    uri: &OriginalUri,
+    method: &axum::http::Method,
+    headers: &HeaderMap,
+    overflow: QueueOverflow,
+) -> Response {
+    let refused = overflow == QueueOverflow::Refuse;
+    let grpc = !refused && is_grpc_request(headers);
+    let status = match (refused, grpc) {
+        (true, _) => 0,
+        (false, true) => 200,
+        (false, false) => StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+    };
+    let log = RequestLog {
+        grpc_status: grpc.then_some(GrpcCode::Unavailable as u8),
+        outcome: Outcome::QueueFull,
+        ..RequestLog::new(state, method.as_str(), uri.0.path(), status)
+    };
+    state.record(log).await;
+
+    if refused {
+        return Response::new(boxed(RefusedBody));
+    }
+    if grpc {
+        return grpc_error(
+            &state.clock,
+            GrpcCode::Unavailable,
+            "Server busy: request queue full",
+        );
+    }
+    let value = json!({
+        "status": "error",
+        "code": StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+        "error": "server busy: request queue full"
+    });
+    Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .header("content-type", content_type(state.output_format))
+        .body(boxed(Full::from(render(
+            state.output_format,
+            &value,
+            "Server busy: request queue full",
+        ))))
+        .unwrap()
+}
+
+/// Reject a request over the rate limit with a 429 (`RESOURCE_EXHAUSTED` for gRPC)
+async fn rate_limited_response(
+    state: &AppState,
+    uri: &OriginalUri,
+    method: &axum::http::Method,
+    headers: &HeaderMap,
+    decision: &Decision,
+) -> Response {
+    let grpc = is_grpc_request(headers);
+    let retry_after_ms = decision.retry_after.as_millis() as u64;
+    let log = RequestLog {
+        grpc_status: grpc.then_some(GrpcCode::ResourceExhausted as u8),
+        outcome: Outcome::RateLimited,
+        ..RequestLog::new(
+            state,
+            method.as_str(),
+            uri.0.path(),
+            if grpc {
+                200
+            } else {
+                StatusCode::TOO_MANY_REQUESTS.as_u16()
+            },
+        )
+    };
+    state.record(log).await;
+
+    if grpc {
+        return grpc_error(
+            &state.clock,
+            GrpcCode::ResourceExhausted,
+            "Rate limit exceeded",
+        );
+    }
+    let value = json!({
+        "status": "error",
+        "code": StatusCode::TOO_MANY_REQUESTS.as_u16(),
+        "error": "rate limit exceeded",
+        "retry_after_ms": retry_after_ms
+    });
+    let text = format!("Rate limit exceeded, retry after {}ms", retry_after_ms);
+    Response::builder()
+        .status(StatusCode::TOO_MANY_REQUESTS)
+        .header("content-type", content_type(state.output_format))
+        .body(boxed(Full::from(render(
+            state.output_format,
+            &value,
+            &text,
+        ))))
+        .unwrap()
+}
+
+async fn handle_request(
+    state: AppState,
+    uri: OriginalUri,
+    method: axum::http::Method,
+    headers: HeaderMap,
+    body: BoxBody,
+) -> Response {
+    if let Some(upstream) = state.upstream.clone() {
+        return proxy_handler(&state, &upstream, method, &uri.0, headers, body).await;
+    }
+
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(err) => return body_error_response(&state, &uri, &method, err).await,
+    };
+    if let Some(attempt) = &state.context.attempt {
+        state.retries.record_body(attempt, &body);
+    }
+    if let Some(replay) = state.replay.clone() {
+        return replay_handler(&state, &replay, &method, &uri.0, &body).await;
+    }
+    if let Some(api) = state.openapi.clone() {
+        return openapi_handler(&state, &api, &method, &uri.0, &body).await;
+    }
+    if is_grpc_request(&headers) {
+        return grpc_handler(&state, uri.0.path(), method.as_str(), &headers, &body).await;
+    }
+
+    let start = state.clock.now();
     let now = state.now_timestamp();
     state.increment_requests();
 
@@ -69,64 +509,156 @@ pub async fn request_handler(
     let delay_ms = state.get_delay();
     if delay_ms > 0 {
         // Simulate delay
-        sleep(Duration::from_millis(delay_ms)).await;
+        state.clock.sleep(Duration::from_millis(delay_ms)).await;
     }
 
+    // Decide whether to inject an error
+    let status = match state.get_error() {
+        Some(status) => StatusCode::from_u16(status).unwrap_or(StatusCode::SERVICE_UNAVAILABLE),
+        None => StatusCode::OK,
+    };
+
+    // Successful responses come from the template or payload, if configured
+    let response = match (&state.template, &state.payload) {
+        _ if status != StatusCode::OK => build_response(
+            &state,
+            status,
+            uri.0.path(),
+            method.as_str(),
+            now,
+            state.elapsed_ms(start),
+            delay_ms,
+        ),
+        (Some(template), _) => render_template(
+            template,
+            &TemplateRequest {
+                method: method.as_str(),
+                path: uri.0.path(),
+                query: uri.0.query(),
+                headers: &headers,
+                body: &body,
+                counter: state.total_requests.load(Ordering::Relaxed),
+                listener: &state.listener,
+                rng: &state.rng,
+                now: state.clock.wall(),
+            },
+            state.output_format,
+        ),
+        (None, Some(payload)) => payload.response(&state.rng),
+        (None, None) => build_response(
+            &state,
+            status,
+            uri.0.path(),
+            method.as_str(),
+            now,
+            state.elapsed_ms(start),
+            delay_ms,
+        ),
+    };
+
     // Build a simple log record
-    let elapsed = start.elapsed();
-    let duration_ms = elapsed.as_secs_f64() * 1000.0;
+    let duration_ms = state.elapsed_ms(start);
 
     let log = RequestLog {
-        path: uri.0.path().to_string(),
-        method: method.to_string(),
-        status: 200,
+        outcome: if status != StatusCode::OK {
+            Outcome::InjectedError
+        } else {
+            Outcome::Served
+        },
         timestamp: now,
         duration_ms,
+        ..RequestLog::new(
+            &state,
+            method.as_str(),
+            uri.0.path(),
+            response.status().as_u16(),
+        )
     };
 
     // Send an event to the TUI
-    let _ = state.tx.send(AppEvent::RequestReceived(log)).await;
+    state.record(log).await;
 
-    let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+    response
+}
 
-    // Format response based on output format
-    let response_body = match state.output_format {
-        OutputFormat::Json => json!({
-            "status": "success",
-            "request": {
-                "path": uri.0.path(),
-                "method": method.to_string(),
-                "timestamp": now
-            },
-            "timing": {
-                "processing_time_ms": elapsed_ms,
-                "simulated_delay_ms": delay_ms
-            }
-        })
-        .to_string(),
-        OutputFormat::Text => format!(
+/// Render a templated response; template failures become a 500
+fn render_template(
+    template: &ResponseTemplate,
+    request: &TemplateRequest,
+    format: OutputFormat,
+) -> Response {
+    match template.render(request) {
+        Ok((status, headers, body)) => {
+            let mut response = Response::new(boxed(body));
+            *response.status_mut() = status;
+            response.headers_mut().insert(
+                "content-type",
+                HeaderValue::from_static(content_type(format)),
+            );
+            response.headers_mut().extend(headers);
+            response
+        }
+        Err(err) => Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .header("content-type", "text/plain")
+            .body(boxed(format!("Response template failed: {:#}", err)))
+            .unwrap(),
+    }
+}
+
+/// Build the synthesized response (success or injected error) in the request's format
+pub(crate) fn build_response(
+    state: &AppState,
+    status: StatusCode,
+    path: &str,
+    method: &str,
+    now: i64,
+    elapsed_ms: f64,
+    delay_ms: u64,
+) -> Response {
+    let value = json!({
+        "status": if status.is_success() { "success" } else { "error" },
+        "code": status.as_u16(),
+        "request": {
+            "path": path,
+            "method": method,
+            "timestamp": now
+        },
+        "timing": {
+            "processing_time_ms": elapsed_ms,
+            "simulated_delay_ms": delay_ms
+        }
+    });
+    let text = if status.is_success() {
+        format!(
             "Request processed in {}ms (simulated delay: {}ms)",
             elapsed_ms, delay_ms
-        ),
+        )
+    } else {
+        format!(
+            "Injected error {} after {}ms (simulated delay: {}ms)",
+            status.as_u16(),
+            elapsed_ms,
+            delay_ms
+        )
     };
 
-    // Return 200 OK
+    // Format response based on output format
     Response::builder()
-        .status(StatusCode::OK)
-        .header(
-            "content-type",
-            match state.output_format {
-                OutputFormat::Json => "application/json",
-                OutputFormat::Text => "text/plain",
-            },
-        )
-        .body(response_body)
+        .status(status)
+        .header("content-type", content_type(state.output_format))
+        .body(boxed(Full::from(render(
+            state.output_format,
+            &value,
+            &text,
+        ))))
         .unwrap()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::state::AppEvent;
 
     #[test]
     fn test_parse_single_delay() {
@@ -156,7 +688,73 @@ mod tests {
     #[test]
     fn test_get_delay_fixed() {
         let config = DelayConfig::parse("100").unwrap();
-        let delay = config.get_delay();
+        let delay = config.get_delay(&mut rand::rng(), 0.0, LoadMetric::InFlight);
         assert_eq!(delay, 100);
     }
+
+    #[test]
+    fn test_get_delay_grows_with_load() {
+        let config = DelayConfig::parse("10")
+            .unwrap()
+            .with_load_curve(Some("linear:2".parse().unwrap()));
+        assert_eq!(
+            config.get_delay(&mut rand::rng(), 0.0, LoadMetric::InFlight),
+            10
+        );
+        assert_eq!(
+            config.get_delay(&mut rand::rng(), 20.0, LoadMetric::InFlight),
+            50
+        );
+    }
+
+    #[test]
+    fn test_error_config_bounds() {
+        assert!(ErrorConfig::new(5.0, 503).is_ok());
+        assert!(ErrorConfig::new(101.0, 503).is_err());
+        assert!(ErrorConfig::new(5.0, 200).is_err());
+    }
+
+    #[test]
+    fn test_get_error_always_and_never() {
+        assert_eq!(
+            ErrorConfig::new(100.0, 503)
+                .unwrap()
+                .get_error(&mut rand::rng(), 0.0),
+            Some(503)
+        );
+        assert_eq!(
+            ErrorConfig::new(0.0, 503)
+                .unwrap()
+                .get_error(&mut rand::rng(), 0.0),
+            None
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_delay_runs_on_paused_time() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let state = AppState::new(tx, "750", OutputFormat::Json).unwrap();
+        let start = tokio::time::Instant::now();
+        let response = handle_request(
+            state,
+            OriginalUri("/slow".parse().unwrap()),
+            axum::http::Method::GET,
+            HeaderMap::new(),
+            boxed(Body::empty()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(start.elapsed(), Duration::from_millis(750));
+        let AppEvent::RequestReceived(log) = rx.recv().await.unwrap();
+        assert_eq!(log.duration_ms, 750.0);
+    }
+
+    #[test]
+    fn test_get_error_above_overload_threshold() {
+        let config = ErrorConfig::new(0.0, 503)
+            .unwrap()
+            .with_overload(Some(50.0));
+        assert_eq!(config.get_error(&mut rand::rng(), 50.0), None);
+        assert_eq!(config.get_error(&mut rand::rng(), 51.0), Some(503));
+    }
 }
@@ -0,0 +1,600 @@
+use crate::clock::{self, Clock};
+use crate::routes::DelayConfig;
+use crate::util::parse_duration;
+use anyhow::{Context, anyhow};
+use rand::Rng;
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A timeline of phases, loaded with `--scenario`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioConfig {
+    phases: Vec<PhaseConfig>,
+    /// Start over after the last phase instead of returning to the configured behavior
+    #[serde(default)]
+    repeat: bool,
+}
+
+/// One phase of a scenario; unset fields use each listener's own settings
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PhaseConfig {
+    name: String,
+    /// e.g. `30s` or `2m`
+    duration: String,
+    /// Move linearly from the previous phase's conditions over this long
+    #[serde(default)]
+    ramp: Option<String>,
+    /// As for `--delay`
+    #[serde(default)]
+    delay: Option<String>,
+    /// Percentage of requests delayed by `spike_delay` instead
+    #[serde(default)]
+    spike_rate: Option<f64>,
+    #[serde(default)]
+    spike_delay: Option<String>,
+    #[serde(default)]
+    error_rate: Option<f64>,
+    #[serde(default)]
+    error_status: Option<u16>,
+    /// Refuse connections for the whole phase
+    #[serde(default)]
+    outage: bool,
+}
+
+/// The conditions requests are served under at one point of the timeline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conditions {
+    delay: (f64, f64),
+    spike_rate: f64,
+    spike_delay: (f64, f64),
+    error_rate: f64,
+    error_status: u16,
+    pub outage: bool,
+}
+
+fn lerp(from: f64, to: f64, progress: f64) -> f64 {
+    from + (to - from) * progress
+}
+
+fn sample(rng: &mut impl Rng, range: (f64, f64)) -> u64 {
+    if range.0 >= range.1 {
+        range.0.round() as u64
+    } else {
+        rng.random_range(range.0..=range.1).round() as u64
+    }
+}
+
+impl Conditions {
+    /// The conditions outside a scenario, from the command-line options
+    pub fn base(delay: &DelayConfig, error_rate: f64, error_status: u16) -> Self {
+        let (min, max) = delay.range();
+        Self {
+            delay: (min as f64, max as f64),
+            spike_rate: 0.0,
+            spike_delay: (0.0, 0.0),
+            error_rate,
+            error_status,
+            outage: false,
+        }
+    }
+
+    /// These conditions with the fields a phase sets replaced
+    fn with(&self, phase: &PhaseConditions) -> Conditions {
+        Conditions {
+            delay: phase.delay.unwrap_or(self.delay),
+            spike_rate: phase.spike_rate,
+            spike_delay: phase.spike_delay,
+            error_rate: phase.error_rate.unwrap_or(self.error_rate),
+            error_status: phase.error_status.unwrap_or(self.error_status),
+            outage: phase.outage,
+        }
+    }
+
+    /// Conditions `progress` (0 to 1) of the way from `self` to `to`
+    fn ramp_to(&self, to: &Conditions, progress: f64) -> Conditions {
+        let range = |from: (f64, f64), to: (f64, f64)| {
+            (lerp(from.0, to.0, progress), lerp(from.1, to.1, progress))
+        };
+        Conditions {
+            delay: range(self.delay, to.delay),
+            spike_rate: lerp(self.spike_rate, to.spike_rate, progress),
+            spike_delay: range(self.spike_delay, to.spike_delay),
+            error_rate: lerp(self.error_rate, to.error_rate, progress),
+            error_status: to.error_status,
+            outage: to.outage,
+        }
+    }
+
+    /// The delay of a request in milliseconds, before any load-dependent latency
+    pub fn delay(&self, rng: &mut impl Rng) -> u64 {
+        if self.spike_rate > 0.0 && rng.random::<f64>() * 100.0 < self.spike_rate {
+            sample(rng, self.spike_delay)
+        } else {
+            sample(rng, self.delay)
+        }
+    }
+
+    /// The injected error status of a request, if any
+    pub fn error(&self, rng: &mut impl Rng) -> Option<u16> {
+        if self.error_rate > 0.0 && rng.random::<f64>() * 100.0 < self.error_rate {
+            Some(self.error_status)
+        } else {
+            None
+        }
+    }
+}
+
+/// e.g. `delay 100 ms, 1% spikes of 1000-3000 ms, 50% errors (503)`
+impl fmt::Display for Conditions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.outage {
+            return f.write_str("outage");
+        }
+        let range = |(min, max): (f64, f64)| {
+            if min == max {
+                format!("{:.0} ms", min)
+            } else {
+                format!("{:.0}-{:.0} ms", min, max)
+            }
+        };
+        write!(f, "delay {}", range(self.delay))?;
+        if self.spike_rate > 0.0 {
+            write!(
+                f,
+                ", {}% spikes of {}",
+                self.spike_rate,
+                range(self.spike_delay)
+            )?;
+        }
+        if self.error_rate > 0.0 {
+            write!(f, ", {}% errors ({})", self.error_rate, self.error_status)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a phase changes; unset fields keep the base conditions
+#[derive(Debug, Clone, Copy)]
+struct PhaseConditions {
+    delay: Option<(f64, f64)>,
+    spike_rate: f64,
+    spike_delay: (f64, f64),
+    error_rate: Option<f64>,
+    error_status: Option<u16>,
+    outage: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Phase {
+    name: String,
+    start: Duration,
+    duration: Duration,
+    ramp: Duration,
+    conditions: PhaseConditions,
+}
+
+/// A phase as planned, for reports
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhasePlan<'a> {
+    pub name: &'a str,
+    /// Time from the start of the scenario
+    pub start: Duration,
+    pub duration: Duration,
+    pub ramp: Duration,
+    pub conditions: Conditions,
+}
+
+/// Where the timeline is now
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseStatus<'a> {
+    /// Index of the phase, from 0
+    pub index: usize,
+    pub name: &'a str,
+    /// Time until the next phase starts
+    pub remaining: Duration,
+    pub conditions: Conditions,
+}
+
+/// A timeline of phases changing the delay, errors and availability of the server
+#[derive(Debug)]
+pub struct Scenario {
+    phases: Vec<Phase>,
+    base: Conditions,
+    repeat: bool,
+    total: Duration,
+    clock: Arc<dyn Clock>,
+    started: Instant,
+}
+
+impl Scenario {
+    /// Load a scenario file. `base` are the conditions outside the scenario, and what
+    /// phases fall back to.
+    pub fn load(path: &Path, base: Conditions) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scenario file {}", path.display()))?;
+        Self::parse(&contents, base)
+            .with_context(|| format!("Failed to parse scenario file {}", path.display()))
+    }
+
+    pub fn parse(contents: &str, base: Conditions) -> anyhow::Result<Self> {
+        let config: ScenarioConfig = serde_json::from_str(contents)?;
+        if config.phases.is_empty() {
+            return Err(anyhow!("A scenario needs at least one phase"));
+        }
+        let mut phases = Vec::new();
+        let mut start = Duration::ZERO;
+        for phase in config.phases {
+            let invalid = |field: &str| format!("Invalid {} in phase '{}'", field, phase.name);
+            let range = |delay: &Option<String>, field: &str| {
+                delay
+                    .as_deref()
+                    .map(|delay| {
+                        let (min, max) = DelayConfig::parse(delay)
+                            .with_context(|| invalid(field))?
+                            .range();
+                        anyhow::Ok((min as f64, max as f64))
+                    })
+                    .transpose()
+            };
+            for (field, rate) in [
+                ("error_rate", phase.error_rate),
+                ("spike_rate", phase.spike_rate),
+            ] {
+                if rate.is_some_and(|rate| !(0.0..=100.0).contains(&rate)) {
+                    return Err(anyhow!("{}: must be between 0 and 100", invalid(field)));
+                }
+            }
+            if phase
+                .error_status
+                .is_some_and(|status| !(400..=599).contains(&status))
+            {
+                return Err(anyhow!(
+                    "{}: must be between 400 and 599",
+                    invalid("error_status")
+                ));
+            }
+            let duration = parse_duration(&phase.duration).with_context(|| invalid("duration"))?;
+            if duration.is_zero() {
+                return Err(anyhow!("{}: must be longer than zero", invalid("duration")));
+            }
+            let ramp = phase
+                .ramp
+                .as_deref()
+                .map(parse_duration)
+                .transpose()
+                .with_context(|| invalid("ramp"))?
+                .unwrap_or_default()
+                .min(duration);
+            let conditions = PhaseConditions {
+                delay: range(&phase.delay, "delay")?,
+                spike_rate: phase.spike_rate.unwrap_or(0.0),
+                spike_delay: range(&phase.spike_delay, "spike_delay")?.unwrap_or_default(),
+                error_rate: phase.error_rate,
+                error_status: phase.error_status,
+                outage: phase.outage,
+            };
+            if conditions.spike_rate > 0.0 && phase.spike_delay.is_none() {
+                return Err(anyhow!(
+                    "Phase '{}' sets spike_rate without spike_delay",
+                    phase.name
+                ));
+            }
+            phases.push(Phase {
+                name: phase.name,
+                start,
+                duration,
+                ramp,
+                conditions,
+            });
+            start += duration;
+        }
+        let clock = clock::system();
+        Ok(Self {
+            phases,
+            base,
+            repeat: config.repeat,
+            total: start,
+            started: clock.now(),
+            clock,
+        })
+    }
+
+    /// The same timeline, with phases falling back to other base conditions, such as those
+    /// of one listener
+    pub fn with_base(&self, base: Conditions) -> Self {
+        Self {
+            phases: self.phases.clone(),
+            base,
+            repeat: self.repeat,
+            total: self.total,
+            clock: self.clock.clone(),
+            started: self.started,
+        }
+    }
+
+    /// Take the time from the given clock, starting the timeline now
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.started = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// Time since the scenario started
+    pub fn elapsed(&self) -> Duration {
+        self.clock.now().saturating_duration_since(self.started)
+    }
+
+    /// The phase now; `None` once a scenario that doesn't repeat has finished
+    pub fn current(&self) -> Option<PhaseStatus<'_>> {
+        self.at(self.elapsed())
+    }
+
+    /// The phase `elapsed` into the scenario
+    pub fn at(&self, elapsed: Duration) -> Option<PhaseStatus<'_>> {
+        let elapsed = if self.repeat {
+            Duration::from_nanos((elapsed.as_nanos() % self.total.as_nanos()) as u64)
+        } else {
+            elapsed
+        };
+        let index = self
+            .phases
+            .iter()
+            .position(|phase| elapsed < phase.start + phase.duration)?;
+        let phase = &self.phases[index];
+        let into = elapsed - phase.start;
+        let conditions = self.base.with(&phase.conditions);
+        let conditions = if into < phase.ramp {
+            let previous = match index {
+                0 if self.repeat => self
+                    .base
+                    .with(&self.phases[self.phases.len() - 1].conditions),
+                0 => self.base,
+                i => self.base.with(&self.phases[i - 1].conditions),
+            };
+            previous.ramp_to(&conditions, into.as_secs_f64() / phase.ramp.as_secs_f64())
+        } else {
+            conditions
+        };
+        Some(PhaseStatus {
+            index,
+            name: &phase.name,
+            remaining: phase.duration - into,
+            conditions,
+        })
+    }
+
+    pub fn phase_count(&self) -> usize {
+        self.phases.len()
+    }
+
+    /// The phases in order
+    pub fn plan(&self) -> Vec<PhasePlan<'_>> {
+        self.phases
+            .iter()
+            .map(|phase| PhasePlan {
+                name: &phase.name,
+                start: phase.start,
+                duration: phase.duration,
+                ramp: phase.ramp,
+                conditions: self.base.with(&phase.conditions),
+            })
+            .collect()
+    }
+
+    /// Whether the scenario starts over after the last phase
+    pub fn repeats(&self) -> bool {
+        self.repeat
+    }
+
+    /// Phase boundaries in the last `window`, newest first, as (time ago, phase that began).
+    /// The end of a scenario that doesn't repeat is named `end`.
+    pub fn boundaries(&self, elapsed: Duration, window: Duration) -> Vec<(Duration, &str)> {
+        let total = self.total.as_secs_f64();
+        let now = elapsed.as_secs_f64();
+        let from = (now - window.as_secs_f64()).max(0.0);
+        let cycles = if self.repeat {
+            (from / total).floor() as u64..=(now / total).floor() as u64
+        } else {
+            0..=0
+        };
+        let mut boundaries = Vec::new();
+        for cycle in cycles {
+            let offset = cycle as f64 * total;
+            let starts = self
+                .phases
+                .iter()
+                .map(|phase| (phase.start.as_secs_f64(), phase.name.as_str()));
+            let end = (!self.repeat).then_some((total, "end"));
+            for (start, name) in starts.chain(end) {
+                let at = offset + start;
+                if at >= from && at <= now {
+                    boundaries.push((Duration::from_secs_f64(now - at), name));
+                }
+            }
+        }
+        boundaries.sort_by_key(|(ago, _)| *ago);
+        boundaries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    const SCENARIO: &str = r#"{
+        "phases": [
+            {"name": "healthy", "duration": "60s"},
+            {"name": "spikes", "duration": "30s", "ramp": "10s", "delay": "100", "spike_rate": 1, "spike_delay": "1000-3000"},
+            {"name": "errors", "duration": "30s", "error_rate": 50, "error_status": 503},
+            {"name": "outage", "duration": "30s", "outage": true},
+            {"name": "recovery", "duration": "1m", "ramp": "1m"}
+        ]
+    }"#;
+
+    fn base() -> Conditions {
+        Conditions::base(&DelayConfig::parse("0").unwrap(), 0.0, 500)
+    }
+
+    #[test]
+    fn test_phases_and_ramps() {
+        let scenario = Scenario::parse(SCENARIO, base()).unwrap();
+        let at = |secs: f64| scenario.at(Duration::from_secs_f64(secs));
+
+        let healthy = at(10.0).unwrap();
+        assert_eq!((healthy.index, healthy.name), (0, "healthy"));
+        assert_eq!(healthy.remaining, Duration::from_secs(50));
+        assert_eq!(healthy.conditions, base());
+
+        // Halfway through the ramp into the spikes
+        let ramping = at(65.0).unwrap().conditions;
+        assert_eq!(ramping.delay, (50.0, 50.0));
+        assert_eq!(ramping.spike_rate, 0.5);
+        assert_eq!(at(75.0).unwrap().conditions.delay, (100.0, 100.0));
+
+        // Phases without a ramp switch at once, and unset fields use the base
+        let errors = at(90.0).unwrap().conditions;
+        assert_eq!((errors.error_rate, errors.error_status), (50.0, 503));
+        assert_eq!(errors.delay, (0.0, 0.0));
+        assert!(at(125.0).unwrap().conditions.outage);
+        assert!(!at(150.0).unwrap().conditions.outage);
+
+        // Finished
+        assert_eq!(at(210.0), None);
+    }
+
+    #[test]
+    fn test_phases_fall_back_to_listener_conditions() {
+        let scenario = Scenario::parse(SCENARIO, base()).unwrap();
+        let listener = Conditions::base(&DelayConfig::parse("20-40").unwrap(), 5.0, 502);
+        let scenario = scenario.with_base(listener);
+        assert_eq!(
+            scenario.at(Duration::from_secs(10)).unwrap().conditions,
+            listener
+        );
+        // Only the fields a phase sets replace the listener's own
+        let errors = scenario.at(Duration::from_secs(90)).unwrap().conditions;
+        assert_eq!(errors.delay, (20.0, 40.0));
+        assert_eq!((errors.error_rate, errors.error_status), (50.0, 503));
+        let spikes = scenario.at(Duration::from_secs(75)).unwrap().conditions;
+        assert_eq!((spikes.delay, spikes.error_rate), ((100.0, 100.0), 5.0));
+        // Ramps start from the listener's conditions too
+        let ramping = scenario.at(Duration::from_secs(65)).unwrap().conditions;
+        assert_eq!(ramping.delay, (60.0, 70.0));
+    }
+
+    #[test]
+    fn test_repeat_wraps_around() {
+        let scenario = Scenario::parse(
+            r#"{"phases": [{"name": "a", "duration": "10s"}, {"name": "b", "duration": "5s", "error_rate": 100}], "repeat": true}"#,
+            base(),
+        )
+        .unwrap();
+        assert_eq!(scenario.at(Duration::from_secs(42)).unwrap().name, "b");
+        assert_eq!(scenario.at(Duration::from_secs(46)).unwrap().name, "a");
+    }
+
+    #[test]
+    fn test_phase_transitions_follow_clock() {
+        let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+        let scenario = Scenario::parse(SCENARIO, base())
+            .unwrap()
+            .with_clock(clock.clone());
+        assert_eq!(scenario.current().unwrap().name, "healthy");
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(
+            scenario.current().unwrap().remaining,
+            Duration::from_secs(1)
+        );
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(scenario.current().unwrap().name, "spikes");
+        clock.advance(Duration::from_secs(65));
+        assert!(scenario.current().unwrap().conditions.outage);
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(scenario.current(), None);
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let scenario = Scenario::parse(SCENARIO, base()).unwrap();
+        let boundaries = scenario.boundaries(Duration::from_secs(125), Duration::from_secs(60));
+        assert_eq!(
+            boundaries,
+            [
+                (Duration::from_secs(5), "outage"),
+                (Duration::from_secs(35), "errors")
+            ]
+        );
+        let boundaries = scenario.boundaries(Duration::from_secs(220), Duration::from_secs(60));
+        assert_eq!(boundaries, [(Duration::from_secs(10), "end")]);
+    }
+
+    #[test]
+    fn test_plan() {
+        let scenario = Scenario::parse(SCENARIO, base()).unwrap();
+        let plan: Vec<(String, u64, String)> = scenario
+            .plan()
+            .iter()
+            .map(|phase| {
+                let start = phase.start.as_secs();
+                (phase.name.to_string(), start, phase.conditions.to_string())
+            })
+            .collect();
+        assert_eq!(
+            plan,
+            [
+                ("healthy".to_string(), 0, "delay 0 ms".to_string()),
+                (
+                    "spikes".to_string(),
+                    60,
+                    "delay 100 ms, 1% spikes of 1000-3000 ms".to_string()
+                ),
+                (
+                    "errors".to_string(),
+                    90,
+                    "delay 0 ms, 50% errors (503)".to_string()
+                ),
+                ("outage".to_string(), 120, "outage".to_string()),
+                ("recovery".to_string(), 150, "delay 0 ms".to_string()),
+            ]
+        );
+        assert!(!scenario.repeats());
+    }
+
+    #[test]
+    fn test_invalid_scenarios() {
+        assert!(Scenario::parse(r#"{"phases": []}"#, base()).is_err());
+        assert!(
+            Scenario::parse(
+                r#"{"phases": [{"name": "a", "duration": "10s", "error_rate": 150}]}"#,
+                base()
+            )
+            .is_err()
+        );
+        assert!(
+            Scenario::parse(
+                r#"{"phases": [{"name": "a", "duration": "10s", "spike_rate": 5}]}"#,
+                base()
+            )
+            .is_err()
+        );
+        assert!(
+            Scenario::parse(
+                r#"{"phases": [{"name": "a", "duration": "10s", "spike_rate": 150, "spike_delay": "1000"}]}"#,
+                base()
+            )
+            .is_err()
+        );
+        assert!(
+            Scenario::parse(r#"{"phases": [{"name": "a", "duration": "soon"}]}"#, base()).is_err()
+        );
+        assert!(
+            Scenario::parse(r#"{"phases": [{"name": "a", "duration": "0s"}]}"#, base()).is_err()
+        );
+    }
+}
@@ -0,0 +1,366 @@
+use crate::config::SequenceConfig;
+use crate::ratelimit::RateLimitKey;
+use anyhow::{Context, anyhow};
+use axum::http::{HeaderMap, Method};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+/// Keys counted before the least recently used ones are forgotten
+const MAX_KEYS: usize = 10_000;
+
+/// What the request at one step of a sequence gets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Served normally, without an injected error
+    Ok,
+    /// Fails with the given status
+    Status(u16),
+    /// Never answered, so the client has to time out
+    Timeout,
+    /// The connection is closed without a response
+    Drop,
+}
+
+impl std::str::FromStr for Action {
+    type Err = anyhow::Error;
+
+    /// Parse `ok` (or `200`), an error status, `timeout` or `drop`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ok" | "200" => Ok(Self::Ok),
+            "timeout" => Ok(Self::Timeout),
+            "drop" => Ok(Self::Drop),
+            status => match status.parse() {
+                Ok(status) if (400..=599).contains(&status) => Ok(Self::Status(status)),
+                _ => Err(anyhow!(
+                    "Invalid step '{}'. Valid values: ok, timeout, drop or a status from 400 to 599",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+/// The step of a sequence a request hit
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceStep {
+    pub name: Arc<str>,
+    /// The key the request was counted under (empty for a global counter)
+    pub key: String,
+    /// Position in the sequence, from 1; past the last step once a sequence that
+    /// doesn't repeat is done
+    pub step: u64,
+    pub steps: usize,
+    /// `None` once the sequence is done, when requests are served normally
+    pub action: Option<Action>,
+}
+
+impl SequenceStep {
+    /// e.g. `pay[abc] 2/3`, or `pay[abc] done` once the steps are used up
+    pub fn label(&self) -> String {
+        let name = if self.key.is_empty() {
+            self.name.to_string()
+        } else {
+            format!("{}[{}]", self.name, self.key)
+        };
+        match self.action {
+            Some(_) => format!("{} {}/{}", name, self.step, self.steps),
+            None => format!("{} done", name),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Sequence {
+    name: Arc<str>,
+    method: Option<Method>,
+    path: String,
+    key: RateLimitKey,
+    steps: Vec<Action>,
+    repeat: bool,
+}
+
+/// Request counter of one sequence and key
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Counter {
+    pub sequence: String,
+    pub key: String,
+    pub requests: u64,
+}
+
+/// Request counts per (sequence index, key), forgetting the least recently used keys
+/// beyond `MAX_KEYS`
+#[derive(Debug, Default)]
+struct Counters {
+    /// Requests counted, and the use that last touched the key
+    counts: BTreeMap<(usize, String), (u64, u64)>,
+    /// Keys by their last use, oldest first
+    recent: BTreeMap<u64, (usize, String)>,
+    uses: u64,
+}
+
+impl Counters {
+    /// Count a request for `key` and return how many there have been
+    fn increment(&mut self, key: (usize, String)) -> u64 {
+        self.uses += 1;
+        let (count, used) = self.counts.entry(key.clone()).or_default();
+        if *count > 0 {
+            self.recent.remove(used);
+        }
+        *count += 1;
+        *used = self.uses;
+        let count = *count;
+        self.recent.insert(self.uses, key);
+        if self.counts.len() > MAX_KEYS
+            && let Some((_, oldest)) = self.recent.pop_first()
+        {
+            self.counts.remove(&oldest);
+        }
+        count
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&(usize, String)) -> bool) {
+        self.counts.retain(|key, _| keep(key));
+        self.recent.retain(|_, key| keep(key));
+    }
+}
+
+/// Scripted responses chosen by how many matching requests came before
+#[derive(Debug, Default)]
+pub struct Sequences {
+    sequences: Vec<Sequence>,
+    counters: Mutex<Counters>,
+}
+
+impl Sequences {
+    pub fn new(configs: &[SequenceConfig]) -> anyhow::Result<Self> {
+        let sequences = configs
+            .iter()
+            .map(|config| {
+                let name = config.name.clone().unwrap_or_else(|| match &config.method {
+                    Some(method) => format!("{} {}", method.to_uppercase(), config.path),
+                    None => config.path.clone(),
+                });
+                let invalid = || format!("Invalid sequence '{}'", name);
+                if config.steps.is_empty() {
+                    return Err(anyhow!("Sequence '{}' needs at least one step", name));
+                }
+                Ok(Sequence {
+                    method: config
+                        .method
+                        .as_deref()
+                        .map(|method| method.to_uppercase().parse())
+                        .transpose()
+                        .with_context(invalid)?,
+                    path: config.path.clone(),
+                    key: config
+                        .key
+                        .as_deref()
+                        .map(str::parse)
+                        .transpose()
+                        .with_context(invalid)?
+                        .unwrap_or_default(),
+                    steps: config
+                        .steps
+                        .iter()
+                        .map(|step| step.parse())
+                        .collect::<anyhow::Result<_>>()
+                        .with_context(invalid)?,
+                    repeat: config.repeat,
+                    name: Arc::from(name),
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            sequences,
+            counters: Mutex::new(Counters::default()),
+        })
+    }
+
+    /// Count a request against the first sequence it matches and return its step
+    pub fn next(
+        &self,
+        method: &Method,
+        path: &str,
+        peer: Option<IpAddr>,
+        headers: &HeaderMap,
+    ) -> Option<SequenceStep> {
+        let (index, sequence) = self.sequences.iter().enumerate().find(|(_, sequence)| {
+            sequence.path == path && sequence.method.as_ref().is_none_or(|m| m == method)
+        })?;
+        let key = sequence.key.of(peer, path, headers);
+        let count = self
+            .counters
+            .lock()
+            .unwrap()
+            .increment((index, key.clone()));
+        let steps = sequence.steps.len() as u64;
+        let step = if sequence.repeat {
+            (count - 1) % steps + 1
+        } else {
+            count
+        };
+        Some(SequenceStep {
+            name: sequence.name.clone(),
+            key,
+            step,
+            steps: sequence.steps.len(),
+            action: sequence.steps.get(step as usize - 1).copied(),
+        })
+    }
+
+    /// Start the named sequence (or all of them) over. Returns the counters cleared.
+    pub fn reset(&self, name: Option<&str>) -> anyhow::Result<usize> {
+        let mut counters = self.counters.lock().unwrap();
+        let before = counters.counts.len();
+        match name {
+            Some(name) => {
+                let index = self
+                    .sequences
+                    .iter()
+                    .position(|sequence| &*sequence.name == name)
+                    .ok_or_else(|| anyhow!("No sequence named '{}'", name))?;
+                counters.retain(|(i, _)| *i != index);
+            }
+            None => *counters = Counters::default(),
+        }
+        Ok(before - counters.counts.len())
+    }
+
+    /// Requests counted so far, by sequence and key
+    pub fn counters(&self) -> Vec<Counter> {
+        self.counters
+            .lock()
+            .unwrap()
+            .counts
+            .iter()
+            .map(|((index, key), (requests, _))| Counter {
+                sequence: self.sequences[*index].name.to_string(),
+                key: key.clone(),
+                requests: *requests,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn sequences(json: &str) -> Sequences {
+        Sequences::new(&Config::parse(json).unwrap().sequences).unwrap()
+    }
+
+    #[test]
+    fn test_parse_steps() {
+        assert_eq!("ok".parse::<Action>().unwrap(), Action::Ok);
+        assert_eq!("503".parse::<Action>().unwrap(), Action::Status(503));
+        assert_eq!("Timeout".parse::<Action>().unwrap(), Action::Timeout);
+        assert!("302".parse::<Action>().is_err());
+        assert!("slow".parse::<Action>().is_err());
+        assert!(
+            Sequences::new(
+                &Config::parse(r#"{"sequences": [{"path": "/", "steps": []}]}"#)
+                    .unwrap()
+                    .sequences
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_steps_then_normal_service() {
+        let sequences = sequences(
+            r#"{"sequences": [{"method": "post", "path": "/pay", "steps": ["503", "503", "ok"]}]}"#,
+        );
+        let next = || sequences.next(&Method::POST, "/pay", None, &HeaderMap::new());
+        let actions: Vec<_> = (0..4).map(|_| next().unwrap().action).collect();
+        assert_eq!(
+            actions,
+            [
+                Some(Action::Status(503)),
+                Some(Action::Status(503)),
+                Some(Action::Ok),
+                None
+            ]
+        );
+        assert_eq!(next().unwrap().label(), "POST /pay done");
+        // Other methods and paths don't match
+        assert_eq!(
+            sequences.next(&Method::GET, "/pay", None, &HeaderMap::new()),
+            None
+        );
+        assert_eq!(
+            sequences.next(&Method::POST, "/pay/1", None, &HeaderMap::new()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_repeat_and_keys() {
+        let sequences = sequences(
+            r#"{"sequences": [{"name": "flaky", "path": "/", "key": "header:x-client", "steps": ["ok", "timeout"], "repeat": true}]}"#,
+        );
+        let mut a = HeaderMap::new();
+        a.insert("x-client", "a".parse().unwrap());
+        let b = HeaderMap::new();
+        let step = |headers| sequences.next(&Method::GET, "/", None, headers).unwrap();
+        assert_eq!(step(&a).action, Some(Action::Ok));
+        let second = step(&a);
+        assert_eq!(second.action, Some(Action::Timeout));
+        assert_eq!(second.label(), "flaky[a] 2/2");
+        assert_eq!(step(&a).step, 1);
+        // Each key has its own counter
+        assert_eq!(step(&b).step, 1);
+        assert_eq!(
+            sequences.counters(),
+            [
+                Counter {
+                    sequence: "flaky".to_string(),
+                    key: String::new(),
+                    requests: 1
+                },
+                Counter {
+                    sequence: "flaky".to_string(),
+                    key: "a".to_string(),
+                    requests: 3
+                }
+            ]
+        );
+
+        assert!(sequences.reset(Some("other")).is_err());
+        assert_eq!(sequences.reset(Some("flaky")).unwrap(), 2);
+        assert_eq!(step(&a).step, 1);
+        assert_eq!(sequences.reset(None).unwrap(), 1);
+        assert!(sequences.counters().is_empty());
+    }
+
+    #[test]
+    fn test_forgets_least_recently_used_keys() {
+        let sequences = sequences(
+            r#"{"sequences": [{"path": "/", "key": "header:x-client", "steps": ["503", "ok"]}]}"#,
+        );
+        let step = |client: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-client", client.parse().unwrap());
+            sequences
+                .next(&Method::GET, "/", None, &headers)
+                .unwrap()
+                .step
+        };
+        step("first");
+        step("busy");
+        for i in 0..MAX_KEYS - 2 {
+            step(&i.to_string());
+        }
+        // Using a key keeps it, so the one left idle longest is forgotten first
+        assert_eq!(step("first"), 2);
+        step("new");
+        assert_eq!(sequences.counters().len(), MAX_KEYS);
+        assert_eq!(step("busy"), 1);
+        assert_eq!(step("first"), 3);
+    }
+}
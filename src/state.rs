@@ -1,18 +1,105 @@
 use crate::OutputFormat;
-use crate::routes::DelayConfig;
-use chrono::Utc;
+use crate::clock::{self, Clock};
+use crate::compression::{CompressionMode, CompressionStats};
+use crate::concurrency::ConcurrencyLimit;
+use crate::connections::ConnectionStats;
+use crate::controls::{Command, Controls, Pause};
+use crate::grpc::GrpcRegistry;
+use crate::load::{LoadMetric, LoadTracker};
+use crate::openapi::OpenApi;
+use crate::payload::Payload;
+use crate::proxy::Upstream;
+use crate::random::{RequestRng, Seeder};
+use crate::ratelimit::RateLimiter;
+use crate::replay::{Recorder, Replay};
+use crate::retries::{Attempt, RetryTracker};
+use crate::routes::{DelayConfig, ErrorConfig};
+use crate::scenario::Scenario;
+use crate::sequence::{Action, SequenceStep, Sequences};
+use crate::templates::ResponseTemplate;
+use crate::throttle::ThrottleConfig;
+use axum::http::HeaderName;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::sync::mpsc::Sender;
 
+/// How the server dealt with a request
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Outcome {
+    /// Served normally
+    #[default]
+    Served,
+    /// The response is an injected error
+    InjectedError,
+    /// The request violated the OpenAPI spec (client bug)
+    ValidationError(String),
+    /// The request was rejected by the rate limit
+    RateLimited,
+    /// The request was turned away by a full queue
+    QueueFull,
+    /// The request was turned away by a paused server
+    Paused,
+    /// The upstream could not be reached or its response failed (proxy mode)
+    UpstreamError,
+}
+
 /// A log of one request
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct RequestLog {
     pub path: String,
     pub method: String,
     pub status: u16,
+    pub grpc_status: Option<u8>, // Set for gRPC calls
+    pub listener: String,        // Name of the listener that served the request
+    pub outcome: Outcome,
     pub timestamp: i64,   // Unix timestamp
     pub duration_ms: f64, // Request duration in milliseconds with nanosecond precision
+    pub context: RequestContext,
+}
+
+impl RequestLog {
+    /// A log of a request served normally by `state`'s listener, timestamped now
+    pub fn new(state: &AppState, method: &str, path: &str, status: u16) -> Self {
+        Self {
+            path: path.to_string(),
+            method: method.to_string(),
+            status,
+            grpc_status: None,
+            listener: state.listener.to_string(),
+            outcome: Outcome::Served,
+            timestamp: state.now_timestamp(),
+            duration_ms: 0.0,
+            context: state.context.clone(),
+        }
+    }
+
+    /// Whether the request failed from the client's point of view: a 4xx/5xx status, a
+    /// non-OK gRPC status, or no response at all
+    pub fn is_error(&self) -> bool {
+        match self.grpc_status {
+            Some(code) => code != 0,
+            None => self.status == 0 || self.status >= 400,
+        }
+    }
+}
+
+/// Details about a request gathered before it reaches a handler
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    /// How long the client waited after it was last rate limited, in milliseconds
+    pub backoff_ms: Option<f64>,
+    /// The client retried before the rate limit allowed it to
+    pub early_retry: bool,
+    /// Time spent waiting for a worker, in milliseconds (not part of `duration_ms`)
+    pub queue_ms: f64,
+    /// Load of the listener when the request started, by its load metric
+    pub load: f64,
+    /// The step of a sequence the request hit
+    pub sequence: Option<SequenceStep>,
+    /// Who sent the request: the identity header's value, or the peer's IP address
+    pub client: String,
+    /// Which attempt of a request this is, when clients retry
+    pub attempt: Option<Attempt>,
 }
 
 /// Events that the server sends to the TUI
@@ -26,8 +113,48 @@ pub enum AppEvent {
 #[derive(Clone)]
 pub struct AppState {
     pub total_requests: Arc<AtomicU64>,
+    /// Source of time for timestamps, durations and delays
+    pub clock: Arc<dyn Clock>,
     pub tx: Sender<AppEvent>,
+    pub listener: Arc<str>,
     delay_config: Arc<DelayConfig>,
+    error_config: Arc<ErrorConfig>,
+    pub grpc: Arc<GrpcRegistry>,
+    pub upstream: Option<Arc<Upstream>>,
+    pub recorder: Option<Arc<Recorder>>,
+    pub replay: Option<Arc<Replay>>,
+    pub openapi: Option<Arc<OpenApi>>,
+    pub template: Option<Arc<ResponseTemplate>>,
+    pub payload: Option<Arc<Payload>>,
+    pub throttle: Arc<ThrottleConfig>,
+    pub compression: CompressionMode,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Worker pool and queue of this listener
+    pub concurrency: Option<Arc<ConcurrencyLimit>>,
+    /// Load of this listener, for load-dependent delays and failures
+    pub load: Arc<LoadTracker>,
+    /// Timeline replacing the delay and error settings while it runs
+    pub scenario: Option<Arc<Scenario>>,
+    /// Scripted responses by request count, shared by all listeners
+    pub sequences: Arc<Sequences>,
+    /// Seeds the random decisions of each request to this listener
+    pub seeder: Arc<Seeder>,
+    /// Random number generator of the request being handled, set per request
+    pub rng: RequestRng,
+    /// Details of the request being handled, set per request
+    pub context: RequestContext,
+    /// Compression statistics of this listener
+    pub compression_stats: Arc<CompressionStats>,
+    /// Response body bytes sent by this listener
+    pub bytes_sent: Arc<AtomicU64>,
+    /// Open connections and requests in flight on this listener
+    pub connections: Arc<ConnectionStats>,
+    /// Header identifying clients (e.g. `x-client-id`), instead of their IP address
+    pub client_header: Option<HeaderName>,
+    /// Recognizes retried requests, shared by all listeners
+    pub retries: Arc<RetryTracker>,
+    /// Settings of this listener changed from the TUI
+    pub controls: Arc<Controls>,
     pub output_format: OutputFormat,
 }
 
@@ -38,14 +165,77 @@ impl AppState {
         output_format: OutputFormat,
     ) -> anyhow::Result<Self> {
         let delay_config = DelayConfig::parse(delay_str)?;
+        let clock = clock::system();
         Ok(Self {
             total_requests: Arc::new(AtomicU64::new(0)),
+            load: Arc::new(LoadTracker::new(LoadMetric::default(), clock.now())),
+            clock,
             tx,
+            listener: Arc::from("default"),
             delay_config: Arc::new(delay_config),
+            error_config: Arc::new(ErrorConfig::default()),
+            grpc: Arc::new(GrpcRegistry::default()),
+            upstream: None,
+            recorder: None,
+            replay: None,
+            openapi: None,
+            template: None,
+            payload: None,
+            throttle: Arc::new(ThrottleConfig::default()),
+            compression: CompressionMode::default(),
+            rate_limiter: None,
+            concurrency: None,
+            scenario: None,
+            sequences: Arc::new(Sequences::default()),
+            seeder: Arc::new(Seeder::new(None)),
+            rng: RequestRng::default(),
+            context: RequestContext::default(),
+            compression_stats: Arc::new(CompressionStats::default()),
+            bytes_sent: Arc::new(AtomicU64::new(0)),
+            connections: Arc::new(ConnectionStats::default()),
+            client_header: None,
+            retries: Arc::new(RetryTracker::default()),
+            controls: Arc::new(Controls::default()),
             output_format,
         })
     }
 
+    /// Name the listener this state serves
+    pub fn with_listener(mut self, name: &str) -> Self {
+        self.listener = Arc::from(name);
+        self.bytes_sent = Arc::new(AtomicU64::new(0));
+        self.connections = Arc::new(ConnectionStats::default());
+        self.controls = Arc::new(Controls::default());
+        self.compression_stats = Arc::new(CompressionStats::default());
+        self.load = Arc::new(LoadTracker::new(self.load.metric, self.clock.now()));
+        self.seeder = Arc::new(self.seeder.for_listener(name));
+        self
+    }
+
+    /// Use the given delay model
+    pub fn with_delay(mut self, delay_config: DelayConfig) -> Self {
+        self.delay_config = Arc::new(delay_config);
+        self
+    }
+
+    /// Use the given response format
+    pub fn with_format(mut self, output_format: OutputFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    /// Use the given error injection model
+    pub fn with_errors(mut self, error_config: ErrorConfig) -> Self {
+        self.error_config = Arc::new(error_config);
+        self
+    }
+
+    /// Serve the given gRPC methods
+    pub fn with_grpc(mut self, grpc: GrpcRegistry) -> Self {
+        self.grpc = Arc::new(grpc);
+        self
+    }
+
     /// Increment the request counter
     pub fn increment_requests(&self) {
         self.total_requests.fetch_add(1, Ordering::Relaxed);
@@ -53,12 +243,212 @@ impl AppState {
 
     /// Return the current Unix timestamp
     pub fn now_timestamp(&self) -> i64 {
-        Utc::now().timestamp()
+        self.clock.wall().timestamp()
+    }
+
+    /// Milliseconds since `start`, by the clock
+    pub fn elapsed_ms(&self, start: std::time::Instant) -> f64 {
+        self.clock
+            .now()
+            .saturating_duration_since(start)
+            .as_secs_f64()
+            * 1000.0
+    }
+
+    /// Take the time from the given clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.load = Arc::new(LoadTracker::new(self.load.metric, clock.now()));
+        self.clock = clock;
+        self
+    }
+
+    /// Forward requests to the given upstream instead of synthesizing responses
+    pub fn with_upstream(mut self, upstream: Option<Upstream>) -> Self {
+        self.upstream = upstream.map(Arc::new);
+        self
+    }
+
+    /// Save proxied exchanges with the given recorder
+    pub fn with_recorder(mut self, recorder: Option<Arc<Recorder>>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Serve recorded responses instead of synthesizing them
+    pub fn with_replay(mut self, replay: Option<Arc<Replay>>) -> Self {
+        self.replay = replay;
+        self
+    }
+
+    /// Serve the operations of an OpenAPI spec instead of synthesizing responses
+    pub fn with_openapi(mut self, openapi: Option<Arc<OpenApi>>) -> Self {
+        self.openapi = openapi;
+        self
+    }
+
+    /// Render responses from the given template instead of the built-in body
+    pub fn with_template(mut self, template: Option<Arc<ResponseTemplate>>) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// Generate successful response bodies from the given payload
+    pub fn with_payload(mut self, payload: Option<Arc<Payload>>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Limit the bandwidth of response and request bodies
+    pub fn with_throttle(mut self, throttle: ThrottleConfig) -> Self {
+        self.throttle = Arc::new(throttle);
+        self
+    }
+
+    /// Compress responses according to the given mode
+    pub fn with_compression(mut self, compression: CompressionMode) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Reject requests over the given rate limit
+    pub fn with_rate_limit(mut self, rate_limiter: Option<Arc<RateLimiter>>) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Serve at most the given number of requests at once, queueing the rest
+    pub fn with_concurrency(mut self, concurrency: Option<ConcurrencyLimit>) -> Self {
+        self.concurrency = concurrency.map(Arc::new);
+        self
+    }
+
+    /// Measure load by the given metric
+    pub fn with_load_metric(mut self, metric: LoadMetric) -> Self {
+        self.load = Arc::new(LoadTracker::new(metric, self.clock.now()));
+        self
+    }
+
+    /// Follow the given scenario timeline
+    pub fn with_scenario(mut self, scenario: Option<Arc<Scenario>>) -> Self {
+        self.scenario = scenario;
+        self
+    }
+
+    /// Derive every random decision from the given seed (a random one if unset)
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.seeder = Arc::new(Seeder::new(seed));
+        self
+    }
+
+    /// Tell clients apart by the given header instead of their IP address
+    pub fn with_client_header(mut self, header: Option<HeaderName>) -> Self {
+        self.client_header = header;
+        self
+    }
+
+    /// Group retried requests by the given tracker's key and window
+    pub fn with_retries(mut self, retries: RetryTracker) -> Self {
+        self.retries = Arc::new(retries);
+        self
+    }
+
+    /// Script responses to matching requests by their count
+    pub fn with_sequences(mut self, sequences: Sequences) -> Self {
+        self.sequences = Arc::new(sequences);
+        self
     }
 
     /// Get the delay for the current request
     pub fn get_delay(&self) -> u64 {
-        self.delay_config.get_delay()
+        if let Some(delay) = self.controls.delay() {
+            return self
+                .rng
+                .with(|rng| delay.get_delay(rng, self.context.load, self.load.metric));
+        }
+        match self
+            .scenario
+            .as_ref()
+            .and_then(|scenario| scenario.current())
+        {
+            Some(phase) => self.delay_config.apply_load(
+                self.rng.with(|rng| phase.conditions.delay(rng)),
+                self.context.load,
+                self.load.metric,
+            ),
+            None => self.rng.with(|rng| {
+                self.delay_config
+                    .get_delay(rng, self.context.load, self.load.metric)
+            }),
+        }
+    }
+
+    /// Get the injected error status for the current request, if any
+    pub fn get_error(&self) -> Option<u16> {
+        // A sequence step decides the outcome by itself
+        if let Some(action) = self.context.sequence.as_ref().and_then(|step| step.action) {
+            return match action {
+                Action::Status(status) => Some(status),
+                _ => None,
+            };
+        }
+        if !self.controls.errors_enabled() {
+            return None;
+        }
+        match self
+            .scenario
+            .as_ref()
+            .and_then(|scenario| scenario.current())
+        {
+            Some(phase) => self
+                .error_config
+                .overload(self.context.load)
+                .or_else(|| self.rng.with(|rng| phase.conditions.error(rng))),
+            None => self
+                .rng
+                .with(|rng| self.error_config.get_error(rng, self.context.load)),
+        }
+    }
+
+    /// Whether the scenario is in an outage, or the listener is paused, when connections
+    /// are refused
+    pub fn in_outage(&self) -> bool {
+        self.controls.pause() == Pause::Refused
+            || self
+                .scenario
+                .as_ref()
+                .and_then(|scenario| scenario.current())
+                .is_some_and(|phase| phase.conditions.outage)
+    }
+
+    /// Apply a command from the TUI to this listener
+    pub fn control(&self, command: Command) {
+        let controls = &self.controls;
+        match command {
+            Command::AdjustDelay(step) => {
+                let delay = controls
+                    .delay()
+                    .unwrap_or_else(|| self.delay_config.clone());
+                let (min, max) = delay.range();
+                let shift = |ms: u64| ms.saturating_add_signed(step);
+                controls.set_delay(self.delay_config.with_range(shift(min), shift(max)));
+            }
+            Command::SetDelay(min, max) => {
+                controls.set_delay(self.delay_config.with_range(min, max))
+            }
+            Command::ToggleErrors => controls.toggle_errors(),
+            Command::CyclePause => controls.cycle_pause(),
+            Command::CycleFormat => {
+                controls.set_format(controls.format().unwrap_or(self.output_format).next())
+            }
+        }
+    }
+
+    /// Send a request log to the TUI, noting whether a retried request failed again
+    pub async fn record(&self, log: RequestLog) {
+        if let Some(attempt) = &log.context.attempt {
+            self.retries.finish(attempt, log.is_error());
+        }
+        let _ = self.tx.send(AppEvent::RequestReceived(log)).await;
     }
 }
 
@@ -66,6 +456,7 @@ impl AppState {
 mod tests {
     use super::*;
     use crate::OutputFormat;
+    use crate::clock::ManualClock;
     use tokio::sync::mpsc;
 
     #[tokio::test]
@@ -81,6 +472,51 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_sequence_step_overrides_errors() {
+        let (tx, _rx) = mpsc::channel(10);
+        let mut state = AppState::new(tx, "0", OutputFormat::Json)
+            .unwrap()
+            .with_errors(ErrorConfig::new(100.0, 500).unwrap());
+        assert_eq!(state.get_error(), Some(500));
+        let step = |action| SequenceStep {
+            name: Arc::from("pay"),
+            key: String::new(),
+            step: 1,
+            steps: 2,
+            action,
+        };
+        state.context.sequence = Some(step(Some(Action::Status(503))));
+        assert_eq!(state.get_error(), Some(503));
+        state.context.sequence = Some(step(Some(Action::Ok)));
+        assert_eq!(state.get_error(), None);
+        // Once the sequence is done, requests are served normally
+        state.context.sequence = Some(step(None));
+        assert_eq!(state.get_error(), Some(500));
+    }
+
+    #[tokio::test]
+    async fn test_seeded_requests_repeat() {
+        let (tx, _rx) = mpsc::channel(10);
+        let state = AppState::new(tx, "0-1000", OutputFormat::Json)
+            .unwrap()
+            .with_errors(ErrorConfig::new(50.0, 503).unwrap())
+            .with_seed(Some(1234));
+        let run = |state: &AppState| {
+            let mut state = state.clone().with_listener("default");
+            (0..20)
+                .map(|_| {
+                    state.rng = state.seeder.next_rng();
+                    (state.get_delay(), state.get_error())
+                })
+                .collect::<Vec<_>>()
+        };
+        let first = run(&state);
+        assert_eq!(first, run(&state));
+        assert!(first.iter().any(|(_, error)| error.is_some()));
+        assert!(first.iter().any(|(_, error)| error.is_none()));
+    }
+
     #[tokio::test]
     async fn test_now_timestamp() {
         let (tx, _rx) = mpsc::channel(10);
@@ -90,4 +526,18 @@ mod tests {
         let current = chrono::Utc::now().timestamp();
         assert!((now - current).abs() < 2);
     }
+
+    #[tokio::test]
+    async fn test_now_timestamp_follows_clock() {
+        let (tx, _rx) = mpsc::channel(10);
+        let clock = Arc::new(ManualClock::new(
+            chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        ));
+        let state = AppState::new(tx, "100", OutputFormat::Json)
+            .unwrap()
+            .with_clock(clock.clone());
+        assert_eq!(state.now_timestamp(), 1_700_000_000);
+        clock.advance(std::time::Duration::from_secs(90));
+        assert_eq!(state.now_timestamp(), 1_700_000_090);
+    }
 }
@@ -0,0 +1,311 @@
+use crate::config::ResponseConfig;
+use crate::random::RequestRng;
+use crate::util::parse_query;
+use anyhow::{Context, anyhow};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use chrono::{DateTime, Utc};
+use minijinja::{
+    Environment, Error, ErrorKind, State, UndefinedBehavior, Value as TemplateValue, context,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::{Value, json};
+use std::fmt::Write;
+
+const BODY_TEMPLATE: &str = "body";
+
+/// The request data a response template can refer to
+pub struct TemplateRequest<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub query: Option<&'a str>,
+    pub headers: &'a HeaderMap,
+    pub body: &'a [u8],
+    pub counter: u64,
+    pub listener: &'a str,
+    pub rng: &'a RequestRng,
+    /// When the request arrived, by the server's clock
+    pub now: DateTime<Utc>,
+}
+
+impl TemplateRequest<'_> {
+    fn context(&self) -> Value {
+        let segments: Vec<&str> = self.path.split('/').filter(|s| !s.is_empty()).collect();
+        let headers: serde_json::Map<String, Value> = self
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), json!(value.to_str().ok()?))))
+            .collect();
+        let body_text = String::from_utf8_lossy(self.body);
+        json!({
+            "method": self.method,
+            "path": self.path,
+            "segments": segments,
+            "query": parse_query(self.query.unwrap_or("")),
+            "headers": headers,
+            "body": serde_json::from_slice::<Value>(self.body).unwrap_or(Value::Null),
+            "body_text": body_text,
+            "counter": self.counter,
+            "listener": self.listener,
+        })
+    }
+}
+
+/// Name of the request's random number generator in the template context
+const RNG_VARIABLE: &str = "__rng";
+
+/// Draw from the random number generator of the request being rendered
+fn with_rng<T>(state: &State, f: impl FnOnce(&mut StdRng) -> T) -> T {
+    match state
+        .lookup(RNG_VARIABLE)
+        .and_then(|rng| rng.downcast_object::<RequestRng>())
+    {
+        Some(rng) => rng.with(f),
+        None => f(&mut StdRng::from_rng(&mut rand::rng())),
+    }
+}
+
+/// Name of the request's arrival time, in microseconds since the epoch, in the template context
+const NOW_VARIABLE: &str = "__now";
+
+/// The arrival time of the request being rendered
+fn request_time(state: &State) -> Result<DateTime<Utc>, Error> {
+    state
+        .lookup(NOW_VARIABLE)
+        .and_then(|now| i64::try_from(now).ok())
+        .and_then(DateTime::from_timestamp_micros)
+        .ok_or_else(|| Error::new(ErrorKind::UndefinedError, "no request time"))
+}
+
+/// Generate a random (version 4) UUID
+fn uuid_v4(rng: &mut impl Rng) -> String {
+    let mut bytes: [u8; 16] = rng.random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Response status, headers and body rendered from templates.
+///
+/// Templates use MiniJinja syntax and see `method`, `path`, `segments`,
+/// `query`, `headers`, `body` (parsed JSON, or none), `body_text`, `counter`
+/// and `listener`, plus the `uuid()`, `random(min, max)`, `now(format)` and
+/// `timestamp()` helpers.
+pub struct ResponseTemplate {
+    env: Environment<'static>,
+    status: StatusCode,
+    headers: Vec<HeaderName>,
+}
+
+impl std::fmt::Debug for ResponseTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseTemplate")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+impl ResponseTemplate {
+    /// Compile the templates and render them once, so errors surface at startup
+    pub fn new(config: &ResponseConfig) -> anyhow::Result<Self> {
+        let status = StatusCode::from_u16(config.status)
+            .map_err(|_| anyhow!("Invalid response status {}", config.status))?;
+        let body = match (&config.body, &config.body_file) {
+            (Some(_), Some(_)) => {
+                return Err(anyhow!("Set either 'body' or 'body_file', not both"));
+            }
+            (Some(body), None) => body.clone(),
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read body template {}", path.display()))?,
+            (None, None) => String::new(),
+        };
+
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Chainable);
+        env.add_function("uuid", |state: &State| with_rng(state, uuid_v4));
+        env.add_function("random", |state: &State, min: i64, max: i64| {
+            if min >= max {
+                min
+            } else {
+                with_rng(state, |rng| rng.random_range(min..=max))
+            }
+        });
+        env.add_function("now", |state: &State, format: Option<String>| {
+            let now = request_time(state)?;
+            let Some(format) = format else {
+                return Ok(now.to_rfc3339());
+            };
+            // Formatting with an invalid specifier fails instead of panicking like to_string
+            let mut out = String::new();
+            write!(out, "{}", now.format(&format)).map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidOperation,
+                    format!("invalid now() format '{}'", format),
+                )
+            })?;
+            Ok(out)
+        });
+        env.add_function("timestamp", |state: &State| {
+            request_time(state).map(|now| now.timestamp())
+        });
+
+        env.add_template_owned(BODY_TEMPLATE, body)
+            .context("Invalid response body template")?;
+        let mut headers = Vec::new();
+        for (name, value) in &config.headers {
+            let header = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| anyhow!("Invalid response header name '{}'", name))?;
+            env.add_template_owned(header.to_string(), value.clone())
+                .with_context(|| format!("Invalid template for response header '{}'", name))?;
+            headers.push(header);
+        }
+
+        let template = Self {
+            env,
+            status,
+            headers,
+        };
+        let sample = HeaderMap::new();
+        template
+            .render(&TemplateRequest {
+                method: "GET",
+                path: "/",
+                query: None,
+                headers: &sample,
+                body: b"",
+                counter: 1,
+                listener: "default",
+                rng: &RequestRng::default(),
+                now: DateTime::UNIX_EPOCH,
+            })
+            .context("Response template failed to render")?;
+        Ok(template)
+    }
+
+    /// Render the response for a request
+    pub fn render(
+        &self,
+        request: &TemplateRequest,
+    ) -> anyhow::Result<(StatusCode, HeaderMap, String)> {
+        let context = context! {
+            __rng => TemplateValue::from_object(request.rng.clone()),
+            __now => request.now.timestamp_micros(),
+            ..TemplateValue::from_serialize(request.context())
+        };
+        let mut headers = HeaderMap::new();
+        for name in &self.headers {
+            let value = self.env.get_template(name.as_str())?.render(&context)?;
+            let value = HeaderValue::from_str(&value)
+                .map_err(|_| anyhow!("Header '{}' rendered to an invalid value", name))?;
+            headers.insert(name.clone(), value);
+        }
+        let body = self.env.get_template(BODY_TEMPLATE)?.render(&context)?;
+        Ok((self.status, headers, body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn template(body: &str, headers: &[(&str, &str)]) -> anyhow::Result<ResponseTemplate> {
+        ResponseTemplate::new(&ResponseConfig {
+            status: 201,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<BTreeMap<_, _>>(),
+            body: Some(body.to_string()),
+            body_file: None,
+        })
+    }
+
+    fn render(
+        template: &ResponseTemplate,
+        path: &str,
+        query: Option<&str>,
+        body: &[u8],
+    ) -> (StatusCode, HeaderMap, String) {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-client-id", HeaderValue::from_static("svc-a"));
+        template
+            .render(&TemplateRequest {
+                method: "POST",
+                path,
+                query,
+                headers: &headers,
+                body,
+                counter: 42,
+                listener: "payments",
+                rng: &RequestRng::seeded(42),
+                now: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_render_request_values() {
+        let template = template(
+            r#"{"id": {{ body.id }}, "user": "{{ segments[1] }}", "page": {{ query.page }}, "n": {{ counter }}}"#,
+            &[("x-echo", "{{ headers['x-client-id'] }}")],
+        )
+        .unwrap();
+        let (status, headers, body) =
+            render(&template, "/users/alice", Some("page=2"), br#"{"id": 7}"#);
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(headers["x-echo"], "svc-a");
+        assert_eq!(body, r#"{"id": 7, "user": "alice", "page": 2, "n": 42}"#);
+    }
+
+    #[test]
+    fn test_missing_values_render_empty() {
+        let template = template("[{{ body.missing.deeper }}]", &[]).unwrap();
+        assert_eq!(render(&template, "/", None, b"").2, "[]");
+    }
+
+    #[test]
+    fn test_helpers() {
+        let template = template(
+            "{{ uuid() }} {{ random(5, 5) }} {{ timestamp() }} {{ now('%Y-%m-%d') }} {{ now() }}",
+            &[],
+        )
+        .unwrap();
+        let body = render(&template, "/", None, b"").2;
+        let parts: Vec<&str> = body.split(' ').collect();
+        assert_eq!(parts[0].len(), 36);
+        assert_eq!(&parts[0][14..15], "4");
+        assert_eq!(parts[1], "5");
+        // Times come from the request, not the wall clock
+        assert_eq!(parts[2], "1700000000");
+        assert_eq!(parts[3], "2023-11-14");
+        assert_eq!(parts[4], "2023-11-14T22:13:20+00:00");
+    }
+
+    #[test]
+    fn test_helpers_use_request_rng() {
+        // The same request generator renders the same values
+        let seeded = template("{{ uuid() }} {{ random(1, 1000000) }}", &[]).unwrap();
+        assert_eq!(
+            render(&seeded, "/", None, b"").2,
+            render(&seeded, "/", None, b"").2
+        );
+    }
+
+    #[test]
+    fn test_errors_reported_at_startup() {
+        assert!(template("{{ body.id ", &[]).is_err());
+        assert!(template("{{ not_a_function() }}", &[]).is_err());
+        assert!(template("ok", &[("bad header", "x")]).is_err());
+        assert!(template("{{ now('%Q') }}", &[]).is_err());
+    }
+}
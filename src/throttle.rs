@@ -0,0 +1,289 @@
+use crate::clock::Clock;
+use crate::config::RouteConfig;
+use crate::payload::parse_byte_size;
+use crate::util::parse_duration;
+use anyhow::anyhow;
+use axum::{
+    body::{Bytes, HttpBody},
+    http::HeaderMap,
+};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, ready};
+use std::time::Duration;
+
+/// How often a bandwidth-limited body sends a slice of data
+const BANDWIDTH_SLICE: Duration = Duration::from_millis(100);
+
+/// Parse `<size>/<duration>`, e.g. `256KiB/s` or `16B/250ms`
+fn parse_rate(s: &str) -> anyhow::Result<(u64, Duration)> {
+    let (size, per) = s
+        .split_once('/')
+        .ok_or_else(|| anyhow!("Expected '<size>/<duration>' (e.g. 256KiB/s), got '{}'", s))?;
+    let size = parse_byte_size(size)?;
+    if size == 0 {
+        return Err(anyhow!("Rate '{}' must be positive", s));
+    }
+    Ok((size, parse_duration(per)?))
+}
+
+/// Sends a body in chunks of `chunk` bytes, one every `interval`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Throttle {
+    chunk: usize,
+    interval: Duration,
+}
+
+impl Throttle {
+    /// A bandwidth limit such as `256KiB/s`, sent in small slices so the rate is smooth
+    pub fn bandwidth(s: &str) -> anyhow::Result<Self> {
+        let (size, per) = parse_rate(s)?;
+        let chunk = (size as f64 * BANDWIDTH_SLICE.as_secs_f64() / per.as_secs_f64()).max(1.0);
+        Ok(Self {
+            chunk: chunk as usize,
+            interval: per.mul_f64(chunk.floor() / size as f64),
+        })
+    }
+
+    /// Chunks of exactly the given size with a pause between them, such as `16B/250ms`
+    pub fn trickle(s: &str) -> anyhow::Result<Self> {
+        let (size, interval) = parse_rate(s)?;
+        Ok(Self {
+            chunk: size as usize,
+            interval,
+        })
+    }
+}
+
+/// A per-path override of the throttles
+#[derive(Debug, Clone)]
+struct RouteThrottle {
+    prefix: String,
+    download: Option<Throttle>,
+    upload: Option<Throttle>,
+}
+
+/// Throttles for response bodies (downloads) and request bodies (uploads)
+#[derive(Debug, Clone, Default)]
+pub struct ThrottleConfig {
+    download: Option<Throttle>,
+    upload: Option<Throttle>,
+    routes: Vec<RouteThrottle>,
+}
+
+/// The download throttle from a bandwidth limit or a trickle setting
+fn download_throttle(
+    bandwidth: Option<&str>,
+    trickle: Option<&str>,
+) -> anyhow::Result<Option<Throttle>> {
+    match (bandwidth, trickle) {
+        (Some(_), Some(_)) => Err(anyhow!("Set either a bandwidth or a trickle, not both")),
+        (Some(rate), None) => Throttle::bandwidth(rate).map(Some),
+        (None, Some(rate)) => Throttle::trickle(rate).map(Some),
+        (None, None) => Ok(None),
+    }
+}
+
+impl ThrottleConfig {
+    pub fn new(
+        bandwidth: Option<&str>,
+        trickle: Option<&str>,
+        upload_bandwidth: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            download: download_throttle(bandwidth, trickle)?,
+            upload: upload_bandwidth.map(Throttle::bandwidth).transpose()?,
+            routes: Vec::new(),
+        })
+    }
+
+    /// Override the throttles for paths starting with each route's prefix
+    pub fn with_routes(mut self, routes: &[RouteConfig]) -> anyhow::Result<Self> {
+        for route in routes {
+            self.routes.push(RouteThrottle {
+                prefix: route.path.clone(),
+                download: download_throttle(route.bandwidth.as_deref(), route.trickle.as_deref())?,
+                upload: route
+                    .upload_bandwidth
+                    .as_deref()
+                    .map(Throttle::bandwidth)
+                    .transpose()?,
+            });
+        }
+        Ok(self)
+    }
+
+    /// The (download, upload) throttles for a path; the longest matching route wins
+    pub fn for_path(&self, path: &str) -> (Option<Throttle>, Option<Throttle>) {
+        let route = self
+            .routes
+            .iter()
+            .filter(|route| path.starts_with(&route.prefix))
+            .max_by_key(|route| route.prefix.len());
+        match route {
+            Some(route) => (
+                route.download.or(self.download),
+                route.upload.or(self.upload),
+            ),
+            None => (self.download, self.upload),
+        }
+    }
+}
+
+/// Wraps a body and delivers it no faster than the throttle allows.
+///
+/// Polling a request body slowly also slows down reading from the socket, so
+/// clients uploading to a throttled route see backpressure.
+pub struct ThrottledBody<B> {
+    inner: B,
+    throttle: Throttle,
+    pending: Bytes,
+    /// Pause owed for the last chunk, taken before the next one is sent
+    owed: Option<Duration>,
+    clock: Arc<dyn Clock>,
+    sleep: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+impl<B> ThrottledBody<B> {
+    /// Pace `inner`, waiting on the given clock between chunks
+    pub fn new(inner: B, throttle: Throttle, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            inner,
+            throttle,
+            pending: Bytes::new(),
+            owed: None,
+            clock,
+            sleep: None,
+        }
+    }
+}
+
+impl<B> HttpBody for ThrottledBody<B>
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        while this.pending.is_empty() {
+            match ready!(Pin::new(&mut this.inner).poll_data(cx)) {
+                Some(Ok(data)) => this.pending = data,
+                other => return Poll::Ready(other),
+            }
+        }
+        if let Some(owed) = this.owed.take() {
+            this.sleep = Some(this.clock.sleep(owed));
+        }
+        if let Some(pause) = &mut this.sleep {
+            ready!(pause.as_mut().poll(cx));
+            this.sleep = None;
+        }
+        let chunk = this
+            .pending
+            .split_to(this.throttle.chunk.min(this.pending.len()));
+        this.owed = Some(
+            this.throttle
+                .interval
+                .mul_f64(chunk.len() as f64 / this.throttle.chunk as f64),
+        );
+        Poll::Ready(Some(Ok(chunk)))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.pending.is_empty() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        let inner = self.inner.size_hint();
+        let pending = self.pending.len() as u64;
+        let mut hint = hyper::body::SizeHint::new();
+        hint.set_lower(inner.lower() + pending);
+        if let Some(upper) = inner.upper() {
+            hint.set_upper(upper + pending);
+        }
+        hint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use axum::body::Full;
+
+    #[test]
+    fn test_parse_throttles() {
+        assert_eq!(
+            Throttle::trickle("16B/250ms").unwrap(),
+            Throttle {
+                chunk: 16,
+                interval: Duration::from_millis(250)
+            }
+        );
+        // 256 KiB/s is sent as 100ms slices
+        let throttle = Throttle::bandwidth("256KiB/s").unwrap();
+        assert_eq!(throttle.chunk, 26214);
+        assert_eq!(throttle.interval.as_millis(), 99);
+        // Very low rates still send whole bytes
+        let throttle = Throttle::bandwidth("5B/s").unwrap();
+        assert_eq!(throttle.chunk, 1);
+        assert_eq!(throttle.interval, Duration::from_millis(200));
+
+        assert!(Throttle::bandwidth("256KiB").is_err());
+        assert!(Throttle::bandwidth("0B/s").is_err());
+        assert!(Throttle::trickle("16B/0ms").is_err());
+        assert!(Throttle::trickle("16B/fortnight").is_err());
+    }
+
+    #[test]
+    fn test_routes_override_by_longest_prefix() {
+        let routes: Vec<RouteConfig> = serde_json::from_str(
+            r#"[
+                {"path": "/files", "bandwidth": "1MB/s"},
+                {"path": "/files/slow", "trickle": "1B/s", "upload_bandwidth": "1KB/s"}
+            ]"#,
+        )
+        .unwrap();
+        let config = ThrottleConfig::new(None, Some("10B/ms"), None)
+            .unwrap()
+            .with_routes(&routes)
+            .unwrap();
+        assert_eq!(config.for_path("/").0.unwrap().chunk, 10);
+        assert_eq!(config.for_path("/files/a").0.unwrap().chunk, 100_000);
+        let (download, upload) = config.for_path("/files/slow/a");
+        assert_eq!(download.unwrap().chunk, 1);
+        assert_eq!(upload.unwrap().chunk, 100);
+        assert!(config.for_path("/files").1.is_none());
+
+        assert!(ThrottleConfig::new(Some("1MB/s"), Some("1B/s"), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_throttled_body_is_chunked_and_paced() {
+        let body = Full::new(Bytes::from_static(b"0123456789"));
+        let throttle = Throttle::trickle("4B/20ms").unwrap();
+        let clock = Arc::new(ManualClock::new(chrono::Utc::now()));
+        let mut body = ThrottledBody::new(body, throttle, clock.clone());
+        let start = clock.now();
+        let mut chunks = Vec::new();
+        while let Some(chunk) = body.data().await {
+            chunks.push(chunk.unwrap());
+        }
+        assert_eq!(chunks, ["0123", "4567", "89"]);
+        // Two pauses between three chunks; none after the last one
+        assert_eq!(clock.now() - start, Duration::from_millis(40));
+    }
+}
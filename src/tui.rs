@@ -1,5 +1,7 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::io::stdout;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant};
 
 use chrono::Utc;
@@ -13,44 +15,281 @@ use crossterm::{
 use ratatui::{
     Terminal,
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Style},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
     symbols,
-    text::Span,
-    widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph},
+    text::{Span, Spans},
+    widgets::{
+        Axis, Block, Borders, Cell, Chart, Clear, Dataset, GraphType, Paragraph, Row, Table,
+    },
 };
 
-use tokio::sync::mpsc::Receiver;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::watch;
 
-use crate::state::{AppEvent, RequestLog};
+use crate::clock::Clock;
+use crate::compression::Encoding;
+use crate::concurrency::QueueOverflow;
+use crate::connections::ConnectionStats;
+use crate::controls::{Command, ControlEvent};
+use crate::grpc::code_name;
+use crate::history::{
+    History, HistoryConfig, LATENCY_BINS_MS, LatencyBucket, LatencyHistogram, merge_columns,
+};
+use crate::layout::{Layouts, Panel, RowHeight};
+use crate::listener::ListenerInfo;
+use crate::report::{self, Report, ReportConfig, format_seconds};
+use crate::retries::{BackoffPattern, classify};
+use crate::routes::DelayConfig;
+use crate::scenario::Scenario;
+use crate::sequence::Action;
+use crate::state::{AppEvent, Outcome, RequestLog};
 
 /// Maximum number of logs to store
 const MAX_LOGS: usize = 1000;
 
-/// Data that the TUI thread holds locally
-struct TuiData {
-    /// Ring buffer of the most recent logs
-    logs: VecDeque<RequestLog>,
+/// Maximum number of distinct route/status rows tracked in the breakdown
+const MAX_ROUTES: usize = 1000;
+
+/// Maximum number of distinct clients tracked in the clients table
+const MAX_CLIENTS: usize = 1000;
+
+/// Maximum number of requests whose attempts are tracked in the retries panel
+const MAX_RETRY_CHAINS: usize = 1000;
+
+/// Retries in the last second from which a surge of retries counts as a storm
+const RETRY_STORM_RPS: f64 = 10.0;
+
+/// Clients listed in the report
+const REPORT_CLIENTS: usize = 20;
+
+/// How much '[' and ']' change the delay, in milliseconds
+const DELAY_STEP_MS: i64 = 10;
+
+/// Keybindings shown by '?'
+const HELP: [(&str, &str); 15] = [
+    ("q, Ctrl+C", "Quit"),
+    ("?, Esc", "Show or hide this help"),
+    ("l", "Filter by listener (commands below apply to it)"),
+    ("v", "Switch layout"),
+    ("+ / -", "Zoom the charts in or out"),
+    ("h", "Latency percentiles or heatmap"),
+    ("c", "Clients instead of routes"),
+    ("r", "Retries instead of routes"),
+    ("[ / ]", "Delay -10 ms / +10 ms"),
+    ("d", "Type in a new delay (ms or min-max)"),
+    ("e", "Switch injected errors off or on"),
+    ("p", "Pause with 503s, then refuse connections, then resume"),
+    ("f", "Next default response format"),
+    ("x", "Reset statistics and logs"),
+    ("", "Changes show in Server Stats and the summary"),
+];
+
+/// Aggregated stats for one route (HTTP path or gRPC method) and status
+#[derive(Debug, Default, Clone)]
+struct RouteStats {
+    count: u64,
+    total_ms: f64,
+    max_ms: f64,
+}
+
+/// Aggregated stats for one client (peer IP address or identity header value)
+#[derive(Debug, Default, Clone)]
+struct ClientStats {
+    requests: u64,
+    errors: u64,
+    total_ms: f64,
+    max_ms: f64,
+    /// (second since start, requests in that second, requests in the second before)
+    arrivals: (u64, u64, u64),
+    /// Requests currently being handled, as last sampled
+    in_flight: u64,
+}
+
+impl ClientStats {
+    fn push_log(&mut self, log: &RequestLog, second: u64) {
+        self.requests += 1;
+        if log.is_error() {
+            self.errors += 1;
+        }
+        self.total_ms += log.duration_ms;
+        self.max_ms = self.max_ms.max(log.duration_ms);
+        self.arrivals = match second.saturating_sub(self.arrivals.0) {
+            0 => (self.arrivals.0, self.arrivals.1 + 1, self.arrivals.2),
+            1 => (second, 1, self.arrivals.1),
+            _ => (second, 1, 0),
+        };
+    }
+
+    /// Requests in the last full second before `second`
+    fn rps(&self, second: u64) -> u64 {
+        match second.saturating_sub(self.arrivals.0) {
+            0 => self.arrivals.2,
+            1 => self.arrivals.1,
+            _ => 0,
+        }
+    }
+
+    /// Percentage of the client's requests that failed
+    fn error_rate(&self) -> f64 {
+        self.errors as f64 * 100.0 / self.requests.max(1) as f64
+    }
+}
+
+/// The attempts of one request, grouped by its idempotency key or fingerprint
+#[derive(Debug, Default, Clone)]
+struct RetryChain {
+    attempts: u32,
+    /// Waits between consecutive attempts, in milliseconds
+    intervals_ms: Vec<f64>,
+    /// Status label of the latest attempt
+    last_status: String,
+    /// The latest attempt got an injected error
+    last_injected: bool,
+    /// Time since the start when the latest attempt was logged
+    last_seen: Duration,
+}
+
+/// How clients retry: retry counts, waits between attempts and backoff patterns
+#[derive(Debug, Clone)]
+struct RetryStats {
+    /// Attempts after the first one of a request
+    retries: u64,
+    /// Retries that followed an injected error
+    after_injected: u64,
+    /// Total wait before retries, for calculating the average
+    interval_total_ms: f64,
+    /// Most attempts seen for one request
+    max_attempts: u32,
+    /// Retries per bucket over the configured window
+    history: History,
+    /// Recent requests by their retry key
+    chains: HashMap<String, RetryChain>,
+}
+
+impl RetryStats {
+    fn new(history: HistoryConfig) -> Self {
+        Self {
+            retries: 0,
+            after_injected: 0,
+            interval_total_ms: 0.0,
+            max_attempts: 0,
+            history: History::new(history),
+            chains: HashMap::new(),
+        }
+    }
+
+    fn push_log(&mut self, log: &RequestLog, elapsed: Duration) {
+        let Some(attempt) = &log.context.attempt else {
+            return;
+        };
+        if attempt.number == 1 {
+            self.chains.remove(&attempt.key);
+        } else {
+            self.retries += 1;
+            self.history.add();
+            self.interval_total_ms += attempt.interval_ms.unwrap_or(0.0);
+        }
+        self.max_attempts = self.max_attempts.max(attempt.number);
+        if !self.chains.contains_key(&attempt.key) && self.chains.len() >= MAX_RETRY_CHAINS {
+            // Make room by forgetting requests that weren't retried, then the stalest one
+            self.chains.retain(|_, chain| chain.attempts > 1);
+            let stalest = self
+                .chains
+                .iter()
+                .min_by_key(|(_, chain)| chain.last_seen)
+                .map(|(key, _)| key.clone());
+            if let Some(key) = stalest.filter(|_| self.chains.len() >= MAX_RETRY_CHAINS) {
+                self.chains.remove(&key);
+            }
+        }
+        let chain = self.chains.entry(attempt.key.clone()).or_default();
+        if attempt.number > 1 && chain.last_injected {
+            self.after_injected += 1;
+        }
+        chain.attempts = attempt.number;
+        chain.intervals_ms.extend(attempt.interval_ms);
+        chain.last_status = status_label(log);
+        chain.last_injected = log.outcome == Outcome::InjectedError;
+        chain.last_seen = elapsed;
+    }
+
+    /// Requests that were retried, most recently retried first
+    fn retried(&self) -> Vec<(&String, &RetryChain)> {
+        let mut chains: Vec<_> = self
+            .chains
+            .iter()
+            .filter(|(_, chain)| chain.attempts > 1)
+            .collect();
+        chains.sort_by(|a, b| b.1.last_seen.cmp(&a.1.last_seen).then_with(|| a.0.cmp(b.0)));
+        chains
+    }
+
+    /// How many retried requests follow each backoff pattern, most common first
+    fn backoff_patterns(&self) -> Vec<(BackoffPattern, usize)> {
+        let mut counts: HashMap<BackoffPattern, usize> = HashMap::new();
+        for chain in self.chains.values() {
+            if let Some(pattern) = classify(&chain.intervals_ms) {
+                *counts.entry(pattern).or_default() += 1;
+            }
+        }
+        let mut patterns: Vec<_> = counts.into_iter().collect();
+        patterns.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        patterns
+    }
+}
+
+/// The status label shown for a request: the gRPC status name or the HTTP status code
+/// (`refused` when the connection was closed without a response)
+fn status_label(log: &RequestLog) -> String {
+    match log.grpc_status {
+        Some(code) => code_name(code).to_string(),
+        None if log.status == 0 && log.outcome == Outcome::QueueFull => "refused".to_string(),
+        None if log.status == 0 => match log.context.sequence.as_ref().and_then(|s| s.action) {
+            Some(Action::Timeout) => "timeout".to_string(),
+            _ => "dropped".to_string(),
+        },
+        None => log.status.to_string(),
+    }
+}
 
-    /// The last 60 seconds of RPS counts for calculation (index 0 = "now")
-    rps_history: [u64; 60],
+/// Traffic statistics for all requests or for a single listener
+struct Stats {
+    /// Request counts over the configured window
+    rps_history: History,
 
-    /// The last 60 seconds of RPS counts for display.
-    /// When a second ticks and no new request comes in, this holds the previous value
-    /// so that the graph doesn't jump to 0.
-    rps_display_history: [u64; 60],
+    /// Rate-limited request counts over the configured window
+    throttled_history: History,
 
-    /// The timestamp (in seconds) for the last time we updated the RPS arrays
-    last_rps_update: i64,
+    /// Served latencies over the configured window
+    latency_history: History<LatencyBucket>,
+
+    /// Served latencies since the start, for the report's percentiles
+    latencies: LatencyHistogram,
 
     /// The total number of requests
     total_requests: u64,
 
-    /// The server's start time, for calculating uptime
-    start_time: Instant,
+    /// Number of injected error responses
+    injected_errors: u64,
+
+    /// Number of requests rejected for violating the OpenAPI spec (client bugs)
+    client_bugs: u64,
 
-    /// The server port, for display
-    port: u16,
+    /// Number of requests rejected by the rate limit
+    rate_limited: u64,
+
+    /// Number of proxied requests the upstream failed to answer
+    upstream_errors: u64,
+
+    /// Total time clients waited after being rate limited, in milliseconds
+    backoff_total_ms: f64,
+
+    /// Number of requests that followed a rate-limited one from the same client
+    backoff_samples: u64,
+
+    /// Retries that came before the rate limit allowed them
+    early_retries: u64,
 
     /// Current request delay in milliseconds
     current_delay: f64,
@@ -66,31 +305,209 @@ struct TuiData {
 
     /// Number of delay samples for calculating average
     delay_samples: u64,
+
+    /// Per route and status breakdown, keyed by (path or gRPC method, status label)
+    routes: HashMap<(String, String), RouteStats>,
+
+    /// Per client breakdown, keyed by peer IP address or identity header value
+    clients: HashMap<String, ClientStats>,
+
+    /// Time since the start, as of the last update
+    elapsed: Duration,
+
+    /// Total response body bytes sent
+    bytes_sent: u64,
+
+    /// Response body bytes sent per second, measured over the last sample
+    throughput: f64,
+
+    /// Responses and bytes per content encoding, indexed like `Encoding::ALL`
+    encodings: [EncodingTotals; 5],
+
+    /// Total time requests waited for a worker, in milliseconds
+    total_queue_ms: f64,
+
+    /// Worker pool gauges; `None` when concurrency isn't limited
+    workers: Option<WorkerGauges>,
+
+    /// Connection counters, as last sampled
+    connections: ConnectionGauges,
+
+    /// Peak open connections per bucket
+    open_history: History,
+
+    /// Peak requests in flight per bucket
+    in_flight_history: History,
+
+    /// Connections opened per bucket
+    opens_history: History,
+
+    /// Connections closed per bucket
+    closes_history: History,
+
+    /// Retried requests and their backoff
+    retries: RetryStats,
+
+    /// The listeners' cumulative counters when the statistics were last reset
+    baseline: Baseline,
 }
 
-impl TuiData {
-    fn new(start_time: Instant, port: u16) -> Self {
+/// Cumulative counters of the listeners as of a reset, so totals shown count from there
+#[derive(Debug, Default, Clone, Copy)]
+struct Baseline {
+    bytes_sent: u64,
+    encodings: [EncodingTotals; 5],
+    connections: ConnectionGauges,
+    rejected: u64,
+}
+
+/// Busy workers, queued and rejected requests of a worker pool
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct WorkerGauges {
+    max: u64,
+    in_flight: u64,
+    queued: u64,
+    /// `None` when requests wait without a bound
+    queue_size: Option<u64>,
+    rejected: u64,
+}
+
+impl WorkerGauges {
+    fn add(&mut self, other: &WorkerGauges) {
+        self.max += other.max;
+        self.in_flight += other.in_flight;
+        self.queued += other.queued;
+        self.queue_size = self.queue_size.zip(other.queue_size).map(|(a, b)| a + b);
+        self.rejected += other.rejected;
+    }
+}
+
+/// Open connections, connection totals and requests in flight of one or more listeners
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct ConnectionGauges {
+    open: u64,
+    opened: u64,
+    closed: u64,
+    requests: u64,
+    in_flight: u64,
+}
+
+impl ConnectionGauges {
+    fn sample(stats: &ConnectionStats) -> Self {
         Self {
-            logs: VecDeque::with_capacity(MAX_LOGS),
-            rps_history: [0; 60],
-            rps_display_history: [0; 60],
-            last_rps_update: 0,
+            open: stats.open.load(Ordering::Relaxed),
+            opened: stats.opened.load(Ordering::Relaxed),
+            closed: stats.closed.load(Ordering::Relaxed),
+            requests: stats.requests.load(Ordering::Relaxed),
+            in_flight: stats.in_flight.load(Ordering::Relaxed),
+        }
+    }
+
+    fn add(&mut self, other: &ConnectionGauges) {
+        self.open += other.open;
+        self.opened += other.opened;
+        self.closed += other.closed;
+        self.requests += other.requests;
+        self.in_flight += other.in_flight;
+    }
+
+    /// The totals counted since `baseline`; open connections and requests in flight stay as is
+    fn since(&self, baseline: &ConnectionGauges) -> Self {
+        Self {
+            opened: self.opened.saturating_sub(baseline.opened),
+            closed: self.closed.saturating_sub(baseline.closed),
+            requests: self.requests.saturating_sub(baseline.requests),
+            ..*self
+        }
+    }
+
+    /// Average requests per connection (keep-alive reuse); `None` before the first connection
+    fn requests_per_connection(&self) -> Option<f64> {
+        (self.opened > 0).then(|| self.requests as f64 / self.opened as f64)
+    }
+}
+
+/// Responses and bytes sent with one content encoding
+#[derive(Debug, Default, Clone, Copy)]
+struct EncodingTotals {
+    responses: u64,
+    raw_bytes: u64,
+    encoded_bytes: u64,
+}
+
+impl EncodingTotals {
+    fn add(&mut self, other: &EncodingTotals) {
+        self.responses += other.responses;
+        self.raw_bytes += other.raw_bytes;
+        self.encoded_bytes += other.encoded_bytes;
+    }
+
+    fn since(&self, baseline: &EncodingTotals) -> Self {
+        Self {
+            responses: self.responses.saturating_sub(baseline.responses),
+            raw_bytes: self.raw_bytes.saturating_sub(baseline.raw_bytes),
+            encoded_bytes: self.encoded_bytes.saturating_sub(baseline.encoded_bytes),
+        }
+    }
+}
+
+impl Stats {
+    fn new(history: HistoryConfig) -> Self {
+        Self {
+            rps_history: History::new(history),
+            throttled_history: History::new(history),
+            latency_history: History::new(history),
+            latencies: LatencyHistogram::default(),
             total_requests: 0,
-            start_time,
-            port,
+            injected_errors: 0,
+            client_bugs: 0,
+            rate_limited: 0,
+            upstream_errors: 0,
+            backoff_total_ms: 0.0,
+            backoff_samples: 0,
+            early_retries: 0,
             current_delay: 0.0,
             min_delay: f64::MAX,
             max_delay: 0.0,
             total_delay: 0.0,
             delay_samples: 0,
+            routes: HashMap::new(),
+            clients: HashMap::new(),
+            elapsed: Duration::ZERO,
+            bytes_sent: 0,
+            throughput: 0.0,
+            encodings: [EncodingTotals::default(); 5],
+            total_queue_ms: 0.0,
+            workers: None,
+            connections: ConnectionGauges::default(),
+            open_history: History::new(history),
+            in_flight_history: History::new(history),
+            opens_history: History::new(history),
+            closes_history: History::new(history),
+            retries: RetryStats::new(history),
+            baseline: Baseline::default(),
         }
     }
 
-    /// Add a new request log and update counters.
-    fn push_log(&mut self, log: RequestLog) {
+    /// Update counters for a new request.
+    fn push_log(&mut self, log: &RequestLog) {
         self.total_requests += 1;
-        if self.logs.len() == MAX_LOGS {
-            self.logs.pop_front();
+        match log.outcome {
+            Outcome::InjectedError => self.injected_errors += 1,
+            Outcome::ValidationError(_) => self.client_bugs += 1,
+            Outcome::RateLimited => {
+                self.rate_limited += 1;
+                self.throttled_history.add();
+            }
+            Outcome::UpstreamError => self.upstream_errors += 1,
+            Outcome::Served | Outcome::QueueFull | Outcome::Paused => {}
+        }
+        if let Some(backoff_ms) = log.context.backoff_ms {
+            self.backoff_total_ms += backoff_ms;
+            self.backoff_samples += 1;
+            if log.context.early_retry {
+                self.early_retries += 1;
+            }
         }
 
         // Update delay statistics
@@ -100,8 +517,60 @@ impl TuiData {
         self.max_delay = self.max_delay.max(delay);
         self.total_delay += delay;
         self.delay_samples += 1;
+        self.total_queue_ms += log.context.queue_ms;
+        self.latency_history.add_latency(delay);
+        self.latencies.add(delay);
 
-        self.logs.push_back(log);
+        // Update the route breakdown, folding new keys into "(other)" once full
+        let mut key = (log.path.clone(), status_label(log));
+        if self.routes.len() >= MAX_ROUTES && !self.routes.contains_key(&key) {
+            key.0 = "(other)".to_string();
+        }
+        let route = self.routes.entry(key).or_default();
+        route.count += 1;
+        route.total_ms += delay;
+        route.max_ms = route.max_ms.max(delay);
+
+        let second = self.elapsed.as_secs();
+        self.client_entry(&log.context.client).push_log(log, second);
+        self.retries.push_log(log, self.elapsed);
+
+        self.rps_history.add();
+    }
+
+    /// The stats of a client, folding new clients into "(other)" once full
+    fn client_entry(&mut self, client: &str) -> &mut ClientStats {
+        let key = if self.clients.len() >= MAX_CLIENTS && !self.clients.contains_key(client) {
+            "(other)"
+        } else {
+            client
+        };
+        self.clients.entry(key.to_string()).or_default()
+    }
+
+    /// Set the requests in flight per client, adding clients whose first requests are
+    /// still in flight
+    fn set_clients_in_flight(&mut self, in_flight: &HashMap<String, u64>) {
+        for client in self.clients.values_mut() {
+            client.in_flight = 0;
+        }
+        for (client, &count) in in_flight {
+            self.client_entry(client).in_flight += count;
+        }
+    }
+
+    /// Return the client breakdown sorted by request count (busiest first)
+    fn client_breakdown(&self) -> Vec<(&String, &ClientStats)> {
+        let mut clients: Vec<_> = self.clients.iter().collect();
+        clients.sort_by(|a, b| b.1.requests.cmp(&a.1.requests).then_with(|| a.0.cmp(b.0)));
+        clients
+    }
+
+    /// Return the route breakdown sorted by request count (busiest first)
+    fn route_breakdown(&self) -> Vec<(&(String, String), &RouteStats)> {
+        let mut routes: Vec<_> = self.routes.iter().collect();
+        routes.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(b.0)));
+        routes
     }
 
     /// Get the minimum request delay seen in milliseconds
@@ -118,246 +587,1185 @@ impl TuiData {
         self.max_delay
     }
 
+    /// Get the average time requests waited for a worker in milliseconds
+    fn get_avg_queue_wait(&self) -> f64 {
+        if self.delay_samples == 0 {
+            0.0
+        } else {
+            self.total_queue_ms / self.delay_samples as f64
+        }
+    }
+
     /// Get the average request delay in milliseconds
     fn get_avg_delay(&self) -> f64 {
         if self.delay_samples == 0 {
             0.0
         } else {
-            self.total_delay as f64 / self.delay_samples as f64
+            self.total_delay / self.delay_samples as f64
         }
     }
 
-    /// Update the RPS data.
-    ///
-    /// For the calculation array we shift and clear new slots to 0.
-    /// For the display array we shift and fill new slots with the last known value
-    /// so that the graph doesn’t jump to 0.
-    fn update_rps(&mut self, now: i64) {
-        if self.last_rps_update == 0 {
-            self.last_rps_update = now;
-            return;
-        }
+    /// Start over, keeping the last samples of the listeners' counters so rates stay right,
+    /// and counting their totals from here
+    fn reset(&mut self, history: HistoryConfig) {
+        let mut fresh = Stats::new(history);
+        fresh.update_rps(self.elapsed);
+        fresh.bytes_sent = self.bytes_sent;
+        fresh.throughput = self.throughput;
+        fresh.encodings = self.encodings;
+        fresh.workers = self.workers;
+        fresh.connections = self.connections;
+        fresh.baseline = Baseline {
+            bytes_sent: self.bytes_sent,
+            encodings: self.encodings,
+            connections: self.connections,
+            rejected: self.workers.map_or(0, |workers| workers.rejected),
+        };
+        *self = fresh;
+    }
 
-        let diff = now - self.last_rps_update;
-        if diff <= 0 {
-            return;
-        }
+    /// Response body bytes sent since the last reset
+    fn sent(&self) -> u64 {
+        self.bytes_sent.saturating_sub(self.baseline.bytes_sent)
+    }
 
-        let diff = diff.min(60) as usize;
-        if diff >= 60 {
-            self.rps_history = [0; 60];
-            self.rps_display_history = [0; 60];
-        } else {
-            // For calculation: shift right and set new slots to 0.
-            self.rps_history.copy_within(0..60 - diff, diff);
-            for i in 0..diff {
-                self.rps_history[i] = 0;
-            }
+    /// Connection counters, with totals since the last reset
+    fn connection_totals(&self) -> ConnectionGauges {
+        self.connections.since(&self.baseline.connections)
+    }
 
-            // For display: shift right and set new slots to 0.
-            self.rps_display_history.copy_within(0..60 - diff, diff);
-            for i in 0..diff {
-                self.rps_display_history[i] = 0;
-            }
+    /// Requests the worker pool rejected since the last reset
+    fn rejected(&self) -> u64 {
+        self.workers.map_or(0, |workers| {
+            workers.rejected.saturating_sub(self.baseline.rejected)
+        })
+    }
+
+    /// Move the RPS, latency and connection histories to `elapsed` since the start
+    fn update_rps(&mut self, elapsed: Duration) {
+        self.elapsed = elapsed;
+        self.rps_history.advance(elapsed);
+        self.throttled_history.advance(elapsed);
+        self.latency_history.advance(elapsed);
+        self.open_history.advance(elapsed);
+        self.in_flight_history.advance(elapsed);
+        self.opens_history.advance(elapsed);
+        self.closes_history.advance(elapsed);
+        self.retries.history.advance(elapsed);
+    }
+
+    /// Record a new sample of the connection counters in the histories
+    fn record_connections(&mut self, gauges: ConnectionGauges) {
+        self.opens_history
+            .add_n(gauges.opened.saturating_sub(self.connections.opened));
+        self.closes_history
+            .add_n(gauges.closed.saturating_sub(self.connections.closed));
+        self.open_history.peak(gauges.open);
+        self.in_flight_history.peak(gauges.in_flight);
+        self.connections = gauges;
+    }
+
+    /// Open connections, connection churn, keep-alive reuse and requests in flight
+    fn connection_summary(&self) -> String {
+        let connections = self.connection_totals();
+        let reuse = connections
+            .requests_per_connection()
+            .map_or_else(|| "-".to_string(), |reuse| format!("{:.1}", reuse));
+        format!(
+            "Connections: {} open, {} total\nOpened/Closed: {}/s, {}/s\nReqs/Conn: {}, In Flight: {}",
+            connections.open,
+            connections.opened,
+            format_rate(self.opens_history.last_second()),
+            format_rate(self.closes_history.last_second()),
+            reuse,
+            connections.in_flight
+        )
+    }
+
+    /// Allowed vs. throttled requests in the last full second, and how clients
+    /// backed off after being throttled; `None` until a request is rate limited
+    fn rate_limit_summary(&self) -> Option<String> {
+        if self.rate_limited == 0 {
+            return None;
         }
+        let throttled = self.throttled_history.last_second();
+        let allowed = (self.rps_history.last_second() - throttled).max(0.0);
+        let backoff = if self.backoff_samples == 0 {
+            "-".to_string()
+        } else {
+            format!(
+                "avg {:.0} ms, {}/{} retries early",
+                self.backoff_total_ms / self.backoff_samples as f64,
+                self.early_retries,
+                self.backoff_samples
+            )
+        };
+        Some(format!(
+            "Allowed: {}/s, Throttled: {}/s ({} total)\nBack-off: {}",
+            format_rate(allowed),
+            format_rate(throttled),
+            self.rate_limited,
+            backoff
+        ))
+    }
 
-        self.last_rps_update = now;
+    /// Whether retries surge: many retries in the last full second, outnumbering first attempts
+    fn retry_storm(&self) -> bool {
+        let retries = self.retries.history.last_second();
+        retries >= RETRY_STORM_RPS && retries * 2.0 > self.rps_history.last_second()
     }
 
-    /// Increment the RPS counter for the current second in both arrays.
-    fn increment_rps(&mut self) {
-        self.rps_history[0] += 1;
-        self.rps_display_history[0] = self.rps_history[0];
+    /// Retry counts and rate, and the most common backoff; `None` until a request is retried
+    fn retry_summary(&self) -> Option<String> {
+        let retries = &self.retries;
+        if retries.retries == 0 {
+            return None;
+        }
+        let storm = if self.retry_storm() {
+            " RETRY STORM"
+        } else {
+            ""
+        };
+        let backoff = retries
+            .backoff_patterns()
+            .first()
+            .map_or_else(|| "-".to_string(), |(pattern, _)| pattern.to_string());
+        Some(format!(
+            "Retries: {}/s, {} total{}\nAfter Injected Errors: {}, Avg Wait: {:.0} ms\nBackoff: {}",
+            format_rate(retries.history.last_second()),
+            retries.retries,
+            storm,
+            retries.after_injected,
+            retries.interval_total_ms / retries.retries as f64,
+            backoff
+        ))
     }
 
-    /// Return the server uptime in seconds.
-    fn uptime_seconds(&self) -> u64 {
-        self.start_time.elapsed().as_secs()
+    /// Responses per negotiated encoding with compression ratios, e.g. `gzip 12 (3.20x)`
+    fn encoding_summary(&self) -> String {
+        let summary: Vec<String> = Encoding::ALL
+            .into_iter()
+            .zip(
+                self.encodings
+                    .iter()
+                    .zip(&self.baseline.encodings)
+                    .map(|(totals, baseline)| totals.since(baseline)),
+            )
+            .filter(|(_, totals)| totals.responses > 0)
+            .map(|(encoding, totals)| match totals.encoded_bytes {
+                0 => format!("{} {}", encoding.name(), totals.responses),
+                encoded => format!(
+                    "{} {} ({:.2}x)",
+                    encoding.name(),
+                    totals.responses,
+                    totals.raw_bytes as f64 / encoded as f64
+                ),
+            })
+            .collect();
+        if summary.is_empty() {
+            "-".to_string()
+        } else {
+            summary.join(", ")
+        }
     }
 
-    /// Compute statistics for the RPS over the last 60 seconds.
-    /// Returns (min, max, average, median, 95th percentile).
-    fn compute_rps_stats(&self) -> (u64, u64, f64, u64, u64) {
-        // Exclude the current second from the calculation
-        let mut data: Vec<u64> = self.rps_history[1..].to_vec();
-        data.sort_unstable();
-        let greater_than_zero: Vec<u64> = data.iter().filter(|&&x| x > 0).cloned().collect();
+    /// Compute statistics for the RPS over the completed buckets within `window`.
+    /// Returns (min, max, average, median, 90th percentile).
+    fn compute_rps_stats(&self, window: Duration) -> (f64, f64, f64, f64, f64) {
+        let mut data = self.rps_history.rates(window);
+        data.sort_unstable_by(f64::total_cmp);
         // min excludes 0 values
-        let min = *greater_than_zero.first().unwrap_or(&0);
+        let min = data.iter().copied().find(|&x| x > 0.0).unwrap_or(0.0);
         let max = *data.last().unwrap();
-        let sum: u64 = data.iter().sum();
-        let count = data.len() as f64;
-        let avg = sum as f64 / count;
+        let avg = data.iter().sum::<f64>() / data.len() as f64;
         let median = if data.len() % 2 == 1 {
             data[data.len() / 2]
         } else {
             let mid = data.len() / 2;
-            (data[mid - 1] + data[mid]) / 2
+            (data[mid - 1] + data[mid]) / 2.0
         };
         let idx_90 = ((data.len() as f64) * 0.90).ceil() as usize;
-        let idx_90 = if idx_90 >= data.len() {
-            data.len() - 1
-        } else {
-            idx_90
-        };
-        let p90 = data[idx_90];
+        let p90 = data[idx_90.min(data.len() - 1)];
         (min, max, avg, median, p90)
     }
 }
 
-/// Main TUI function (runs in a blocking thread)
-///
-/// Receives `AppEvent` messages on `rx` and updates the TUI accordingly.
-pub fn run_tui(mut rx: Receiver<AppEvent>, port: u16) -> anyhow::Result<()> {
-    enable_raw_mode()?;
-    let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-
-    let start_time = Instant::now();
-    let mut data = TuiData::new(start_time, port);
+/// A request rate without trailing zeros, e.g. `12`, `0.3`
+fn format_rate(rate: f64) -> String {
+    if rate.fract() == 0.0 {
+        format!("{:.0}", rate)
+    } else {
+        format!("{:.1}", rate)
+    }
+}
 
-    let tick_rate = Duration::from_millis(200);
-    let mut last_tick = Instant::now();
+/// A chart window or bucket width, e.g. `100ms`, `30s`, `15m`, `1h`
+fn format_span(span: Duration) -> String {
+    let secs = span.as_secs();
+    match secs {
+        _ if secs == 0 || span.subsec_millis() != 0 => format!("{}ms", span.as_millis()),
+        _ if secs.is_multiple_of(3600) => format!("{}h", secs / 3600),
+        _ if secs.is_multiple_of(60) => format!("{}m", secs / 60),
+        _ => format!("{}s", secs),
+    }
+}
 
-    loop {
-        let now_ts = Utc::now().timestamp();
-        data.update_rps(now_ts);
+/// The completed buckets of the run within a history window, for the report
+struct Timeline {
+    window: Duration,
+    /// Completed buckets since the start, up to the window's
+    buckets: usize,
+    /// Seconds from the start to the first of them
+    start: f64,
+}
 
-        while let Ok(event) = rx.try_recv() {
-            match event {
-                AppEvent::RequestReceived(log) => {
-                    data.push_log(log);
-                    data.increment_rps();
-                }
-                // disable warning for unreachable pattern
-                #[allow(unreachable_patterns)]
-                _ => (),
-            }
-        }
+impl Timeline {
+    /// Counts per second of the buckets, oldest first
+    fn rates(&self, history: &History) -> Vec<f64> {
+        let mut rates = history.rates(self.window);
+        rates.truncate(self.buckets);
+        rates.reverse();
+        rates
+    }
 
-        terminal.draw(|frame| {
-            draw_ui(frame, &data);
-        })?;
+    /// Latencies of the buckets, oldest first
+    fn latencies(&self, stats: &Stats) -> Vec<LatencyBucket> {
+        let mut buckets = stats.latency_history.buckets(self.window);
+        buckets.truncate(self.buckets);
+        buckets.reverse();
+        buckets
+    }
+}
 
-        if crossterm::event::poll(Duration::from_millis(1))? {
-            if let CEvent::Key(key) = event::read()? {
-                if key.code == KeyCode::Char('q')
-                    || (key.code == KeyCode::Char('c')
-                        && key.modifiers.contains(KeyModifiers::CONTROL))
-                {
-                    break;
-                }
-            }
-        }
+/// What the breakdown next to the logs shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Breakdown {
+    Routes,
+    Clients,
+    Retries,
+}
 
-        if last_tick.elapsed() >= tick_rate {
-            last_tick = Instant::now();
+impl Breakdown {
+    /// Switch to `other`, or back to routes if it is already shown
+    fn toggle(self, other: Breakdown) -> Breakdown {
+        if self == other {
+            Breakdown::Routes
+        } else {
+            other
         }
     }
+}
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+/// Data that the TUI thread holds locally
+struct TuiData {
+    /// Ring buffer of the most recent logs
+    logs: VecDeque<RequestLog>,
 
-    Ok(())
-}
+    /// Statistics across all listeners
+    all: Stats,
 
-/// Draw the entire UI layout.
-fn draw_ui<B: ratatui::backend::Backend>(frame: &mut ratatui::Frame<B>, data: &TuiData) {
-    // Vertical layout: top (stats), middle (chart), bottom (logs)
-    let vertical_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Percentage(30),
-            Constraint::Percentage(40),
-            Constraint::Percentage(30),
-        ])
-        .split(frame.size());
-
-    // Top area split into three columns: RPS Stats, Delay Stats, and Server Stats.
-    let top_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage(33),
-            Constraint::Percentage(34),
-            Constraint::Percentage(33),
-        ])
-        .split(vertical_chunks[0]);
+    /// Statistics per listener, keyed by listener name
+    by_listener: HashMap<String, Stats>,
 
-    // Left widget: Detailed RPS statistics.
-    let (rps_min, rps_max, rps_avg, rps_median, rps_p90) = data.compute_rps_stats();
-    let rps_stats_text = format!(
-        "Min RPS: {}\nMax RPS: {}\nAvg RPS: {:.2}\nMedian RPS: {}\n90th Percentile: {}",
-        rps_min, rps_max, rps_avg, rps_median, rps_p90
-    );
-    let rps_stats_paragraph = Paragraph::new(rps_stats_text)
-        .block(Block::default().borders(Borders::ALL).title("RPS Stats"));
-    frame.render_widget(rps_stats_paragraph, top_chunks[0]);
+    /// The listeners being served, for display and filtering
+    listeners: Vec<ListenerInfo>,
 
-    // Middle widget: Delay statistics.
-    let delay_stats_text = format!(
-        "Min Delay: {:.3} ms\nMax Delay: {:.3} ms\nAvg Delay: {:.3} ms",
-        data.get_min_delay(),
-        data.get_max_delay(),
-        data.get_avg_delay()
-    );
-    let delay_stats_paragraph = Paragraph::new(delay_stats_text)
-        .block(Block::default().borders(Borders::ALL).title("Delay Stats"));
-    frame.render_widget(delay_stats_paragraph, top_chunks[1]);
+    /// Index of the listener the view is filtered to (`None` = all listeners)
+    selected_listener: Option<usize>,
 
-    // Right widget: General server stats.
-    let server_stats_text = format!(
-        "Uptime: {}s\nTotal Requests: {}\nURL: http://localhost:{}",
-        data.uptime_seconds(),
-        data.total_requests,
-        data.port
-    );
-    let server_stats_paragraph = Paragraph::new(server_stats_text)
-        .block(Block::default().borders(Borders::ALL).title("Server Stats"));
-    frame.render_widget(server_stats_paragraph, top_chunks[2]);
+    /// Source of time for the RPS windows, throughput and uptime
+    clock: Arc<dyn Clock>,
 
-    // RPS chart in the middle remains similar.
-    let chart_data: Vec<(f64, f64)> = data.rps_display_history[1..]
-        .iter()
-        .enumerate()
-        .map(|(i, &count)| (i as f64, count as f64))
-        .collect();
+    /// The server's start time, for calculating uptime
+    start_time: Instant,
 
-    let datasets = vec![
-        Dataset::default()
-            .name("RPS")
-            .marker(symbols::Marker::Braille)
-            .graph_type(GraphType::Line)
-            .style(Style::default().fg(Color::Green))
-            .data(&chart_data),
-    ];
+    /// When the listeners' byte counters were last sampled
+    last_throughput_sample: Instant,
 
-    let max_rps = chart_data.iter().map(|(_x, y)| *y).fold(0.0, f64::max);
-    let y_max = ((max_rps * 1.2).ceil() / 10.0).ceil() * 10.0;
-    let y_max = y_max.max(10.0);
+    /// Whether gRPC methods are configured, so the breakdown shows methods
+    grpc_mode: bool,
 
-    let chart = Chart::new(datasets)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("RPS (Last 60s)"),
-        )
-        .x_axis(
-            Axis::default()
+    /// The scenario timeline being played, if any
+    scenario: Option<Arc<Scenario>>,
+
+    /// Seed of the run's random decisions
+    seed: Option<u64>,
+
+    /// Bucket width and window of the RPS histories
+    history: HistoryConfig,
+
+    /// Index of the RPS chart's window in `history.zoom_levels()`
+    zoom: usize,
+
+    /// Whether latency is shown as a heatmap instead of percentile lines
+    latency_heatmap: bool,
+
+    /// Whether the breakdown shows routes, clients or retries
+    breakdown: Breakdown,
+
+    /// The layouts to cycle through, and the one shown
+    layouts: Layouts,
+
+    /// Where commands to the server go; `None` when the TUI only watches
+    control_tx: Option<Sender<ControlEvent>>,
+
+    /// Whether the keybindings are shown over the dashboard
+    show_help: bool,
+
+    /// The delay being typed in, while the prompt is open
+    prompt: Option<String>,
+
+    /// Why the last delay typed in was rejected
+    prompt_error: Option<String>,
+}
+
+impl TuiData {
+    fn new(
+        clock: Arc<dyn Clock>,
+        history: HistoryConfig,
+        listeners: Vec<ListenerInfo>,
+        grpc_mode: bool,
+    ) -> Self {
+        let start_time = clock.now();
+        let by_listener = listeners
+            .iter()
+            .map(|listener| (listener.name.clone(), Stats::new(history)))
+            .collect();
+        Self {
+            logs: VecDeque::with_capacity(MAX_LOGS),
+            all: Stats::new(history),
+            by_listener,
+            listeners,
+            selected_listener: None,
+            clock,
+            start_time,
+            last_throughput_sample: start_time,
+            grpc_mode,
+            scenario: None,
+            seed: None,
+            history,
+            // Show the whole configured window until zoomed in
+            zoom: history.zoom_levels().len() - 1,
+            latency_heatmap: false,
+            breakdown: Breakdown::Routes,
+            layouts: Layouts::default(),
+            control_tx: None,
+            show_help: false,
+            prompt: None,
+            prompt_error: None,
+        }
+    }
+
+    /// Add a new request log and update counters.
+    fn push_log(&mut self, log: RequestLog) {
+        if self.logs.len() == MAX_LOGS {
+            self.logs.pop_front();
+        }
+        self.all.push_log(&log);
+        self.by_listener
+            .entry(log.listener.clone())
+            .or_insert_with(|| Stats::new(self.history))
+            .push_log(&log);
+        self.logs.push_back(log);
+    }
+
+    /// Update the RPS data of every view.
+    fn update_rps(&mut self) {
+        let elapsed = self.clock.now().saturating_duration_since(self.start_time);
+        self.all.update_rps(elapsed);
+        for stats in self.by_listener.values_mut() {
+            stats.update_rps(elapsed);
+        }
+    }
+
+    /// The window the RPS chart and statistics cover
+    fn chart_window(&self) -> Duration {
+        self.history.zoom_levels()[self.zoom]
+    }
+
+    /// Show a shorter (`zoom_in`) or longer window in the RPS chart
+    fn zoom(&mut self, zoom_in: bool) {
+        let levels = self.history.zoom_levels().len();
+        self.zoom = if zoom_in {
+            self.zoom.saturating_sub(1)
+        } else {
+            (self.zoom + 1).min(levels - 1)
+        };
+    }
+
+    /// Sample the listeners' byte counters and update the throughput of every view
+    fn update_throughput(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+        let mut total = 0;
+        for listener in &self.listeners {
+            let sent = listener.bytes_sent.load(Ordering::Relaxed);
+            total += sent;
+            let stats = self
+                .by_listener
+                .entry(listener.name.clone())
+                .or_insert_with(|| Stats::new(self.history));
+            stats.throughput = sent.saturating_sub(stats.bytes_sent) as f64 / seconds;
+            stats.bytes_sent = sent;
+        }
+        self.all.throughput = total.saturating_sub(self.all.bytes_sent) as f64 / seconds;
+        self.all.bytes_sent = total;
+    }
+
+    /// Copy the listeners' compression counters into every view
+    fn update_encodings(&mut self) {
+        let mut all = [EncodingTotals::default(); 5];
+        for listener in &self.listeners {
+            let stats = self
+                .by_listener
+                .entry(listener.name.clone())
+                .or_insert_with(|| Stats::new(self.history));
+            for (i, encoding) in Encoding::ALL.into_iter().enumerate() {
+                let counters = listener.compression.get(encoding);
+                stats.encodings[i] = EncodingTotals {
+                    responses: counters.responses.load(Ordering::Relaxed),
+                    raw_bytes: counters.raw_bytes.load(Ordering::Relaxed),
+                    encoded_bytes: counters.encoded_bytes.load(Ordering::Relaxed),
+                };
+                all[i].add(&stats.encodings[i]);
+            }
+        }
+        self.all.encodings = all;
+    }
+
+    /// Sample the listeners' connection counters into every view
+    fn update_connections(&mut self) {
+        let mut all = ConnectionGauges::default();
+        let mut all_clients = HashMap::new();
+        for listener in &self.listeners {
+            let gauges = ConnectionGauges::sample(&listener.connections);
+            let clients = listener.connections.clients_in_flight();
+            let stats = self
+                .by_listener
+                .entry(listener.name.clone())
+                .or_insert_with(|| Stats::new(self.history));
+            stats.record_connections(gauges);
+            stats.set_clients_in_flight(&clients);
+            all.add(&gauges);
+            for (client, count) in clients {
+                *all_clients.entry(client).or_default() += count;
+            }
+        }
+        self.all.record_connections(all);
+        self.all.set_clients_in_flight(&all_clients);
+    }
+
+    /// Copy the listeners' worker pool gauges into every view
+    fn update_workers(&mut self) {
+        let mut all = None;
+        for listener in &self.listeners {
+            let Some(limit) = &listener.concurrency else {
+                continue;
+            };
+            let gauges = WorkerGauges {
+                max: limit.max as u64,
+                in_flight: limit.in_flight.load(Ordering::Relaxed),
+                queued: limit.queued.load(Ordering::Relaxed),
+                queue_size: (limit.overflow != QueueOverflow::Wait)
+                    .then_some(limit.queue_size as u64),
+                rejected: limit.rejected.load(Ordering::Relaxed),
+            };
+            self.by_listener
+                .entry(listener.name.clone())
+                .or_insert_with(|| Stats::new(self.history))
+                .workers = Some(gauges);
+            all = Some(match all {
+                Some(mut sum) => {
+                    WorkerGauges::add(&mut sum, &gauges);
+                    sum
+                }
+                None => gauges,
+            });
+        }
+        self.all.workers = all;
+    }
+
+    /// The listener the view is filtered to, if any
+    fn selected(&self) -> Option<&ListenerInfo> {
+        self.selected_listener.and_then(|i| self.listeners.get(i))
+    }
+
+    /// Statistics for the current view
+    fn view(&self) -> &Stats {
+        self.selected()
+            .and_then(|listener| self.by_listener.get(&listener.name))
+            .unwrap_or(&self.all)
+    }
+
+    /// Cycle the view through all listeners, then back to the aggregate
+    fn cycle_listener(&mut self) {
+        self.selected_listener = match self.selected_listener {
+            _ if self.listeners.len() < 2 => None,
+            None => Some(0),
+            Some(i) if i + 1 < self.listeners.len() => Some(i + 1),
+            Some(_) => None,
+        };
+    }
+
+    /// The most recent logs in the current view, newest first
+    fn visible_logs(&self) -> impl Iterator<Item = &RequestLog> {
+        let selected = self.selected().map(|listener| listener.name.as_str());
+        self.logs
+            .iter()
+            .rev()
+            .filter(move |log| selected.is_none_or(|name| log.listener == name))
+    }
+
+    /// The current scenario phase and the time to the next one
+    fn scenario_status(&self) -> Option<String> {
+        let scenario = self.scenario.as_ref()?;
+        Some(match scenario.current() {
+            Some(phase) => format!(
+                "Phase: {} ({}/{}), next in {}s",
+                phase.name,
+                phase.index + 1,
+                scenario.phase_count(),
+                phase.remaining.as_secs_f64().ceil()
+            ),
+            None => "Phase: scenario finished".to_string(),
+        })
+    }
+
+    /// Scenario phase boundaries on the RPS chart, as vertical lines named after
+    /// the phase that began there
+    fn phase_markers(&self, y_max: f64) -> Vec<(String, [(f64, f64); 2])> {
+        let Some(scenario) = &self.scenario else {
+            return Vec::new();
+        };
+        let window = self.chart_window();
+        // The chart starts with the last completed bucket
+        let width = self.all.rps_history.bucket_width(window).as_secs_f64();
+        let x_max = window.as_secs_f64() - width;
+        scenario
+            .boundaries(scenario.elapsed(), window)
+            .into_iter()
+            .map(|(ago, name)| (ago.as_secs_f64() - width, name))
+            .filter(|(x, _)| (0.0..=x_max).contains(x))
+            .map(|(x, name)| (format!("| {}", name), [(x, 0.0), (x, y_max)]))
+            .collect()
+    }
+
+    /// Return the server uptime in seconds.
+    fn uptime_seconds(&self) -> u64 {
+        self.clock
+            .now()
+            .saturating_duration_since(self.start_time)
+            .as_secs()
+    }
+
+    /// Send a command to the listener in view, or to every listener
+    fn send(&self, command: Command) {
+        if let Some(tx) = &self.control_tx {
+            let listener = self.selected().map(|listener| listener.name.clone());
+            let _ = tx.try_send(ControlEvent { listener, command });
+        }
+    }
+
+    /// Type a key into the delay prompt: Enter sets the delay, Esc closes the prompt
+    fn edit_prompt(&mut self, key: KeyCode) {
+        let Some(input) = &mut self.prompt else {
+            return;
+        };
+        match key {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Enter => match DelayConfig::parse(input.trim()) {
+                Ok(delay) => {
+                    let (min, max) = delay.range();
+                    self.send(Command::SetDelay(min, max));
+                    self.prompt = None;
+                }
+                Err(err) => self.prompt_error = Some(err.to_string()),
+            },
+            _ => {}
+        }
+    }
+
+    /// Clear the statistics and logs of every view
+    fn reset_stats(&mut self) {
+        self.logs.clear();
+        self.all.reset(self.history);
+        for stats in self.by_listener.values_mut() {
+            stats.reset(self.history);
+        }
+    }
+
+    /// What was changed from the TUI on the listeners in view, if anything
+    fn controls_summary(&self) -> Option<String> {
+        if let Some(listener) = self.selected() {
+            return listener.controls.summary();
+        }
+        let changed: Vec<String> = self
+            .listeners
+            .iter()
+            .filter_map(|listener| {
+                let summary = listener.controls.summary()?;
+                Some(match self.listeners.len() {
+                    1 => summary,
+                    _ => format!("{}: {}", listener.name, summary),
+                })
+            })
+            .collect();
+        (!changed.is_empty()).then(|| changed.join("; "))
+    }
+
+    /// The run as a report, from the same statistics as the dashboard. `settings` are
+    /// the command-line settings to list with the listeners and seed.
+    fn report(&self, settings: &[(String, String)]) -> Report {
+        let stats = &self.all;
+        // The shortest window covering the run, for the finest buckets it has
+        let window = self
+            .history
+            .zoom_levels()
+            .into_iter()
+            .find(|&window| window >= stats.elapsed)
+            .unwrap_or(self.history.window);
+        let width = stats.rps_history.bucket_width(window);
+        let completed = (stats.elapsed.as_millis() / width.as_millis()) as usize;
+        let buckets = completed.min((window.as_millis() / width.as_millis()) as usize);
+        let timeline = Timeline {
+            window,
+            buckets,
+            start: (completed - buckets) as f64 * width.as_secs_f64(),
+        };
+
+        let mut config = vec![(
+            "Listeners".to_string(),
+            self.listeners
+                .iter()
+                .map(|listener| match &listener.upstream {
+                    Some(upstream) => {
+                        format!("{}: {} -> {}", listener.name, listener.address, upstream)
+                    }
+                    None => format!("{}: {}", listener.name, listener.address),
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        )];
+        if let Some(seed) = self.seed {
+            config.push(("Seed".to_string(), seed.to_string()));
+        }
+        config.extend(settings.iter().cloned());
+        if let Some(controls) = self.controls_summary() {
+            config.push(("Changed in the TUI".to_string(), controls));
+        }
+
+        let markers: Vec<(f64, String)> =
+            self.scenario.as_ref().map_or_else(Vec::new, |scenario| {
+                let elapsed = stats.elapsed.as_secs_f64();
+                scenario
+                    .boundaries(scenario.elapsed(), timeline.window)
+                    .into_iter()
+                    .map(|(ago, name)| (elapsed - ago.as_secs_f64(), name.to_string()))
+                    .filter(|(at, _)| *at >= timeline.start)
+                    .rev()
+                    .collect()
+            });
+        let latencies = timeline.latencies(stats);
+        let latency_line = |name, color, p| report::Line {
+            name,
+            color,
+            values: latencies
+                .iter()
+                .map(|bucket| bucket.percentile(p))
+                .collect(),
+        };
+        let charts = vec![
+            report::Chart {
+                title: "Requests per second".to_string(),
+                unit: "req/s",
+                start: timeline.start,
+                bucket: width.as_secs_f64(),
+                lines: vec![
+                    report::Line {
+                        name: "rps",
+                        color: "#2a7ae2",
+                        values: timeline
+                            .rates(&stats.rps_history)
+                            .into_iter()
+                            .map(Some)
+                            .collect(),
+                    },
+                    report::Line {
+                        name: "limited",
+                        color: "#e2a22a",
+                        values: timeline
+                            .rates(&stats.throttled_history)
+                            .into_iter()
+                            .map(Some)
+                            .collect(),
+                    },
+                ],
+                markers: markers.clone(),
+            },
+            report::Chart {
+                title: "Latency".to_string(),
+                unit: "ms",
+                start: timeline.start,
+                bucket: width.as_secs_f64(),
+                lines: vec![
+                    latency_line("p50", "#2ca02c", 0.5),
+                    latency_line("p99", "#e2a22a", 0.99),
+                    latency_line("max", "#d62728", 1.0),
+                ],
+                markers,
+            },
+        ];
+
+        let mut tables = vec![self.percentile_table(&timeline), self.status_table()];
+        let (title, route) = if self.grpc_mode {
+            ("Methods", "Method")
+        } else {
+            ("Routes", "Route")
+        };
+        tables.push(report::Table {
+            title: title.to_string(),
+            note: None,
+            headers: vec![route, "Status", "Requests", "Avg ms", "Max ms"],
+            rows: stats
+                .route_breakdown()
+                .into_iter()
+                .map(|((route, status), route_stats)| {
+                    vec![
+                        route.clone(),
+                        status.clone(),
+                        route_stats.count.to_string(),
+                        format!("{:.1}", route_stats.total_ms / route_stats.count as f64),
+                        format!("{:.1}", route_stats.max_ms),
+                    ]
+                })
+                .collect(),
+        });
+        if stats.clients.len() > 1 {
+            tables.push(report::Table {
+                title: "Clients".to_string(),
+                note: Some(format!("The {} busiest", REPORT_CLIENTS)),
+                headers: vec!["Client", "Requests", "Errors", "Avg ms", "Max ms"],
+                rows: stats
+                    .client_breakdown()
+                    .into_iter()
+                    .take(REPORT_CLIENTS)
+                    .map(|(client, client_stats)| {
+                        vec![
+                            client.clone(),
+                            client_stats.requests.to_string(),
+                            format!("{:.1}%", client_stats.error_rate()),
+                            format!(
+                                "{:.1}",
+                                client_stats.total_ms / client_stats.requests.max(1) as f64
+                            ),
+                            format!("{:.1}", client_stats.max_ms),
+                        ]
+                    })
+                    .collect(),
+            });
+        }
+        if let Some(scenario) = &self.scenario {
+            tables.push(report::Table {
+                title: "Scenario phases".to_string(),
+                note: scenario
+                    .repeats()
+                    .then(|| "Repeated from the first phase after the last".to_string()),
+                headers: vec!["Phase", "Start", "Duration", "Ramp", "Conditions"],
+                rows: scenario
+                    .plan()
+                    .into_iter()
+                    .map(|phase| {
+                        vec![
+                            phase.name.to_string(),
+                            format_seconds(phase.start.as_secs_f64()),
+                            format_seconds(phase.duration.as_secs_f64()),
+                            format_seconds(phase.ramp.as_secs_f64()),
+                            phase.conditions.to_string(),
+                        ]
+                    })
+                    .collect(),
+            });
+        }
+
+        Report {
+            generated: self
+                .clock
+                .wall()
+                .format("%Y-%m-%d %H:%M:%S UTC")
+                .to_string(),
+            settings: config,
+            summary: self.report_summary(&timeline),
+            charts,
+            tables,
+        }
+    }
+
+    /// Totals of the run for the report
+    fn report_summary(&self, timeline: &Timeline) -> Vec<(String, String)> {
+        let stats = &self.all;
+        let errors: u64 = stats.clients.values().map(|client| client.errors).sum();
+        let rates = timeline.rates(&stats.rps_history);
+        let peak = rates.iter().copied().fold(0.0, f64::max);
+        let seconds = stats.elapsed.as_secs_f64();
+        let mut summary = vec![
+            ("Duration".to_string(), format_seconds(seconds)),
+            ("Requests".to_string(), stats.total_requests.to_string()),
+            (
+                "Requests per second".to_string(),
+                format!(
+                    "avg {:.1}, peak {}",
+                    stats.total_requests as f64 / seconds.max(1.0),
+                    format_rate(peak)
+                ),
+            ),
+            (
+                "Errors".to_string(),
+                format!(
+                    "{} ({:.1}%)",
+                    errors,
+                    errors as f64 * 100.0 / stats.total_requests.max(1) as f64
+                ),
+            ),
+            (
+                "Injected errors".to_string(),
+                stats.injected_errors.to_string(),
+            ),
+            ("Client bugs".to_string(), stats.client_bugs.to_string()),
+            (
+                "Delay".to_string(),
+                format!(
+                    "min {:.1} ms, avg {:.1} ms, max {:.1} ms",
+                    stats.get_min_delay(),
+                    stats.get_avg_delay(),
+                    stats.get_max_delay()
+                ),
+            ),
+            (
+                "Sent".to_string(),
+                format!("{:.2} MB", stats.sent() as f64 / 1_000_000.0),
+            ),
+            ("Encodings".to_string(), stats.encoding_summary()),
+        ];
+        let connections = stats.connection_totals();
+        summary.push((
+            "Connections".to_string(),
+            match connections.requests_per_connection() {
+                Some(reuse) => format!("{} total, {:.1} requests each", connections.opened, reuse),
+                None => "-".to_string(),
+            },
+        ));
+        if stats.rate_limited > 0 {
+            summary.push(("Rate limited".to_string(), stats.rate_limited.to_string()));
+        }
+        if stats.upstream_errors > 0 {
+            summary.push((
+                "Upstream failures".to_string(),
+                stats.upstream_errors.to_string(),
+            ));
+        }
+        let retries = &stats.retries;
+        if retries.retries > 0 {
+            let patterns: Vec<String> = retries
+                .backoff_patterns()
+                .iter()
+                .map(|(pattern, count)| format!("{} ({})", pattern, count))
+                .collect();
+            summary.push((
+                "Retries".to_string(),
+                format!(
+                    "{}, {} after injected errors, avg wait {:.0} ms",
+                    retries.retries,
+                    retries.after_injected,
+                    retries.interval_total_ms / retries.retries as f64
+                ),
+            ));
+            if !patterns.is_empty() {
+                summary.push(("Backoff".to_string(), patterns.join(", ")));
+            }
+        }
+        summary
+    }
+
+    /// Latency and RPS percentiles of all listeners, and of each when there are several
+    fn percentile_table(&self, timeline: &Timeline) -> report::Table {
+        let mut views = vec![("all", &self.all)];
+        if self.listeners.len() > 1 {
+            views.extend(
+                self.listeners
+                    .iter()
+                    .filter_map(|l| Some((l.name.as_str(), self.by_listener.get(&l.name)?))),
+            );
+        }
+        let rows = views
+            .into_iter()
+            .map(|(name, stats)| {
+                let latency = |p| {
+                    stats
+                        .latencies
+                        .percentile(p)
+                        .map_or_else(|| "-".to_string(), |ms| format!("{:.1}", ms))
+                };
+                let mut rates = timeline.rates(&stats.rps_history);
+                rates.sort_unstable_by(f64::total_cmp);
+                let rate = |p: f64| match rates.len() {
+                    0 => "-".to_string(),
+                    n => format_rate(rates[((n as f64 * p).ceil() as usize).clamp(1, n) - 1]),
+                };
+                vec![
+                    name.to_string(),
+                    stats.total_requests.to_string(),
+                    latency(0.5),
+                    latency(0.9),
+                    latency(0.99),
+                    latency(0.999),
+                    latency(1.0),
+                    rate(0.5),
+                    rate(0.9),
+                    rate(1.0),
+                ]
+            })
+            .collect();
+        report::Table {
+            title: "Percentiles".to_string(),
+            note: Some(format!(
+                "Latency in ms, to within 5% (max is exact). \
+                 Requests per second over {} buckets.",
+                format_span(self.all.rps_history.bucket_width(timeline.window))
+            )),
+            headers: vec![
+                "Listener",
+                "Requests",
+                "p50 ms",
+                "p90 ms",
+                "p99 ms",
+                "p99.9 ms",
+                "Max ms",
+                "Median RPS",
+                "p90 RPS",
+                "Peak RPS",
+            ],
+            rows,
+        }
+    }
+
+    /// Requests per status, over all routes
+    fn status_table(&self) -> report::Table {
+        let mut statuses: HashMap<&str, u64> = HashMap::new();
+        for ((_, status), route) in &self.all.routes {
+            *statuses.entry(status).or_default() += route.count;
+        }
+        let mut statuses: Vec<_> = statuses.into_iter().collect();
+        statuses.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        let total = self.all.total_requests.max(1) as f64;
+        report::Table {
+            title: "Statuses".to_string(),
+            note: None,
+            headers: vec!["Status", "Requests", "Share"],
+            rows: statuses
+                .into_iter()
+                .map(|(status, count)| {
+                    vec![
+                        status.to_string(),
+                        count.to_string(),
+                        format!("{:.1}%", count as f64 * 100.0 / total),
+                    ]
+                })
+                .collect(),
+        }
+    }
+
+    /// Update the throughput once a second has passed since the last sample
+    fn sample_throughput(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_duration_since(self.last_throughput_sample);
+        if elapsed >= Duration::from_secs(1) {
+            self.update_throughput(elapsed);
+            self.last_throughput_sample = now;
+        }
+    }
+}
+
+/// What the TUI shows besides the listeners' traffic, and how
+pub struct TuiOptions {
+    /// Whether gRPC methods are configured, so the breakdown shows methods
+    pub grpc_mode: bool,
+    /// The scenario timeline being played, if any
+    pub scenario: Option<Arc<Scenario>>,
+    /// Seed of the run's random decisions
+    pub seed: u64,
+    /// Source of time for the histories and uptime
+    pub clock: Arc<dyn Clock>,
+    /// Bucket width and window of the histories
+    pub history: HistoryConfig,
+    /// Layouts to cycle through, starting with the selected one
+    pub layouts: Layouts,
+    /// Where the runtime controls send their commands to the server
+    pub control_tx: Sender<ControlEvent>,
+    /// Where to write the report on exit, if anywhere
+    pub report: Option<ReportConfig>,
+    /// Closes the TUI when the server shuts down
+    pub shutdown: watch::Receiver<bool>,
+}
+
+/// Main TUI function (runs in a blocking thread)
+///
+/// Receives `AppEvent` messages on `rx` and updates the TUI accordingly.
+pub fn run_tui(
+    mut rx: Receiver<AppEvent>,
+    listeners: Vec<ListenerInfo>,
+    options: TuiOptions,
+) -> anyhow::Result<()> {
+    enable_raw_mode()?;
+    let mut stdout = stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let mut data = TuiData::new(options.clock, options.history, listeners, options.grpc_mode);
+    data.scenario = options.scenario;
+    data.seed = Some(options.seed);
+    data.layouts = options.layouts;
+    data.control_tx = Some(options.control_tx);
+
+    let tick_rate = Duration::from_millis(200);
+    let mut last_tick = Instant::now();
+
+    while !*options.shutdown.borrow() {
+        data.update_rps();
+        data.sample_throughput();
+
+        data.update_encodings();
+        data.update_workers();
+        data.update_connections();
+
+        while let Ok(event) = rx.try_recv() {
+            let AppEvent::RequestReceived(log) = event;
+            data.push_log(log);
+        }
+
+        terminal.draw(|frame| {
+            draw_ui(frame, &data);
+        })?;
+
+        if crossterm::event::poll(Duration::from_millis(1))?
+            && let CEvent::Key(key) = event::read()?
+        {
+            if data.prompt.is_some() {
+                data.edit_prompt(key.code);
+            } else if key.code == KeyCode::Char('q')
+                || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+            {
+                break;
+            }
+            match key.code {
+                _ if data.prompt.is_some() => {}
+                KeyCode::Char('l') => data.cycle_listener(),
+                KeyCode::Char('+') | KeyCode::Char('=') => data.zoom(true),
+                KeyCode::Char('-') => data.zoom(false),
+                KeyCode::Char('h') => data.latency_heatmap = !data.latency_heatmap,
+                KeyCode::Char('c') => data.breakdown = data.breakdown.toggle(Breakdown::Clients),
+                KeyCode::Char('r') => data.breakdown = data.breakdown.toggle(Breakdown::Retries),
+                KeyCode::Char('v') => data.layouts.cycle(),
+                KeyCode::Char('?') => data.show_help = !data.show_help,
+                KeyCode::Esc => data.show_help = false,
+                KeyCode::Char('[') => data.send(Command::AdjustDelay(-DELAY_STEP_MS)),
+                KeyCode::Char(']') => data.send(Command::AdjustDelay(DELAY_STEP_MS)),
+                KeyCode::Char('d') => {
+                    data.prompt = Some(String::new());
+                    data.prompt_error = None;
+                }
+                KeyCode::Char('e') => data.send(Command::ToggleErrors),
+                KeyCode::Char('p') => data.send(Command::CyclePause),
+                KeyCode::Char('f') => data.send(Command::CycleFormat),
+                KeyCode::Char('x') => data.reset_stats(),
+                _ => {}
+            }
+        }
+
+        if last_tick.elapsed() >= tick_rate {
+            last_tick = Instant::now();
+        }
+    }
+
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+
+    if let Some(config) = options.report {
+        data.update_rps();
+        data.report(&config.settings).write(&config)?;
+        println!("Report written to {}", config.path.display());
+    }
+    Ok(())
+}
+
+/// Draw p50, p99 and max served latency per bucket, x = seconds ago like the RPS chart.
+fn draw_latency_chart<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    area: Rect,
+    stats: &Stats,
+    window: Duration,
+) {
+    let width = stats.latency_history.bucket_width(window).as_secs_f64();
+    let x_max = window.as_secs_f64() - width;
+    let buckets = stats.latency_history.buckets(window);
+    // Buckets without requests leave a gap instead of dropping to 0 ms
+    let points = |value: &dyn Fn(&LatencyBucket) -> Option<f64>| -> Vec<(f64, f64)> {
+        buckets
+            .iter()
+            .enumerate()
+            .filter_map(|(i, bucket)| Some((i as f64 * width, value(bucket)?)))
+            .collect()
+    };
+    let p50 = points(&|bucket| bucket.percentile(0.5));
+    let p99 = points(&|bucket| bucket.percentile(0.99));
+    let max = points(&|bucket| bucket.percentile(1.0));
+
+    let max_ms = max.iter().map(|(_x, y)| *y).fold(0.0, f64::max);
+    let y_max = ((max_ms * 1.2).ceil() / 10.0).ceil() * 10.0;
+    let y_max = y_max.max(10.0);
+
+    let datasets = [
+        ("p50", &p50, Color::Green),
+        ("p99", &p99, Color::Yellow),
+        ("max", &max, Color::Red),
+    ]
+    .into_iter()
+    .map(|(name, line, color)| {
+        Dataset::default()
+            .name(name)
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(color))
+            .data(line)
+    })
+    .collect();
+    let chart = Chart::new(datasets)
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Latency (Last {}) ('h' for heatmap)",
+            format_span(window)
+        )))
+        .x_axis(
+            Axis::default()
                 .title(Span::raw("Seconds Ago"))
                 .style(Style::default().fg(Color::Gray))
-                .bounds([0.0, 59.0])
-                .labels(vec!["0".into(), "30".into(), "59".into()]),
+                .bounds([0.0, x_max])
+                .labels(vec![
+                    "0".into(),
+                    format_rate(x_max / 2.0).into(),
+                    format_rate(x_max).into(),
+                ]),
         )
         .y_axis(
             Axis::default()
-                .title(Span::raw("Count"))
+                .title(Span::raw("ms"))
                 .style(Style::default().fg(Color::Gray))
                 .bounds([0.0, y_max])
                 .labels(vec![
@@ -366,44 +1774,769 @@ fn draw_ui<B: ratatui::backend::Backend>(frame: &mut ratatui::Frame<B>, data: &T
                     format!("{}", y_max).into(),
                 ]),
         );
-    frame.render_widget(chart, vertical_chunks[1]);
+    frame.render_widget(chart, area);
+}
 
-    // Logs panel remains unchanged.
-    let logs_text: String = data
-        .logs
+/// Draw open connections and requests in flight (peaks per bucket) with connections opened
+/// and closed per second
+fn draw_connections_chart<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    area: Rect,
+    stats: &Stats,
+    window: Duration,
+) {
+    let width = stats.open_history.bucket_width(window).as_secs_f64();
+    let x_max = window.as_secs_f64() - width;
+    let points = |values: Vec<f64>| -> Vec<(f64, f64)> {
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| (i as f64 * width, value))
+            .collect()
+    };
+    let lines = [
+        (
+            "open",
+            points(stats.open_history.values(window)),
+            Color::Cyan,
+        ),
+        (
+            "in flight",
+            points(stats.in_flight_history.values(window)),
+            Color::Magenta,
+        ),
+        (
+            "opened/s",
+            points(stats.opens_history.rates(window)),
+            Color::Green,
+        ),
+        (
+            "closed/s",
+            points(stats.closes_history.rates(window)),
+            Color::Red,
+        ),
+    ];
+    let max = lines
         .iter()
+        .flat_map(|(_, line, _)| line.iter().map(|(_x, y)| *y))
+        .fold(0.0, f64::max);
+    let y_max = ((max * 1.2).ceil() / 10.0).ceil() * 10.0;
+    let y_max = y_max.max(10.0);
+
+    let datasets = lines
+        .iter()
+        .map(|(name, line, color)| {
+            Dataset::default()
+                .name(*name)
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(*color))
+                .data(line)
+        })
+        .collect();
+    let chart = Chart::new(datasets)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Connections (Last {})", format_span(window))),
+        )
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([0.0, x_max])
+                .labels(vec!["0".into(), format_rate(x_max).into()]),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([0.0, y_max])
+                .labels(vec!["0".into(), format!("{}", y_max).into()]),
+        );
+    frame.render_widget(chart, area);
+}
+
+/// Label of a latency histogram bin, e.g. `<=5ms` or `>5s`
+fn latency_bin_label(bin: usize) -> String {
+    let format_ms = |ms: f64| {
+        if ms >= 1000.0 {
+            format!("{}s", ms / 1000.0)
+        } else {
+            format!("{}ms", ms)
+        }
+    };
+    match LATENCY_BINS_MS.get(bin) {
+        Some(&bound) => format!("<={}", format_ms(bound)),
+        None => format!(">{}", format_ms(LATENCY_BINS_MS[LATENCY_BINS_MS.len() - 1])),
+    }
+}
+
+/// Rows of a latency heatmap, slowest bin first: the bin label and one cell per column.
+/// Each column is shaded by the share of its requests in the bin, so drift shows at any rate.
+fn heatmap_rows(columns: &[LatencyBucket]) -> Vec<(String, Vec<(char, Color)>)> {
+    let used = |bin: usize| columns.iter().any(|column| column.counts[bin] > 0);
+    let bins = 0..=LATENCY_BINS_MS.len();
+    let (Some(fastest), Some(slowest)) = (
+        bins.clone().find(|&bin| used(bin)),
+        bins.rev().find(|&bin| used(bin)),
+    ) else {
+        return Vec::new();
+    };
+    (fastest..=slowest)
         .rev()
-        .take(20)
+        .map(|bin| {
+            let cells = columns
+                .iter()
+                .map(|column| {
+                    let total: u64 = column.counts.iter().sum();
+                    let share = column.counts[bin] as f64 / total.max(1) as f64;
+                    match share {
+                        0.0 => (' ', Color::Reset),
+                        _ if share < 0.25 => ('░', Color::Blue),
+                        _ if share < 0.5 => ('▒', Color::Cyan),
+                        _ if share < 0.75 => ('▓', Color::Yellow),
+                        _ => ('█', Color::Red),
+                    }
+                })
+                .collect();
+            (latency_bin_label(bin), cells)
+        })
+        .collect()
+}
+
+/// Draw a heatmap of served latency: time on X (newest on the left, like the charts),
+/// latency bins on Y
+fn draw_latency_heatmap<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    area: Rect,
+    stats: &Stats,
+    window: Duration,
+) {
+    const LABEL_WIDTH: usize = 8;
+    let buckets = stats.latency_history.buckets(window);
+    let width = area.width.saturating_sub(2) as usize;
+    let columns = width.saturating_sub(LABEL_WIDTH).max(1);
+    let per_column = buckets.len().div_ceil(columns);
+    let column_span = stats.latency_history.bucket_width(window) * per_column as u32;
+    let rows = heatmap_rows(&merge_columns(&buckets, per_column));
+
+    let text: Vec<Spans> = if rows.is_empty() {
+        vec![Spans::from("No requests yet")]
+    } else {
+        rows.into_iter()
+            .map(|(label, cells)| {
+                let mut spans = vec![Span::raw(format!("{:>w$} ", label, w = LABEL_WIDTH - 1))];
+                spans.extend(cells.into_iter().map(|(symbol, color)| {
+                    Span::styled(symbol.to_string(), Style::default().fg(color))
+                }));
+                Spans::from(spans)
+            })
+            .collect()
+    };
+    let heatmap =
+        Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(format!(
+            "Latency Heatmap (Last {}, {} per column) ('h' for percentiles)",
+            format_span(window),
+            format_span(column_span)
+        )));
+    frame.render_widget(heatmap, area);
+}
+
+/// Draw the per-client breakdown, busiest client first; `hint` ends the title
+fn draw_clients_table<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    area: Rect,
+    stats: &Stats,
+    hint: &str,
+) {
+    let second = stats.elapsed.as_secs();
+    let rows = stats.client_breakdown().into_iter().map(|(client, stats)| {
+        Row::new(vec![
+            Cell::from(client.clone()),
+            Cell::from(stats.requests.to_string()),
+            Cell::from(stats.rps(second).to_string()),
+            Cell::from(stats.in_flight.to_string()),
+            Cell::from(format!("{:.1}%", stats.error_rate())),
+            Cell::from(format!(
+                "{:.1}",
+                stats.total_ms / stats.requests.max(1) as f64
+            )),
+            Cell::from(format!("{:.1}", stats.max_ms)),
+        ])
+    });
+    let clients_table = Table::new(rows)
+        .header(
+            Row::new(vec![
+                "Client", "Count", "RPS", "Active", "Errors", "Avg ms", "Max ms",
+            ])
+            .style(Style::default().fg(Color::Yellow)),
+        )
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Clients{}", hint)),
+        )
+        .widths(&[
+            Constraint::Percentage(24),
+            Constraint::Percentage(11),
+            Constraint::Percentage(9),
+            Constraint::Percentage(10),
+            Constraint::Percentage(12),
+            Constraint::Percentage(14),
+            Constraint::Percentage(14),
+        ]);
+    frame.render_widget(clients_table, area);
+}
+
+/// Draw the retried requests, most recently retried first, with the waits between attempts;
+/// `hint` ends the title
+fn draw_retries_table<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    area: Rect,
+    stats: &Stats,
+    hint: &str,
+) {
+    let rows = stats.retries.retried().into_iter().map(|(key, chain)| {
+        let intervals: Vec<String> = chain
+            .intervals_ms
+            .iter()
+            .map(|ms| format!("{:.0}", ms))
+            .collect();
+        let backoff = classify(&chain.intervals_ms)
+            .map_or_else(|| "-".to_string(), |pattern| pattern.to_string());
+        Row::new(vec![
+            Cell::from(key.clone()),
+            Cell::from(chain.attempts.to_string()),
+            Cell::from(intervals.join(", ")),
+            Cell::from(backoff),
+            Cell::from(chain.last_status.clone()),
+        ])
+    });
+    let title = if stats.retry_storm() {
+        format!("Retries - RETRY STORM{}", hint)
+    } else {
+        format!(
+            "Retries ({} retried, max {} attempts){}",
+            stats.retries.retried().len(),
+            stats.retries.max_attempts,
+            hint
+        )
+    };
+    let retries_table = Table::new(rows)
+        .header(
+            Row::new(vec!["Request", "Tries", "Waits ms", "Backoff", "Last"])
+                .style(Style::default().fg(Color::Yellow)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .widths(&[
+            Constraint::Percentage(20),
+            Constraint::Percentage(9),
+            Constraint::Percentage(21),
+            Constraint::Percentage(37),
+            Constraint::Percentage(13),
+        ]);
+    frame.render_widget(retries_table, area);
+}
+
+/// One line of the key numbers, for layouts without the statistics panels
+fn summary_text(data: &TuiData) -> String {
+    let stats = data.view();
+    let errors: u64 = stats.clients.values().map(|client| client.errors).sum();
+    let storm = if stats.retry_storm() {
+        " | RETRY STORM"
+    } else {
+        ""
+    };
+    let controls = data
+        .controls_summary()
+        .map_or_else(String::new, |controls| format!(" | {}", controls));
+    format!(
+        "RPS: {} | Requests: {} | Errors: {} | Delay: avg {:.1} ms, max {:.1} ms | Open: {} | Uptime: {}s{}{}",
+        format_rate(stats.rps_history.last_second()),
+        stats.total_requests,
+        errors,
+        stats.get_avg_delay(),
+        stats.get_max_delay(),
+        stats.connections.open,
+        data.uptime_seconds(),
+        storm,
+        controls
+    )
+}
+
+/// Draw the one-line summary, titled with the layout and listener shown
+fn draw_summary<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    area: Rect,
+    data: &TuiData,
+) {
+    let listener = data
+        .selected()
+        .map_or_else(String::new, |listener| format!(" [{}]", listener.name));
+    let title = format!(
+        "Summary{} (layout: {}, 'v' to switch)",
+        listener,
+        data.layouts.name()
+    );
+    let summary = Paragraph::new(summary_text(data))
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(summary, area);
+}
+
+/// Draw detailed RPS statistics over the chart's window.
+fn draw_rps_stats<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    area: Rect,
+    stats: &Stats,
+    window: Duration,
+) {
+    let (rps_min, rps_max, rps_avg, rps_median, rps_p90) = stats.compute_rps_stats(window);
+    let mut rps_stats_text = format!(
+        "Min RPS: {}\nMax RPS: {}\nAvg RPS: {:.2}\nMedian RPS: {}\n90th Percentile: {}",
+        format_rate(rps_min),
+        format_rate(rps_max),
+        rps_avg,
+        format_rate(rps_median),
+        format_rate(rps_p90)
+    );
+    for summary in [stats.rate_limit_summary(), stats.retry_summary()]
+        .into_iter()
+        .flatten()
+    {
+        rps_stats_text.push('\n');
+        rps_stats_text.push_str(&summary);
+    }
+    let rps_stats_paragraph = Paragraph::new(rps_stats_text).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("RPS Stats (Last {})", format_span(window))),
+    );
+    frame.render_widget(rps_stats_paragraph, area);
+}
+
+/// Draw delay statistics, and the worker pool when concurrency is limited.
+fn draw_delay_stats<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    area: Rect,
+    stats: &Stats,
+) {
+    let mut delay_stats_text = format!(
+        "Min Delay: {:.3} ms\nMax Delay: {:.3} ms\nAvg Delay: {:.3} ms",
+        stats.get_min_delay(),
+        stats.get_max_delay(),
+        stats.get_avg_delay()
+    );
+    // Queue wait is shown apart from the service time above
+    if let Some(workers) = &stats.workers {
+        let queue_size = workers
+            .queue_size
+            .map_or_else(String::new, |size| format!("/{}", size));
+        delay_stats_text.push_str(&format!(
+            "\nAvg Queue Wait: {:.3} ms\nIn Flight: {}/{}\nQueued: {}{}\nRejected: {}",
+            stats.get_avg_queue_wait(),
+            workers.in_flight,
+            workers.max,
+            workers.queued,
+            queue_size,
+            stats.rejected()
+        ));
+    }
+    let delay_stats_paragraph = Paragraph::new(delay_stats_text)
+        .block(Block::default().borders(Borders::ALL).title("Delay Stats"));
+    frame.render_widget(delay_stats_paragraph, area);
+}
+
+/// Draw general server stats.
+fn draw_server_stats<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    area: Rect,
+    data: &TuiData,
+) {
+    let stats = data.view();
+    let describe = |listener: &ListenerInfo| match &listener.upstream {
+        Some(upstream) => format!("{} -> {}", listener.address, upstream),
+        None => listener.address.clone(),
+    };
+    let listeners_text = match data.selected() {
+        Some(listener) => format!("Listener: {} ({})", listener.name, describe(listener)),
+        None => data
+            .listeners
+            .iter()
+            .map(|listener| format!("{}: {}", listener.name, describe(listener)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    let phase_text = data
+        .scenario_status()
+        .map_or_else(String::new, |status| format!("{}\n", status));
+    let seed_text = data
+        .seed
+        .map_or_else(String::new, |seed| format!(" (seed {})", seed));
+    let controls_text = data
+        .controls_summary()
+        .map_or_else(String::new, |controls| format!("Controls: {}\n", controls));
+    let server_stats_text = format!(
+        "{}{}Uptime: {}s{}\nTotal Requests: {}\n{}\nInjected Errors: {}\nClient Bugs: {}\nSent: {:.2} MB ({:.2} MB/s)\nEncodings: {}\n{}",
+        phase_text,
+        controls_text,
+        data.uptime_seconds(),
+        seed_text,
+        stats.total_requests,
+        stats.connection_summary(),
+        stats.injected_errors,
+        stats.client_bugs,
+        stats.sent() as f64 / 1_000_000.0,
+        stats.throughput / 1_000_000.0,
+        stats.encoding_summary(),
+        listeners_text
+    );
+    let server_stats_title = match (data.selected(), data.listeners.len()) {
+        (Some(listener), _) => format!("Server Stats [{}] ('l' to cycle)", listener.name),
+        (None, n) if n > 1 => "Server Stats [all] ('l' to cycle)".to_string(),
+        _ => "Server Stats".to_string(),
+    };
+    let server_stats_paragraph = Paragraph::new(server_stats_text).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(server_stats_title),
+    );
+    frame.render_widget(server_stats_paragraph, area);
+}
+
+/// Draw the RPS chart, x = seconds ago of each completed bucket.
+fn draw_rps_chart<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    area: Rect,
+    data: &TuiData,
+) {
+    let stats = data.view();
+    let window = data.chart_window();
+    let bucket_width = stats.rps_history.bucket_width(window);
+    let width = bucket_width.as_secs_f64();
+    let x_max = window.as_secs_f64() - width;
+    let points = |rates: Vec<f64>| -> Vec<(f64, f64)> {
+        rates
+            .into_iter()
+            .enumerate()
+            .map(|(i, rate)| (i as f64 * width, rate))
+            .collect()
+    };
+    let chart_data = points(stats.rps_history.rates(window));
+    let throttled_data = points(stats.throttled_history.rates(window));
+
+    let max_rps = chart_data.iter().map(|(_x, y)| *y).fold(0.0, f64::max);
+    let y_max = ((max_rps * 1.2).ceil() / 10.0).ceil() * 10.0;
+    let y_max = y_max.max(10.0);
+    let phase_markers = data.phase_markers(y_max);
+
+    let mut datasets = vec![
+        Dataset::default()
+            .name("RPS")
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Green))
+            .data(&chart_data),
+    ];
+    if stats.rate_limited > 0 {
+        datasets.push(
+            Dataset::default()
+                .name("Throttled")
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Yellow))
+                .data(&throttled_data),
+        );
+    }
+    for (name, line) in &phase_markers {
+        datasets.push(
+            Dataset::default()
+                .name(name.as_str())
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Cyan))
+                .data(line),
+        );
+    }
+
+    let chart = Chart::new(datasets)
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "RPS (Last {}, {} buckets) ('+'/'-' to zoom)",
+            format_span(window),
+            format_span(bucket_width)
+        )))
+        .x_axis(
+            Axis::default()
+                .title(Span::raw("Seconds Ago"))
+                .style(Style::default().fg(Color::Gray))
+                .bounds([0.0, x_max])
+                .labels(vec![
+                    "0".into(),
+                    format_rate(x_max / 2.0).into(),
+                    format_rate(x_max).into(),
+                ]),
+        )
+        .y_axis(
+            Axis::default()
+                .title(Span::raw("Count"))
+                .style(Style::default().fg(Color::Gray))
+                .bounds([0.0, y_max])
+                .labels(vec![
+                    "0".into(),
+                    format!("{}", y_max / 2.0).into(),
+                    format!("{}", y_max).into(),
+                ]),
+        );
+    frame.render_widget(chart, area);
+}
+
+/// Draw the most recent logs that fit. Client bugs (spec violations), injected errors
+/// and rate-limited requests are highlighted differently.
+fn draw_logs<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    area: Rect,
+    data: &TuiData,
+) {
+    let logs_text: Vec<Spans> = data
+        .visible_logs()
+        .take(area.height.saturating_sub(2) as usize)
         .map(|log| {
             let timestamp = chrono::DateTime::<Utc>::from_timestamp(log.timestamp, 0)
                 .unwrap()
                 .format("%Y-%m-%d %H:%M:%S")
                 .to_string();
-            let status_text = format!("[{}]", log.status);
-            format!(
-                "{} {} {} {} ({:.3} ms)",
-                timestamp, status_text, log.method, log.path, log.duration_ms
-            )
+            let status_text = format!("[{}]", status_label(log));
+            let queued = if log.context.queue_ms > 0.0 {
+                format!(", queued {:.3} ms", log.context.queue_ms)
+            } else {
+                String::new()
+            };
+            let sequence = log
+                .context
+                .sequence
+                .as_ref()
+                .map_or_else(String::new, |step| format!(" <{}>", step.label()));
+            let line = format!(
+                "{} {} {} {} ({:.3} ms{}){}",
+                timestamp, status_text, log.method, log.path, log.duration_ms, queued, sequence
+            );
+            match &log.outcome {
+                Outcome::ValidationError(violation) => Spans::from(Span::styled(
+                    format!("{} CLIENT BUG: {}", line, violation),
+                    Style::default().fg(Color::Magenta),
+                )),
+                Outcome::InjectedError => Spans::from(Span::styled(
+                    format!("{} (injected)", line),
+                    Style::default().fg(Color::Red),
+                )),
+                Outcome::RateLimited => Spans::from(Span::styled(
+                    format!("{} (rate limited)", line),
+                    Style::default().fg(Color::Yellow),
+                )),
+                Outcome::QueueFull => Spans::from(Span::styled(
+                    format!("{} (queue full)", line),
+                    Style::default().fg(Color::Yellow),
+                )),
+                Outcome::Paused => Spans::from(Span::styled(
+                    format!("{} (paused)", line),
+                    Style::default().fg(Color::Yellow),
+                )),
+                Outcome::UpstreamError => Spans::from(Span::styled(
+                    format!("{} (upstream failed)", line),
+                    Style::default().fg(Color::LightRed),
+                )),
+                Outcome::Served => Spans::from(line),
+            }
         })
-        .collect::<Vec<_>>()
-        .join("\n");
+        .collect();
 
     let logs_paragraph =
         Paragraph::new(logs_text).block(Block::default().borders(Borders::ALL).title("Logs"));
-    frame.render_widget(logs_paragraph, vertical_chunks[2]);
+    frame.render_widget(logs_paragraph, area);
+}
+
+/// Draw the per-route breakdown: gRPC methods and statuses, or HTTP paths and status codes.
+fn draw_routes_table<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    area: Rect,
+    stats: &Stats,
+    grpc_mode: bool,
+) {
+    let (title, route_header) = if grpc_mode {
+        ("gRPC Methods ('c' clients, 'r' retries)", "Method")
+    } else {
+        ("Routes ('c' clients, 'r' retries)", "Path")
+    };
+    let rows = stats
+        .route_breakdown()
+        .into_iter()
+        .map(|((route, status), stats)| {
+            Row::new(vec![
+                Cell::from(route.clone()),
+                Cell::from(status.clone()),
+                Cell::from(stats.count.to_string()),
+                Cell::from(format!("{:.1}", stats.total_ms / stats.count as f64)),
+                Cell::from(format!("{:.1}", stats.max_ms)),
+            ])
+        });
+    let routes_table = Table::new(rows)
+        .header(
+            Row::new(vec![route_header, "Status", "Count", "Avg ms", "Max ms"])
+                .style(Style::default().fg(Color::Yellow)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .widths(&[
+            Constraint::Percentage(40),
+            Constraint::Percentage(20),
+            Constraint::Percentage(12),
+            Constraint::Percentage(14),
+            Constraint::Percentage(14),
+        ]);
+    frame.render_widget(routes_table, area);
+}
+
+/// Draw one panel of the layout
+fn draw_panel<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    panel: Panel,
+    area: Rect,
+    data: &TuiData,
+) {
+    let stats = data.view();
+    let window = data.chart_window();
+    match panel {
+        Panel::Summary => draw_summary(frame, area, data),
+        Panel::RpsStats => draw_rps_stats(frame, area, stats, window),
+        Panel::DelayStats => draw_delay_stats(frame, area, stats),
+        Panel::ServerStats => draw_server_stats(frame, area, data),
+        Panel::RpsChart => draw_rps_chart(frame, area, data),
+        Panel::LatencyChart if data.latency_heatmap => {
+            draw_latency_heatmap(frame, area, stats, window)
+        }
+        Panel::LatencyChart => draw_latency_chart(frame, area, stats, window),
+        Panel::ConnectionsChart => draw_connections_chart(frame, area, stats, window),
+        Panel::Logs => draw_logs(frame, area, data),
+        // The routes panel shows clients or retries instead when toggled
+        Panel::Routes => match data.breakdown {
+            Breakdown::Routes => draw_routes_table(frame, area, stats, data.grpc_mode),
+            Breakdown::Clients => draw_clients_table(frame, area, stats, " ('c' for routes)"),
+            Breakdown::Retries => draw_retries_table(frame, area, stats, " ('r' for routes)"),
+        },
+        Panel::Clients => draw_clients_table(frame, area, stats, ""),
+        Panel::Retries => draw_retries_table(frame, area, stats, ""),
+    }
+}
+
+/// A `width` x `height` area in the middle of `area`, shrunk to fit
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// Draw the keybindings over the dashboard
+fn draw_help<B: ratatui::backend::Backend>(frame: &mut ratatui::Frame<B>) {
+    let lines: Vec<Spans> = HELP
+        .iter()
+        .map(|&(keys, action)| {
+            Spans::from(vec![
+                Span::styled(
+                    format!("{:>10}  ", keys),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(action),
+            ])
+        })
+        .collect();
+    let area = centered(frame.size(), 70, HELP.len() as u16 + 2);
+    let help = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Keys ('?' or Esc to close)"),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(help, area);
+}
+
+/// Draw the prompt for a new delay, with why the last one was rejected
+fn draw_prompt<B: ratatui::backend::Backend>(
+    frame: &mut ratatui::Frame<B>,
+    input: &str,
+    error: Option<&str>,
+) {
+    let mut lines = vec![Spans::from(format!("> {}_", input))];
+    if let Some(error) = error {
+        lines.push(Spans::from(Span::styled(
+            error.to_string(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    let area = centered(frame.size(), 60, lines.len() as u16 + 2);
+    let prompt = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Delay in ms, e.g. 100 or 50-200 (Enter to set, Esc to cancel)"),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(prompt, area);
+}
+
+/// Draw the entire UI, in rows of panels as laid out for the terminal's size.
+fn draw_ui<B: ratatui::backend::Backend>(frame: &mut ratatui::Frame<B>, data: &TuiData) {
+    let size = frame.size();
+    let layout = data.layouts.current(size.width, size.height);
+    let heights: Vec<Constraint> = layout
+        .rows
+        .iter()
+        .map(|row| match row.height {
+            RowHeight::Percent(percent) => Constraint::Percentage(percent),
+            RowHeight::Lines(lines) => Constraint::Length(lines),
+        })
+        .collect();
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(heights)
+        .split(size);
+    for (row, &row_area) in layout.rows.iter().zip(rows.iter()) {
+        let widths: Vec<Constraint> = row
+            .panels
+            .iter()
+            .map(|&(_, width)| Constraint::Percentage(width))
+            .collect();
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(widths)
+            .split(row_area);
+        for (&(panel, _), &area) in row.panels.iter().zip(columns.iter()) {
+            draw_panel(frame, panel, area, data);
+        }
+    }
+    if data.show_help {
+        draw_help(frame);
+    }
+    if let Some(input) = &data.prompt {
+        draw_prompt(frame, input, data.prompt_error.as_deref());
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::state::RequestLog;
+    use crate::clock::{self, ManualClock};
+    use crate::concurrency::ConcurrencyLimit;
+    use crate::retries::Attempt;
+    use crate::routes::DelayConfig;
+    use crate::scenario::Conditions;
+    use crate::state::{RequestContext, RequestLog};
     use chrono::Utc;
-    use std::time::Instant;
+    use std::sync::atomic::AtomicU64;
 
     #[test]
     fn test_push_log_updates_stats() {
-        let start = Instant::now();
-        let mut data = TuiData::new(start, 8080);
+        let mut data = TuiData::new(clock::system(), HistoryConfig::default(), Vec::new(), false);
         let now = Utc::now().timestamp();
         let log = RequestLog {
             path: "/test".to_string(),
@@ -411,53 +2544,681 @@ mod tests {
             status: 200,
             timestamp: now,
             duration_ms: 120.0,
+            ..Default::default()
         };
         data.push_log(log);
-        assert_eq!(data.total_requests, 1);
-        assert_eq!(data.get_min_delay(), 120.0);
-        assert_eq!(data.get_max_delay(), 120.0);
-        assert_eq!(data.get_avg_delay(), 120.0);
+        assert_eq!(data.all.total_requests, 1);
+        assert_eq!(data.all.get_min_delay(), 120.0);
+        assert_eq!(data.all.get_max_delay(), 120.0);
+        assert_eq!(data.all.get_avg_delay(), 120.0);
         assert_eq!(data.logs.len(), 1);
     }
 
     #[test]
     fn test_update_rps_shifts_history() {
-        let start = Instant::now();
-        let mut data = TuiData::new(start, 8080);
-        // Simulate some RPS count at the current second.
-        data.rps_history[0] = 5;
-        data.rps_display_history[0] = 5;
-        // Simulate that 10 seconds have passed.
-        let now = Utc::now().timestamp();
-        data.last_rps_update = now - 10;
-        data.update_rps(now);
-        // After shifting, the previous count should now be at index 10.
-        assert_eq!(data.rps_history[10], 5);
-        // And the first 10 indices should be reset to 0.
-        for i in 0..10 {
-            assert_eq!(data.rps_history[i], 0);
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let mut data = TuiData::new(clock.clone(), HistoryConfig::default(), Vec::new(), false);
+        data.update_rps();
+        for _ in 0..5 {
+            data.push_log(RequestLog::default());
         }
+        clock.advance(Duration::from_secs(10));
+        data.update_rps();
+        // After shifting, the previous count should be 10 completed buckets back
+        let rates = data.all.rps_history.rates(Duration::from_secs(60));
+        assert_eq!(rates[9], 5.0);
+        // And the buckets since then should be empty
+        assert!(rates[..9].iter().all(|&rate| rate == 0.0));
+        assert_eq!(data.uptime_seconds(), 10);
     }
 
     #[test]
     fn test_compute_rps_stats() {
-        let start = Instant::now();
-        let mut data = TuiData::new(start, 8080);
-        // Manually set rps_history for indices 1.. (index 0 is the current second and ignored)
-        // Here we simulate sample RPS counts; non-zero values: 5, 3, 8, 2, 7, 4, 6.
-        data.rps_history = [
-            0, 5, 3, 8, 2, 7, 1, 4, 4, 6, 8, 7, 2, 2, 3, 5, 4, 4, 7, 1, 7, 5, 9, 9, 8, 9, 5, 9, 2,
-            7, 6, 8, 1, 1, 2, 8, 7, 4, 2, 7, 11, 6, 6, 5, 6, 2, 3, 2, 8, 7, 1, 5, 7, 3, 4, 5, 6, 5,
-            5, 3,
+        let mut stats = Stats::new(HistoryConfig::default());
+        // Sample RPS counts of the last 60 seconds, newest first
+        let counts = [
+            5, 3, 8, 2, 7, 1, 4, 4, 6, 8, 7, 2, 2, 3, 5, 4, 4, 7, 1, 7, 5, 9, 9, 8, 9, 5, 9, 2, 7,
+            6, 8, 1, 1, 2, 8, 7, 4, 2, 7, 11, 6, 6, 5, 6, 2, 3, 2, 8, 7, 1, 5, 7, 3, 4, 5, 6, 5, 5,
+            3, 9,
         ];
-        let (min, max, avg, median, p90) = data.compute_rps_stats();
-        // After sorting non-zero values: 2, 3, 4, 5, 6, 7, 8.
-        assert_eq!(min, 1);
-        assert_eq!(max, 11);
-        assert_eq!(median, 5);
-        assert_eq!(avg.round() as u64, 5);
-        assert_eq!(p90, 9);
+        for (second, &count) in counts.iter().rev().enumerate() {
+            stats.update_rps(Duration::from_secs(second as u64));
+            for _ in 0..count {
+                stats.rps_history.add();
+            }
+        }
+        // Requests in the current second are ignored
+        stats.update_rps(Duration::from_secs(60));
+        stats.rps_history.add();
+        let (min, max, avg, median, p90) = stats.compute_rps_stats(Duration::from_secs(60));
+        assert_eq!(min, 1.0);
+        assert_eq!(max, 11.0);
+        assert_eq!(median, 5.0);
+        assert_eq!(avg.round(), 5.0);
+        assert_eq!(p90, 9.0);
         // The 90th percentile (p90) should lie between the median and max.
         assert!(p90 >= median && p90 <= max);
     }
+
+    #[test]
+    fn test_heatmap_rows() {
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let mut data = TuiData::new(clock.clone(), HistoryConfig::default(), Vec::new(), false);
+        assert!(
+            heatmap_rows(&data.all.latency_history.buckets(Duration::from_secs(60))).is_empty()
+        );
+        // Latency drifts from ~4ms to ~40ms
+        for duration_ms in [3.0, 4.0, 4.0, 30.0] {
+            data.push_log(RequestLog {
+                duration_ms,
+                ..Default::default()
+            });
+        }
+        clock.advance(Duration::from_secs(1));
+        data.update_rps();
+        data.push_log(RequestLog {
+            duration_ms: 40.0,
+            ..Default::default()
+        });
+        clock.advance(Duration::from_secs(1));
+        data.update_rps();
+
+        let columns = data.all.latency_history.buckets(Duration::from_secs(60));
+        let rows = heatmap_rows(&columns[..2]);
+        // Only the bins from the fastest to the slowest request, slowest first
+        let labels: Vec<&str> = rows.iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(labels, vec!["<=50ms", "<=20ms", "<=10ms", "<=5ms"]);
+        // Newest column first: all of it in the 50ms bin, then 3/4 of the older one at 5ms
+        assert_eq!(rows[0].1, vec![('█', Color::Red), ('▒', Color::Cyan)]);
+        assert_eq!(rows[3].1, vec![(' ', Color::Reset), ('█', Color::Red)]);
+        assert_eq!(latency_bin_label(LATENCY_BINS_MS.len()), ">5s");
+    }
+
+    #[test]
+    fn test_route_breakdown_groups_by_status() {
+        let mut data = TuiData::new(clock::system(), HistoryConfig::default(), Vec::new(), true);
+        for (status, duration_ms) in [(Some(0), 10.0), (Some(14), 30.0), (Some(0), 20.0)] {
+            data.push_log(RequestLog {
+                path: "/helloworld.Greeter/SayHello".to_string(),
+                method: "POST".to_string(),
+                status: 200,
+                grpc_status: status,
+                duration_ms,
+                ..Default::default()
+            });
+        }
+        let breakdown = data.all.route_breakdown();
+        assert_eq!(breakdown.len(), 2);
+        assert_eq!(breakdown[0].0.1, "OK");
+        assert_eq!(breakdown[0].1.count, 2);
+        assert_eq!(breakdown[0].1.max_ms, 20.0);
+        assert_eq!(breakdown[1].0.1, "UNAVAILABLE");
+    }
+
+    #[test]
+    fn test_client_bugs_counted_separately_from_injected_errors() {
+        let mut data = TuiData::new(clock::system(), HistoryConfig::default(), Vec::new(), false);
+        data.push_log(RequestLog {
+            status: 503,
+            outcome: Outcome::InjectedError,
+            ..Default::default()
+        });
+        data.push_log(RequestLog {
+            status: 400,
+            outcome: Outcome::ValidationError("missing required request body".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(data.all.injected_errors, 1);
+        assert_eq!(data.all.client_bugs, 1);
+    }
+
+    #[test]
+    fn test_rate_limit_summary() {
+        let mut stats = Stats::new(HistoryConfig::default());
+        assert_eq!(stats.rate_limit_summary(), None);
+        stats.update_rps(Duration::from_secs(100));
+        for (outcome, backoff_ms, early_retry) in [
+            (Outcome::Served, None, false),
+            (Outcome::RateLimited, None, false),
+            (Outcome::RateLimited, Some(200.0), true),
+            (Outcome::Served, Some(1000.0), false),
+        ] {
+            stats.push_log(&RequestLog {
+                outcome,
+                context: RequestContext {
+                    backoff_ms,
+                    early_retry,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+        }
+        // The summary describes the last full second
+        stats.update_rps(Duration::from_secs(101));
+        assert_eq!(
+            stats.rate_limit_summary().unwrap(),
+            "Allowed: 2/s, Throttled: 2/s (2 total)\nBack-off: avg 600 ms, 1/2 retries early"
+        );
+    }
+
+    #[test]
+    fn test_update_workers_sums_listener_gauges() {
+        let listeners: Vec<ListenerInfo> = [QueueOverflow::Reject, QueueOverflow::Wait]
+            .into_iter()
+            .enumerate()
+            .map(|(i, overflow)| ListenerInfo {
+                name: format!("listener{}", i),
+                address: format!("unix:/tmp/listener{}.sock", i),
+                upstream: None,
+                bytes_sent: Arc::new(AtomicU64::new(0)),
+                compression: Arc::default(),
+                concurrency: Some(Arc::new(ConcurrencyLimit::new(4, 10, overflow).unwrap())),
+                connections: Arc::default(),
+                controls: Arc::default(),
+            })
+            .collect();
+        let limit = listeners[0].concurrency.clone().unwrap();
+        limit.in_flight.store(4, Ordering::Relaxed);
+        limit.queued.store(3, Ordering::Relaxed);
+        limit.rejected.store(2, Ordering::Relaxed);
+        let mut data = TuiData::new(clock::system(), HistoryConfig::default(), listeners, false);
+        data.update_workers();
+
+        let workers = data.by_listener["listener0"].workers.unwrap();
+        assert_eq!((workers.in_flight, workers.queued), (4, 3));
+        assert_eq!(workers.queue_size, Some(10));
+        // The second listener's queue has no bound, so neither has the total
+        let all = data.all.workers.unwrap();
+        assert_eq!((all.max, all.rejected, all.queue_size), (8, 2, None));
+    }
+
+    #[test]
+    fn test_update_connections() {
+        let listeners: Vec<ListenerInfo> = ["payments", "users"]
+            .iter()
+            .map(|name| ListenerInfo {
+                name: name.to_string(),
+                address: format!("unix:/tmp/{}.sock", name),
+                upstream: None,
+                bytes_sent: Arc::new(AtomicU64::new(0)),
+                compression: Arc::default(),
+                concurrency: None,
+                connections: Arc::default(),
+                controls: Arc::default(),
+            })
+            .collect();
+        let payments = listeners[0].connections.clone();
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let mut data = TuiData::new(clock.clone(), HistoryConfig::default(), listeners, false);
+
+        // One client opens 3 connections and sends 12 requests over them
+        let streams: Vec<_> = (0..3).map(|_| payments.track(())).collect();
+        let requests: Vec<_> = (0..12).map(|_| payments.request("10.0.0.1")).collect();
+        drop(requests);
+        let _in_flight = payments.request("10.0.0.1");
+        data.update_connections();
+        drop(streams);
+        data.update_connections();
+        clock.advance(Duration::from_secs(1));
+        data.update_rps();
+
+        let view = &data.by_listener["payments"];
+        assert_eq!(
+            view.connection_summary(),
+            "Connections: 0 open, 3 total\nOpened/Closed: 3/s, 3/s\nReqs/Conn: 4.3, In Flight: 1"
+        );
+        assert_eq!(view.open_history.values(Duration::from_secs(60))[0], 3.0);
+        assert_eq!(data.all.connections, view.connections);
+        assert_eq!(
+            data.by_listener["users"].connection_summary(),
+            "Connections: 0 open, 0 total\nOpened/Closed: 0/s, 0/s\nReqs/Conn: -, In Flight: 0"
+        );
+    }
+
+    #[test]
+    fn test_client_breakdown() {
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let mut data = TuiData::new(clock.clone(), HistoryConfig::default(), Vec::new(), false);
+        let log = |client: &str, status, duration_ms| RequestLog {
+            status,
+            duration_ms,
+            context: RequestContext {
+                client: client.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        data.update_rps();
+        for _ in 0..3 {
+            data.push_log(log("10.0.0.1", 200, 10.0));
+        }
+        data.push_log(log("billing", 503, 40.0));
+        data.push_log(log("billing", 200, 20.0));
+        clock.advance(Duration::from_secs(1));
+        data.update_rps();
+        data.push_log(log("billing", 0, 5.0));
+        data.all.set_clients_in_flight(&HashMap::from([
+            ("billing".to_string(), 2),
+            ("reports".to_string(), 1),
+        ]));
+
+        let breakdown = data.all.client_breakdown();
+        let names: Vec<&str> = breakdown.iter().map(|(name, _)| name.as_str()).collect();
+        // A client with requests in flight shows up before its first response
+        assert_eq!(names, vec!["10.0.0.1", "billing", "reports"]);
+        let billing = breakdown[1].1;
+        assert_eq!(billing.requests, 3);
+        assert_eq!(billing.rps(1), 2);
+        assert_eq!(billing.in_flight, 2);
+        assert!((billing.error_rate() - 66.7).abs() < 0.1);
+        assert_eq!(billing.max_ms, 40.0);
+        assert_eq!(breakdown[0].1.rps(1), 3);
+        assert_eq!(breakdown[0].1.rps(5), 0);
+    }
+
+    #[test]
+    fn test_summary_text() {
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let mut data = TuiData::new(clock.clone(), HistoryConfig::default(), Vec::new(), false);
+        data.update_rps();
+        for (status, duration_ms) in [(200, 10.0), (200, 30.0), (503, 20.0)] {
+            data.push_log(RequestLog {
+                status,
+                duration_ms,
+                ..Default::default()
+            });
+        }
+        clock.advance(Duration::from_secs(2));
+        data.update_rps();
+        assert_eq!(
+            summary_text(&data),
+            "RPS: 0 | Requests: 3 | Errors: 1 | Delay: avg 20.0 ms, max 30.0 ms | Open: 0 | Uptime: 2s"
+        );
+    }
+
+    #[test]
+    fn test_runtime_controls() {
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let mut data = TuiData::new(clock.clone(), HistoryConfig::default(), Vec::new(), false);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        data.control_tx = Some(tx);
+        data.update_rps();
+        data.push_log(RequestLog {
+            status: 503,
+            outcome: Outcome::Paused,
+            ..Default::default()
+        });
+
+        // An invalid delay keeps the prompt open
+        data.prompt = Some(String::new());
+        for key in [KeyCode::Char('9'), KeyCode::Char('-'), KeyCode::Enter] {
+            data.edit_prompt(key);
+        }
+        assert!(data.prompt.is_some() && data.prompt_error.is_some());
+        for key in [KeyCode::Char('1'), KeyCode::Char('0'), KeyCode::Enter] {
+            data.edit_prompt(key);
+        }
+        assert_eq!(data.prompt, None);
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            ControlEvent {
+                listener: None,
+                command: Command::SetDelay(9, 10)
+            }
+        );
+
+        clock.advance(Duration::from_secs(3));
+        data.update_rps();
+        data.reset_stats();
+        assert_eq!(data.logs.len(), 0);
+        assert_eq!(data.all.total_requests, 0);
+        assert_eq!(
+            summary_text(&data),
+            "RPS: 0 | Requests: 0 | Errors: 0 | Delay: avg 0.0 ms, max 0.0 ms | Open: 0 | Uptime: 3s"
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let mut data = TuiData::new(clock.clone(), HistoryConfig::default(), Vec::new(), false);
+        data.seed = Some(7);
+        data.update_rps();
+        for second in 0..5 {
+            for (status, duration_ms) in [(200, 10.0), (200, 30.0), (503, 200.0)] {
+                data.push_log(RequestLog {
+                    path: "/orders".to_string(),
+                    status,
+                    duration_ms,
+                    ..Default::default()
+                });
+            }
+            if second == 2 {
+                data.push_log(RequestLog {
+                    path: "/health".to_string(),
+                    status: 200,
+                    duration_ms: 1.0,
+                    ..Default::default()
+                });
+            }
+            clock.advance(Duration::from_secs(1));
+            data.update_rps();
+        }
+        let settings = [("Delay".to_string(), "0 ms".to_string())];
+        let report = data.report(&settings);
+
+        assert_eq!(
+            report.settings[1..],
+            [
+                ("Seed".to_string(), "7".to_string()),
+                ("Delay".to_string(), "0 ms".to_string())
+            ]
+        );
+        assert_eq!(
+            report.summary[..4],
+            [
+                ("Duration".to_string(), "5s".to_string()),
+                ("Requests".to_string(), "16".to_string()),
+                (
+                    "Requests per second".to_string(),
+                    "avg 3.2, peak 4".to_string()
+                ),
+                ("Errors".to_string(), "5 (31.2%)".to_string()),
+            ]
+        );
+        // The timeline covers the five seconds so far, in the 10s window's 1s buckets
+        let rps = &report.charts[0];
+        assert_eq!((rps.start, rps.bucket), (0.0, 1.0));
+        assert_eq!(
+            rps.lines[0].values,
+            [3.0, 3.0, 4.0, 3.0, 3.0].map(Some).to_vec()
+        );
+        assert_eq!(report.charts[1].lines[2].values[2], Some(200.0));
+
+        let table = |title: &str| {
+            report
+                .tables
+                .iter()
+                .find(|table| table.title == title)
+                .unwrap()
+                .rows
+                .clone()
+        };
+        assert_eq!(
+            table("Percentiles"),
+            [[
+                "all", "16", "30.2", "200.0", "200.0", "200.0", "200.0", "3", "4", "4"
+            ]
+            .map(String::from)
+            .to_vec()]
+        );
+        assert_eq!(
+            table("Statuses"),
+            [
+                ["200", "11", "68.8%"].map(String::from).to_vec(),
+                ["503", "5", "31.2%"].map(String::from).to_vec(),
+            ]
+        );
+        assert_eq!(table("Routes")[0][..3], ["/orders", "200", "10"]);
+    }
+
+    #[test]
+    fn test_retry_stats() {
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let mut data = TuiData::new(clock.clone(), HistoryConfig::default(), Vec::new(), false);
+        let log = |key: &str, number, interval_ms, status, injected: bool| RequestLog {
+            status,
+            outcome: if injected {
+                Outcome::InjectedError
+            } else {
+                Outcome::Served
+            },
+            context: RequestContext {
+                attempt: Some(Attempt {
+                    key: key.to_string(),
+                    number,
+                    interval_ms,
+                    order: 0,
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        data.update_rps();
+        assert_eq!(data.all.retry_summary(), None);
+        // A client backing off exponentially after injected errors
+        data.push_log(log("order-1", 1, None, 503, true));
+        data.push_log(log("order-1", 2, Some(100.0), 503, true));
+        data.push_log(log("order-1", 3, Some(200.0), 503, true));
+        data.push_log(log("order-1", 4, Some(400.0), 200, false));
+        // One that hammers away without waiting
+        data.push_log(log("order-2", 1, None, 429, false));
+        for number in 2..=12 {
+            data.push_log(log("order-2", number, Some(2.0), 429, false));
+        }
+        data.push_log(log("order-3", 1, None, 200, false));
+
+        let retried = data.all.retries.retried();
+        let keys: Vec<&str> = retried.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, vec!["order-1", "order-2"]);
+        assert_eq!(retried[0].1.intervals_ms, vec![100.0, 200.0, 400.0]);
+        assert_eq!(retried[1].1.last_status, "429");
+        let patterns: Vec<String> = data
+            .all
+            .retries
+            .backoff_patterns()
+            .into_iter()
+            .map(|(pattern, count)| format!("{} {}", pattern, count))
+            .collect();
+        assert_eq!(patterns, vec!["immediate 1", "exponential 1"]);
+        assert_eq!(data.all.retries.after_injected, 3);
+        assert_eq!(data.all.retries.max_attempts, 12);
+
+        // 14 retries out of 17 requests in the last second
+        clock.advance(Duration::from_secs(1));
+        data.update_rps();
+        assert!(data.all.retry_storm());
+        assert_eq!(
+            data.all.retry_summary().unwrap(),
+            "Retries: 14/s, 14 total RETRY STORM\nAfter Injected Errors: 3, Avg Wait: 52 ms\nBackoff: immediate"
+        );
+        clock.advance(Duration::from_secs(1));
+        data.update_rps();
+        assert!(!data.all.retry_storm());
+    }
+
+    #[test]
+    fn test_scenario_status() {
+        let mut data = TuiData::new(clock::system(), HistoryConfig::default(), Vec::new(), false);
+        assert_eq!(data.scenario_status(), None);
+        let scenario = Scenario::parse(
+            r#"{"phases": [{"name": "warmup", "duration": "10s"}, {"name": "outage", "duration": "5s", "outage": true}]}"#,
+            Conditions::base(&DelayConfig::parse("0").unwrap(), 0.0, 503),
+        )
+        .unwrap();
+        data.scenario = Some(Arc::new(scenario));
+        assert_eq!(
+            data.scenario_status().unwrap(),
+            "Phase: warmup (1/2), next in 10s"
+        );
+        // The scenario just started, so no boundary is on the chart yet
+        assert!(data.phase_markers(10.0).is_empty());
+    }
+
+    #[test]
+    fn test_listener_filter() {
+        let listeners = ["payments", "users"]
+            .iter()
+            .map(|name| ListenerInfo {
+                name: name.to_string(),
+                address: format!("unix:/tmp/{}.sock", name),
+                upstream: None,
+                bytes_sent: Arc::new(AtomicU64::new(0)),
+                compression: Arc::default(),
+                concurrency: None,
+                connections: Arc::default(),
+                controls: Arc::default(),
+            })
+            .collect();
+        let mut data = TuiData::new(clock::system(), HistoryConfig::default(), listeners, false);
+        for (listener, duration_ms) in [("payments", 10.0), ("users", 50.0), ("payments", 30.0)] {
+            data.push_log(RequestLog {
+                listener: listener.to_string(),
+                duration_ms,
+                ..Default::default()
+            });
+        }
+        assert_eq!(data.view().total_requests, 3);
+        assert_eq!(data.visible_logs().count(), 3);
+
+        data.cycle_listener();
+        assert_eq!(data.selected().unwrap().name, "payments");
+        assert_eq!(data.view().total_requests, 2);
+        assert_eq!(data.view().get_max_delay(), 30.0);
+        assert_eq!(data.visible_logs().count(), 2);
+
+        data.cycle_listener();
+        assert_eq!(data.view().total_requests, 1);
+        assert_eq!(data.view().get_avg_delay(), 50.0);
+
+        // Cycling past the last listener returns to the aggregate view
+        data.cycle_listener();
+        assert!(data.selected().is_none());
+        assert_eq!(data.view().total_requests, 3);
+    }
+
+    #[test]
+    fn test_update_throughput() {
+        let listener = ListenerInfo {
+            name: "payments".to_string(),
+            address: "http://127.0.0.1:9001".to_string(),
+            upstream: None,
+            bytes_sent: Arc::new(AtomicU64::new(0)),
+            compression: Arc::default(),
+            concurrency: None,
+            connections: Arc::default(),
+            controls: Arc::default(),
+        };
+        let counter = listener.bytes_sent.clone();
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let mut data = TuiData::new(
+            clock.clone(),
+            HistoryConfig::default(),
+            vec![listener],
+            false,
+        );
+
+        counter.fetch_add(4_000_000, Ordering::Relaxed);
+        // Nothing is sampled until a second has passed
+        clock.advance(Duration::from_millis(500));
+        data.sample_throughput();
+        assert_eq!(data.all.bytes_sent, 0);
+        clock.advance(Duration::from_millis(1500));
+        data.sample_throughput();
+        assert_eq!(data.all.bytes_sent, 4_000_000);
+        assert_eq!(data.all.throughput, 2_000_000.0);
+        assert_eq!(data.by_listener["payments"].throughput, 2_000_000.0);
+
+        // Only bytes sent since the last sample count towards the throughput
+        counter.fetch_add(1_000_000, Ordering::Relaxed);
+        data.update_throughput(Duration::from_secs(1));
+        assert_eq!(data.all.bytes_sent, 5_000_000);
+        assert_eq!(data.all.throughput, 1_000_000.0);
+    }
+
+    #[test]
+    fn test_encoding_summary() {
+        let listener = ListenerInfo {
+            name: "default".to_string(),
+            address: "http://127.0.0.1:31337".to_string(),
+            upstream: None,
+            bytes_sent: Arc::new(AtomicU64::new(0)),
+            compression: Arc::default(),
+            concurrency: None,
+            connections: Arc::default(),
+            controls: Arc::default(),
+        };
+        let compression = listener.compression.clone();
+        let mut data = TuiData::new(
+            clock::system(),
+            HistoryConfig::default(),
+            vec![listener],
+            false,
+        );
+        assert_eq!(data.all.encoding_summary(), "-");
+
+        let gzip = compression.get(Encoding::Gzip);
+        gzip.responses.fetch_add(2, Ordering::Relaxed);
+        gzip.raw_bytes.fetch_add(1000, Ordering::Relaxed);
+        gzip.encoded_bytes.fetch_add(250, Ordering::Relaxed);
+        compression
+            .get(Encoding::Identity)
+            .responses
+            .fetch_add(5, Ordering::Relaxed);
+        data.update_encodings();
+        data.update_workers();
+        assert_eq!(data.all.encoding_summary(), "identity 5, gzip 2 (4.00x)");
+    }
+
+    #[test]
+    fn test_reset_counts_listener_totals_from_zero() {
+        let listener = ListenerInfo {
+            name: "default".to_string(),
+            address: "http://127.0.0.1:31337".to_string(),
+            upstream: None,
+            bytes_sent: Arc::new(AtomicU64::new(0)),
+            compression: Arc::default(),
+            concurrency: None,
+            connections: Arc::default(),
+            controls: Arc::default(),
+        };
+        let (sent, compression, connections) = (
+            listener.bytes_sent.clone(),
+            listener.compression.clone(),
+            listener.connections.clone(),
+        );
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let mut data = TuiData::new(
+            clock.clone(),
+            HistoryConfig::default(),
+            vec![listener],
+            false,
+        );
+        let serve = |bytes: u64| {
+            sent.fetch_add(bytes, Ordering::Relaxed);
+            compression
+                .get(Encoding::Gzip)
+                .responses
+                .fetch_add(1, Ordering::Relaxed);
+            drop(connections.track(()));
+        };
+        let update = |data: &mut TuiData| {
+            data.update_throughput(Duration::from_secs(1));
+            data.update_encodings();
+            data.update_connections();
+        };
+        serve(2_000_000);
+        serve(2_000_000);
+        update(&mut data);
+        assert_eq!(data.all.sent(), 4_000_000);
+
+        data.reset_stats();
+        update(&mut data);
+        for stats in [&data.all, &data.by_listener["default"]] {
+            assert_eq!(stats.sent(), 0);
+            assert_eq!(stats.encoding_summary(), "-");
+            assert_eq!(stats.connection_totals().opened, 0);
+            assert_eq!(stats.rejected(), 0);
+        }
+
+        serve(1_000_000);
+        update(&mut data);
+        assert_eq!(data.all.sent(), 1_000_000);
+        assert_eq!(data.all.encoding_summary(), "gzip 1");
+        assert!(
+            data.by_listener["default"]
+                .connection_summary()
+                .starts_with("Connections: 0 open, 1 total")
+        );
+    }
 }
@@ -0,0 +1,109 @@
+use anyhow::anyhow;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Decode `%XX` escapes in a URL component. Escapes that aren't two hex digits are kept
+/// as they are, and `+` is left alone since it only means a space in query strings.
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(&[high, low]) = bytes.get(i + 1..i + 3)
+            && high.is_ascii_hexdigit()
+            && low.is_ascii_hexdigit()
+        {
+            let hex = |digit: u8| (digit as char).to_digit(16).unwrap() as u8;
+            out.push(hex(high) * 16 + hex(low));
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Split a query string into its decoded parameters; the last of repeated keys wins
+pub fn parse_query(query: &str) -> HashMap<String, String> {
+    let decode = |s: &str| percent_decode(&s.replace('+', " "));
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+/// Parse a duration such as `s`, `250ms`, `2s`, `1m` or `1h`
+pub fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = if number.is_empty() {
+        1.0
+    } else {
+        number
+            .parse()
+            .map_err(|_| anyhow!("Invalid duration '{}'", s))?
+    };
+    let unit = match unit {
+        "ms" => Duration::from_millis(1),
+        "s" => Duration::from_secs(1),
+        "m" => Duration::from_secs(60),
+        "h" => Duration::from_secs(3600),
+        _ => {
+            return Err(anyhow!(
+                "Invalid duration '{}'. Valid units: ms, s, m, h",
+                s
+            ));
+        }
+    };
+    let duration = Duration::try_from_secs_f64(unit.as_secs_f64() * number)
+        .map_err(|_| anyhow!("Duration '{}' is too long", s))?;
+    if duration.is_zero() {
+        return Err(anyhow!("Duration '{}' must be positive", s));
+    }
+    Ok(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%20b"), "a b");
+        assert_eq!(percent_decode("a+b"), "a+b");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%+f%-1%2"), "%+f%-1%2");
+        assert_eq!(percent_decode("%e2%82%ac"), "€");
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query("name=a+b%2Bc&flag&&x=1&x=2");
+        assert_eq!(query["name"], "a b+c");
+        assert_eq!(query["flag"], "");
+        assert_eq!(query["x"], "2");
+        assert_eq!(query.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("s").unwrap(), Duration::from_secs(1));
+        assert_eq!(parse_duration("1.5m").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration(" 2h ").unwrap(), Duration::from_secs(7200));
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("1x").is_err());
+        assert!(parse_duration("1e30s").is_err());
+        assert!(parse_duration(&format!("{}h", "9".repeat(30))).is_err());
+    }
+}